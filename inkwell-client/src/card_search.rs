@@ -0,0 +1,192 @@
+//! Typing a card in by hand, for cards that won't scan. Suggestions come from the
+//! on-device index when it's loaded (so this works offline) and from the server's
+//! catalog otherwise; both rank with [`inkwell_core::search`].
+
+use gloo_net::http::Request;
+use inkwell_core::compact::CompactIndex;
+use inkwell_core::Card;
+use leptos::*;
+use std::rc::Rc;
+
+const SUGGESTIONS: usize = 8;
+/// Pause in typing before the server is asked
+const DEBOUNCE_MS: u32 = 200;
+
+#[derive(serde::Deserialize)]
+struct CardPage {
+    cards: Vec<Card>,
+}
+
+async fn fetch_suggestions(query: &str) -> Result<Vec<Card>, String> {
+    let url = format!(
+        "/api/cards?fuzzy=true&limit={}&q={}",
+        SUGGESTIONS,
+        String::from(js_sys::encode_uri_component(query))
+    );
+    let resp = Request::get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Card search failed: {:?}", e))?;
+    if !resp.ok() {
+        return Err(format!("Card search failed: HTTP {}", resp.status()));
+    }
+    resp.json::<CardPage>()
+        .await
+        .map(|page| page.cards)
+        .map_err(|e| format!("Invalid card search response: {:?}", e))
+}
+
+/// Set (or promo grouping) and number, as printed on the card.
+fn set_and_number(card: &Card) -> String {
+    format!(
+        "{}/{}",
+        card.promo_grouping.as_deref().unwrap_or(&card.set_code),
+        card.card_number
+    )
+}
+
+/// Search box with typeahead; `on_pick` gets the chosen card and whether it's a foil.
+#[component]
+pub fn CardSearch(
+    local_index: StoredValue<Option<Rc<CompactIndex>>>,
+    #[prop(into)] on_pick: Callback<(Card, bool)>,
+) -> impl IntoView {
+    let (query, set_query) = create_signal(String::new());
+    let (suggestions, set_suggestions) = create_signal::<Vec<Card>>(Vec::new());
+    let (highlighted, set_highlighted) = create_signal(0usize);
+    let (foil, set_foil) = create_signal(false);
+    // Bumped on every keystroke so slower, older answers are dropped
+    let (generation, set_generation) = create_signal(0u32);
+
+    let update = move |text: String| {
+        set_query.set(text.clone());
+        set_highlighted.set(0);
+        set_generation.update(|g| *g += 1);
+        let current = generation.get_untracked();
+        if text.trim().is_empty() {
+            set_suggestions.set(Vec::new());
+            return;
+        }
+        let local = local_index.with_value(|index| {
+            index.as_ref().map(|index| {
+                inkwell_core::search::search(&text, &index.cards, SUGGESTIONS)
+                    .into_iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+        });
+        if let Some(cards) = local {
+            set_suggestions.set(cards);
+            return;
+        }
+        spawn_local(async move {
+            gloo_timers::future::TimeoutFuture::new(DEBOUNCE_MS).await;
+            if generation.get_untracked() != current {
+                return;
+            }
+            match fetch_suggestions(&text).await {
+                Ok(cards) if generation.get_untracked() == current => set_suggestions.set(cards),
+                Ok(_) => {}
+                Err(e) => log::warn!("{}", e),
+            }
+        });
+    };
+
+    let pick = move |card: Card| {
+        on_pick.call((card, foil.get_untracked()));
+        set_query.set(String::new());
+        set_suggestions.set(Vec::new());
+        set_generation.update(|g| *g += 1);
+    };
+
+    let on_keydown = move |ev: ev::KeyboardEvent| {
+        let count = suggestions.with_untracked(Vec::len);
+        match ev.key().as_str() {
+            "ArrowDown" if count > 0 => {
+                ev.prevent_default();
+                set_highlighted.update(|i| *i = (*i + 1) % count);
+            }
+            "ArrowUp" if count > 0 => {
+                ev.prevent_default();
+                set_highlighted.update(|i| *i = (*i + count - 1) % count);
+            }
+            "Enter" => {
+                ev.prevent_default();
+                let chosen = suggestions
+                    .with_untracked(|cards| cards.get(highlighted.get_untracked()).cloned());
+                if let Some(card) = chosen {
+                    pick(card);
+                }
+            }
+            "Escape" => {
+                set_suggestions.set(Vec::new());
+            }
+            _ => {}
+        }
+    };
+
+    view! {
+        <div class="max-w-lg w-full relative">
+            <div class="flex flex-row items-center gap-2">
+                <input
+                    type="search"
+                    placeholder="Add by name or set/number, e.g. mickey sorcerer or 1/12"
+                    autocomplete="off"
+                    class="flex-1 min-w-0 px-3 py-2 rounded-lg bg-slate-800 border border-slate-700 text-white text-sm"
+                    on:input=move |ev| update(event_target_value(&ev))
+                    on:keydown=on_keydown
+                    prop:value=query
+                />
+                <label class="flex items-center gap-1 text-xs text-slate-300 cursor-pointer">
+                    <input
+                        type="checkbox"
+                        class="w-4 h-4 rounded border-slate-600 text-purple-600 bg-slate-700"
+                        on:change=move |ev| set_foil.set(event_target_checked(&ev))
+                        prop:checked=foil
+                    />
+                    "Foil"
+                </label>
+            </div>
+            <Show when=move || suggestions.with(|cards| !cards.is_empty())>
+                <ul class="absolute z-40 mt-1 w-full bg-slate-800 border border-slate-700 rounded-xl overflow-hidden shadow-xl">
+                    {move || {
+                        suggestions
+                            .get()
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, card)| {
+                                let label = set_and_number(&card);
+                                let chosen = card.clone();
+                                view! {
+                                    <li
+                                        class=move || format!(
+                                            "flex flex-row items-center gap-3 p-2 cursor-pointer {}",
+                                            if highlighted.get() == i { "bg-slate-700" } else { "hover:bg-slate-700" }
+                                        )
+                                        on:mousedown=move |ev| {
+                                            // Before the input loses focus
+                                            ev.prevent_default();
+                                            pick(chosen.clone());
+                                        }
+                                    >
+                                        <img
+                                            src=format!("/{}", card.image_url.trim_start_matches('/'))
+                                            alt=card.name.clone()
+                                            loading="lazy"
+                                            class="w-8 h-11 object-cover rounded"
+                                        />
+                                        <div class="flex-1 min-w-0">
+                                            <p class="font-bold text-sm truncate">{card.name.clone()}</p>
+                                            <p class="text-xs text-slate-400 truncate">{card.subtitle.clone()}</p>
+                                        </div>
+                                        <span class="text-xs font-mono text-slate-400">{label}</span>
+                                    </li>
+                                }
+                            })
+                            .collect_view()
+                    }}
+                </ul>
+            </Show>
+        </div>
+    }
+}
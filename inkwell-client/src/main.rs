@@ -1,6 +1,11 @@
 use base64::Engine;
 use gloo_net::http::Request;
-use inkwell_core::ScanResult;
+use inkwell_core::{
+    compact::CompactIndex,
+    export::{self, ExportFormat, ExportItem},
+    import::{ImportResult, ImportedItem},
+    ErrorResponse, ScanResult,
+};
 use leptos::*;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -8,7 +13,19 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::MediaStreamConstraints;
 
-#[derive(Clone, Debug, serde::Deserialize)]
+mod card_search;
+mod on_device;
+mod scanned_list;
+mod storage;
+
+use card_search::CardSearch;
+use scanned_list::{ListEdit, ScannedList};
+
+/// How often scans queued while offline are sent again, for when the `online` event
+/// never comes (the network was up but the server wasn't) or the server asked to retry
+const QUEUE_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct LorcastPrices {
     pub usd: Option<String>,
     pub usd_foil: Option<String>,
@@ -19,20 +36,55 @@ pub struct LorcastCard {
     pub prices: LorcastPrices,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum CsvFormat {
-    Standard,
-    Dreamborn,
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct ScannedItem {
     pub card: inkwell_core::Card,
     pub prices: Option<LorcastPrices>,
     pub is_foil: bool,
+    /// Copies of this card; items saved before quantities existed count as one
+    #[serde(default = "one")]
+    pub quantity: u32,
     pub scanned_at: String,
 }
 
+fn one() -> u32 {
+    1
+}
+
+impl ScannedItem {
+    pub fn new(card: inkwell_core::Card, prices: Option<LorcastPrices>) -> Self {
+        Self {
+            card: card.without_descriptors(),
+            prices,
+            is_foil: false,
+            quantity: 1,
+            scanned_at: now_iso(),
+        }
+    }
+
+    /// Market price of one copy in the item's finish, falling back to the normal price
+    /// for foils without one.
+    pub fn unit_price(&self) -> Option<&str> {
+        self.prices.as_ref().and_then(|p| {
+            if self.is_foil {
+                p.usd_foil.as_deref().or(p.usd.as_deref())
+            } else {
+                p.usd.as_deref()
+            }
+        })
+    }
+
+    pub fn export_item(&self) -> ExportItem {
+        ExportItem {
+            card: self.card.without_descriptors(),
+            foil: self.is_foil,
+            quantity: self.quantity,
+            price: self.unit_price().map(str::to_string),
+            scanned_at: self.scanned_at.clone(),
+        }
+    }
+}
+
 #[component]
 pub fn App() -> impl IntoView {
     let video_ref = create_node_ref::<html::Video>();
@@ -42,29 +94,61 @@ pub fn App() -> impl IntoView {
     let (camera_error, set_camera_error) = create_signal::<Option<String>>(None);
     let (logs, set_logs) = create_signal::<Vec<String>>(vec![]);
     let (facing_mode, set_facing_mode) = create_signal("environment".to_string());
-    let (scanned_cards, set_scanned_cards) = create_signal::<Vec<ScannedItem>>(vec![]);
+    // Resume the last session; without local storage the list just isn't saved
+    let resumed = storage::resume(&now_iso())
+        .map_err(|e| log::error!("Session storage unavailable: {}", e))
+        .ok();
+    let (session_id, set_session_id) = create_signal(resumed.as_ref().map(|s| s.id.clone()));
+    let (sessions, set_sessions) = create_signal(storage::list_sessions());
+    let (scanned_cards, set_scanned_cards) = create_signal(
+        resumed
+            .map(|s| storage::load_items(&s.id))
+            .unwrap_or_default(),
+    );
     let (show_logs, set_show_logs) = create_signal(false);
     let (global_total, set_global_total) = create_signal(0u64);
-    let (csv_format, set_csv_format) = create_signal(CsvFormat::Standard);
+    let (export_format, set_export_format) = create_signal("standard".to_string());
+    // Columns of the "template" export format, see `export::Template::parse`
+    let (export_template, set_export_template) =
+        create_signal("Qty=count,Card=full_name,set,number,variant,price".to_string());
     let (scan_status, set_scan_status) = create_signal::<Option<bool>>(None);
+    let (correcting, set_correcting) = create_signal(false);
+    let (label_set, set_label_set) = create_signal(String::new());
+    let (label_number, set_label_number) = create_signal(String::new());
+
+    let (queued_scans, set_queued_scans) = create_signal(storage::queued_scans().len());
+    let (flushing, set_flushing) = create_signal(false);
+
+    // Compact index for identifying cards on-device; `on_device` makes scans use it even
+    // while the server is reachable
+    let local_index = store_value::<Option<Rc<CompactIndex>>>(None);
+    let (index_cards, set_index_cards) = create_signal::<Option<usize>>(None);
+    let (on_device, set_on_device) = create_signal(false);
+    let (index_loading, set_index_loading) = create_signal(false);
+    let has_local_index = move || local_index.with_value(Option::is_some);
+    let identify_on_device = move |canvas: &web_sys::HtmlCanvasElement| {
+        local_index.with_value(|index| match index {
+            Some(index) => on_device::identify(index, canvas).map_err(IdentifyError::Failed),
+            None => Err(IdentifyError::Failed("No on-device index".into())),
+        })
+    };
+
+    // Previous scanned list and what changed it, for a single level of undo
+    let (last_undo, set_last_undo) = create_signal::<Option<(String, Vec<ScannedItem>)>>(None);
 
     let running_total = move || {
         scanned_cards.get().iter().fold(0.0, |acc, item| {
-            let price_str = item
-                .prices
-                .as_ref()
-                .and_then(|p| {
-                    if item.is_foil {
-                        p.usd_foil.as_deref().or(p.usd.as_deref())
-                    } else {
-                        p.usd.as_deref()
-                    }
-                })
-                .unwrap_or("0");
-            let val: f64 = price_str.parse().unwrap_or(0.0);
-            acc + val
+            let val: f64 = item.unit_price().unwrap_or("0").parse().unwrap_or(0.0);
+            acc + val * item.quantity as f64
         })
     };
+    let card_count = move || {
+        scanned_cards
+            .get()
+            .iter()
+            .map(|item| item.quantity as usize)
+            .sum::<usize>()
+    };
 
     // Custom logging helper that goes to console AND screen
     let log_msg = move |msg: String| {
@@ -127,6 +211,24 @@ pub fn App() -> impl IntoView {
         });
     });
 
+    // Save the scanned list whenever it changes (the first run only sees what was loaded)
+    create_effect(move |prev: Option<()>| {
+        let items = scanned_cards.get();
+        let Some(id) = session_id.get_untracked() else {
+            return;
+        };
+        if prev.is_none() {
+            return;
+        }
+        match storage::save_items(&id, &items, &now_iso()) {
+            Ok(()) => {
+                set_sessions.set(storage::list_sessions());
+                sync_session(id);
+            }
+            Err(e) => log_err(format!("Session not saved: {}", e)),
+        }
+    });
+
     let (is_torch_on, set_is_torch_on) = create_signal(false);
 
     let toggle_torch = move |_| {
@@ -351,40 +453,36 @@ pub fn App() -> impl IntoView {
             spawn_local(async move {
                 log_msg(format!("Scanned bytes: {}", bytes.len()));
 
-                // Call API (on port 4000) with raw bytes
-                match Request::post("/api/identify")
-                    .body(bytes) // Send raw bytes
-                    .unwrap()
-                    .send()
-                    .await
-                {
-                    Ok(resp) => {
-                        let result = resp.json::<ScanResult>().await.unwrap();
+                let online = web_sys::window()
+                    .map(|w| w.navigator().on_line())
+                    .unwrap_or(true);
+                let outcome = if on_device.get_untracked() && has_local_index() {
+                    // Let the spinner render before matching blocks the page
+                    gloo_timers::future::TimeoutFuture::new(0).await;
+                    identify_on_device(&crop_canvas)
+                } else if online {
+                    identify(bytes).await
+                } else {
+                    Err(IdentifyError::Offline("browser is offline".into()))
+                };
+                // Without the server, match on-device if we can rather than queueing
+                let outcome = match outcome {
+                    Err(IdentifyError::Offline(msg)) if has_local_index() => {
+                        log_msg(format!("Server unreachable ({}), matching on-device.", msg));
+                        gloo_timers::future::TimeoutFuture::new(0).await;
+                        identify_on_device(&crop_canvas)
+                    }
+                    outcome => outcome,
+                };
+                match outcome {
+                    Ok(result) => {
                         if let Some(card) = result.card.clone() {
                             set_scan_status.set(Some(true));
-                            let lorcast_url = format!(
-                                "https://api.lorcast.com/v0/cards/{}/{}",
-                                card.set_code, card.card_number
-                            );
-                            let prices =
-                                match gloo_net::http::Request::get(&lorcast_url).send().await {
-                                    Ok(res) if res.ok() => {
-                                        res.json::<LorcastCard>().await.ok().map(|lc| lc.prices)
-                                    }
-                                    _ => None,
-                                };
-                            let scanned_at = js_sys::Date::new_0()
-                                .to_iso_string()
-                                .as_string()
-                                .unwrap_or_default();
-
-                            let item = ScannedItem {
-                                card,
-                                prices,
-                                is_foil: false,
-                                scanned_at,
-                            };
-                            set_scanned_cards.update(|list| list.push(item));
+                            let prices = fetch_prices(&card).await;
+                            set_scanned_cards
+                                .update(|list| list.push(ScannedItem::new(card, prices)));
+                            // Restoring an older list would drop this scan
+                            set_last_undo.set(None);
                         } else {
                             set_scan_status.set(Some(false));
                         }
@@ -392,11 +490,42 @@ pub fn App() -> impl IntoView {
                             move || set_scan_status.set(None),
                             std::time::Duration::from_millis(1500),
                         );
-                        set_global_total.set(result.global_total_scans);
+                        // On-device results don't know the global count
+                        if result.global_total_scans > 0 {
+                            set_global_total.set(result.global_total_scans);
+                        }
+                        set_correcting.set(false);
                         set_scan_result.set(Some(result));
                     }
-                    Err(e) => {
-                        log_err(format!("API Request failed: {:?}", e));
+                    Err(IdentifyError::Failed(msg) | IdentifyError::Retryable(msg)) => {
+                        log_err(msg);
+                        set_scan_status.set(Some(false));
+                        set_timeout(
+                            move || set_scan_status.set(None),
+                            std::time::Duration::from_millis(1500),
+                        );
+                    }
+                    Err(IdentifyError::Offline(msg)) => {
+                        // JPEG keeps queued frames small
+                        let frame = crop_canvas
+                            .to_data_url_with_type("image/jpeg")
+                            .unwrap_or(data_url);
+                        let frame = frame
+                            .split_once(',')
+                            .and_then(|(_, data)| {
+                                base64::engine::general_purpose::STANDARD.decode(data).ok()
+                            })
+                            .unwrap_or_default();
+                        let queued =
+                            storage::queue_scan(session_id.get_untracked(), now_iso(), &frame)
+                                .await;
+                        match queued {
+                            Ok(count) => {
+                                log_msg(format!("Offline ({}), scan queued.", msg));
+                                set_queued_scans.set(count);
+                            }
+                            Err(e) => log_err(format!("Offline and could not queue scan: {}", e)),
+                        }
                     }
                 }
                 set_is_scanning.set(false);
@@ -404,14 +533,213 @@ pub fn App() -> impl IntoView {
         }
     };
 
-    let download_csv = move |_| {
+    // Sends scans queued while offline, oldest first, stopping at the first one that
+    // still can't reach the server or that the server asks to send again later
+    let flush_queue = move || {
+        if flushing.get_untracked() || storage::queued_scans().is_empty() {
+            return;
+        }
+        set_flushing.set(true);
+        spawn_local(async move {
+            while let Some(queued) = storage::queued_scans().into_iter().next() {
+                let outcome = match storage::queued_frame(&queued).await {
+                    Ok(Some(bytes)) => identify(bytes).await,
+                    Ok(None) => Err(IdentifyError::Failed(format!(
+                        "Queued scan from {} has no stored frame",
+                        queued.captured_at
+                    ))),
+                    Err(e) => {
+                        log_err(e);
+                        break;
+                    }
+                };
+                match outcome {
+                    Ok(result) => {
+                        set_global_total.set(result.global_total_scans);
+                        if let Some(card) = result.card {
+                            log_msg(format!(
+                                "Queued scan from {}: {} - {}",
+                                queued.captured_at, card.name, card.subtitle
+                            ));
+                            let prices = fetch_prices(&card).await;
+                            let mut item = ScannedItem::new(card, prices);
+                            item.scanned_at = queued.captured_at.clone();
+                            match &queued.session_id {
+                                Some(id) if session_id.get_untracked().as_ref() != Some(id) => {
+                                    match storage::append_item(id, item, &now_iso()) {
+                                        Ok(()) => sync_session(id.clone()),
+                                        Err(e) => log_err(format!("Queued scan not saved: {}", e)),
+                                    }
+                                    set_sessions.set(storage::list_sessions());
+                                }
+                                _ => {
+                                    set_scanned_cards.update(|list| list.push(item));
+                                    set_last_undo.set(None);
+                                }
+                            }
+                        } else {
+                            log_err(format!(
+                                "Queued scan from {} matched no card.",
+                                queued.captured_at
+                            ));
+                        }
+                    }
+                    Err(IdentifyError::Failed(msg)) => log_err(msg),
+                    Err(IdentifyError::Offline(_)) => break,
+                    Err(IdentifyError::Retryable(msg)) => {
+                        log_msg(format!("{}, keeping queued scans for later.", msg));
+                        break;
+                    }
+                }
+                match storage::dequeue_scan().await {
+                    Ok(count) => set_queued_scans.set(count),
+                    Err(e) => {
+                        log_err(e);
+                        break;
+                    }
+                }
+            }
+            set_flushing.set(false);
+        });
+    };
+    flush_queue();
+    let _ = window_event_listener(ev::online, move |_| flush_queue());
+    set_interval(
+        move || {
+            let online = web_sys::window()
+                .map(|w| w.navigator().on_line())
+                .unwrap_or(true);
+            if online {
+                flush_queue();
+            }
+        },
+        QUEUE_RETRY_INTERVAL,
+    );
+
+    spawn_local(async move {
+        match on_device::load_cached().await {
+            Ok(Some(index)) => {
+                set_index_cards.set(Some(index.cards.len()));
+                local_index.set_value(Some(Rc::new(index)));
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("No on-device index: {}", e),
+        }
+    });
+
+    let toggle_on_device = move |_| {
+        if on_device.get_untracked() {
+            set_on_device.set(false);
+            log_msg("On-device matching off.".into());
+            return;
+        }
+        // Refresh the index when turning it on, keeping the cached one if that fails
+        set_index_loading.set(true);
+        spawn_local(async move {
+            match on_device::download().await {
+                Ok(index) => {
+                    log_msg(format!(
+                        "On-device index: {} cards, {} features.",
+                        index.cards.len(),
+                        index.descriptor_count()
+                    ));
+                    set_index_cards.set(Some(index.cards.len()));
+                    local_index.set_value(Some(Rc::new(index)));
+                }
+                Err(e) => log_err(format!("Could not update on-device index: {}", e)),
+            }
+            set_index_loading.set(false);
+            if has_local_index() {
+                set_on_device.set(true);
+                log_msg("On-device matching on.".into());
+            }
+        });
+    };
+
+    // Reports the card a scan actually showed, fixing up the scanned list to match
+    let submit_label = move |scan_id: i64| {
+        let set_code = label_set.get_untracked().trim().to_string();
+        let Ok(card_number) = label_number.get_untracked().trim().parse::<u32>() else {
+            log_err("Enter the set and collector number printed on the card.".into());
+            return;
+        };
+        let wrong_card_id = scan_result
+            .get_untracked()
+            .and_then(|res| res.card)
+            .map(|card| card.id);
+
+        spawn_local(async move {
+            let request = match Request::post(&format!("/api/scans/{}/label", scan_id))
+                .json(&serde_json::json!({ "set_code": set_code, "card_number": card_number }))
+            {
+                Ok(request) => request,
+                Err(e) => {
+                    log_err(format!("Invalid correction: {:?}", e));
+                    return;
+                }
+            };
+            match request.send().await {
+                Ok(resp) if resp.ok() => {
+                    let card = match resp.json::<inkwell_core::Card>().await {
+                        Ok(card) => card,
+                        Err(e) => {
+                            log_err(format!("Invalid correction response: {:?}", e));
+                            return;
+                        }
+                    };
+                    log_msg(format!(
+                        "Scan corrected to {} - {}",
+                        card.name, card.subtitle
+                    ));
+                    let prices = fetch_prices(&card).await;
+                    set_scanned_cards.update(|list| match list.last_mut() {
+                        Some(last) if wrong_card_id.as_deref() == Some(last.card.id.as_str()) => {
+                            last.card = card.clone();
+                            last.prices = prices;
+                        }
+                        _ => list.push(ScannedItem::new(card.clone(), prices)),
+                    });
+                    set_scan_result.update(|res| {
+                        if let Some(res) = res {
+                            res.card = Some(card);
+                        }
+                    });
+                    set_correcting.set(false);
+                }
+                Ok(resp) => {
+                    let status = resp.status();
+                    match resp.json::<serde_json::Value>().await {
+                        Ok(json) if json["error"].is_string() => log_err(format!(
+                            "Correction failed: {}",
+                            json["error"].as_str().unwrap_or_default()
+                        )),
+                        _ => log_err(format!("Correction failed: HTTP {}", status)),
+                    }
+                }
+                Err(e) => log_err(format!("API Request failed: {:?}", e)),
+            }
+        });
+    };
+
+    let download_export = move |_| {
         let cards = scanned_cards.get();
         if cards.is_empty() {
             log_err("No cards to export!".into());
             return;
         }
 
-        let csv_content = generate_csv(&cards, csv_format.get());
+        let format = match ExportFormat::from_name(
+            &export_format.get_untracked(),
+            Some(&export_template.get_untracked()),
+        ) {
+            Ok(format) => format,
+            Err(e) => {
+                log_err(format!("Export failed: {}", e));
+                return;
+            }
+        };
+        let items: Vec<ExportItem> = cards.iter().map(ScannedItem::export_item).collect();
+        let content = export::export(&items, &format);
 
         // Trigger download
         let window = web_sys::window().unwrap();
@@ -419,10 +747,10 @@ pub fn App() -> impl IntoView {
         let body = document.body().unwrap();
 
         let blob_parts = js_sys::Array::new();
-        blob_parts.push(&JsValue::from_str(&csv_content));
+        blob_parts.push(&JsValue::from_str(&content));
 
         let blob_props = web_sys::BlobPropertyBag::new();
-        blob_props.set_type("text/csv");
+        blob_props.set_type(format.content_type());
 
         let blob =
             web_sys::Blob::new_with_str_sequence_and_options(&blob_parts, &blob_props).unwrap();
@@ -433,22 +761,165 @@ pub fn App() -> impl IntoView {
             .unwrap()
             .unchecked_into::<web_sys::HtmlAnchorElement>();
         anchor.set_href(&url);
-        anchor.set_download("inkwell_matches.csv");
+        anchor.set_download(&format!("inkwell_matches.{}", format.file_extension()));
         anchor.style().set_property("display", "none").unwrap();
         body.append_child(&anchor).unwrap();
         anchor.click();
         body.remove_child(&anchor).unwrap();
         web_sys::Url::revoke_object_url(&url).unwrap();
 
-        log_msg("CSV Download triggered.".into());
+        log_msg(format!("{} download triggered.", format.label()));
+    };
+
+    // Applies an edit to the scanned list, keeping the previous list for undo
+    let edit_list = move |action: &str, edit: &dyn Fn(&mut Vec<ScannedItem>)| {
+        let before = scanned_cards.get_untracked();
+        set_scanned_cards.update(|list| edit(list));
+        set_last_undo.set(Some((action.to_string(), before)));
+    };
+
+    let import_csv = move |ev: ev::Event| {
+        let input = event_target::<web_sys::HtmlInputElement>(&ev);
+        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        // Lets the same file be picked again
+        input.set_value("");
+        log_msg(format!("Importing {}...", file.name()));
+
+        spawn_local(async move {
+            let text = match wasm_bindgen_futures::JsFuture::from(file.text()).await {
+                Ok(text) => text.as_string().unwrap_or_default(),
+                Err(e) => {
+                    log_err(format!("Could not read {}: {:?}", file.name(), e));
+                    return;
+                }
+            };
+            let result = match resolve_import(text).await {
+                Ok(result) => result,
+                Err(e) => {
+                    log_err(e);
+                    return;
+                }
+            };
+
+            let copies: u32 = result.items.iter().map(|item| item.count).sum();
+            let now = now_iso();
+            edit_list("import", &|list| {
+                merge_imported(list, &result.items, &now);
+            });
+            log_msg(format!(
+                "Imported {} cards from {} rows ({:?} CSV).",
+                copies,
+                result.items.len(),
+                result.format
+            ));
+            for row in &result.unresolved {
+                log_err(format!(
+                    "Line {} not imported: {} ({})",
+                    row.line, row.reason, row.text
+                ));
+            }
+        });
+    };
+
+    let undo = move |_| {
+        if let Some((action, items)) = last_undo.get_untracked() {
+            set_scanned_cards.set(items);
+            set_last_undo.set(None);
+            log_msg(format!("Undid {}.", action));
+        }
     };
 
     let reset_session = move |_| {
-        set_scanned_cards.set(vec![]);
+        edit_list("reset", &|list| list.clear());
         set_scan_result.set(None);
         log_msg("Session reset.".into());
     };
 
+    let switch_session = move |id: String| {
+        if let Err(e) = storage::set_current(&id) {
+            log_err(e);
+            return;
+        }
+        let items = storage::load_items(&id);
+        log_msg(format!("Switched session ({} cards).", items.len()));
+        set_session_id.set(Some(id));
+        set_scanned_cards.set(items);
+        set_scan_result.set(None);
+        set_last_undo.set(None);
+    };
+
+    let new_session = move |_| match storage::create_session(&now_iso()) {
+        Ok(meta) => {
+            log_msg(format!("Started {}.", meta.name));
+            set_session_id.set(Some(meta.id));
+            set_scanned_cards.set(vec![]);
+            set_scan_result.set(None);
+            set_last_undo.set(None);
+            set_sessions.set(storage::list_sessions());
+        }
+        Err(e) => log_err(e),
+    };
+
+    // A card typed in by hand is added just like a scan of it
+    let add_manual = move |(card, foil): (inkwell_core::Card, bool)| {
+        spawn_local(async move {
+            log_msg(format!("Added by hand: {} - {}", card.name, card.subtitle));
+            let prices = fetch_prices(&card).await;
+            let mut item = ScannedItem::new(card, prices);
+            item.is_foil = foil;
+            set_scanned_cards.update(|list| list.push(item));
+            set_last_undo.set(None);
+        });
+    };
+
+    let (syncing, set_syncing) = create_signal(storage::sync_token().is_some());
+    let set_sync = move |_| {
+        let current = storage::sync_token().unwrap_or_default();
+        let Some(token) = web_sys::window().and_then(|w| {
+            w.prompt_with_message_and_default(
+                "Server write token for syncing sessions (leave empty to stop syncing)",
+                &current,
+            )
+            .ok()
+            .flatten()
+        }) else {
+            return;
+        };
+        if let Err(e) = storage::set_sync_token(token.trim()) {
+            log_err(e);
+            return;
+        }
+        set_syncing.set(storage::sync_token().is_some());
+        if let Some(id) = session_id.get_untracked() {
+            sync_session(id);
+        }
+    };
+
+    let delete_session = move |_| {
+        let Some(id) = session_id.get_untracked() else {
+            return;
+        };
+        let confirmed = web_sys::window()
+            .and_then(|w| w.confirm_with_message("Delete this session?").ok())
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        if let Err(e) = storage::delete_session(&id) {
+            log_err(e);
+            return;
+        }
+        unsync_session(id.clone());
+        match storage::resume(&now_iso()) {
+            Ok(meta) => switch_session(meta.id),
+            Err(e) => log_err(e),
+        }
+        set_sessions.set(storage::list_sessions());
+        log_msg("Session deleted.".into());
+    };
+
     view! {
         <div class="flex flex-col items-center gap-3 sm:gap-4 p-2 sm:p-4 text-white bg-slate-900 min-h-screen relative pb-16">
             <h1 class="text-2xl sm:text-3xl font-bold bg-gradient-to-r from-purple-400 to-pink-600 bg-clip-text text-transparent pt-2">
@@ -458,14 +929,86 @@ pub fn App() -> impl IntoView {
             <div class="text-lg sm:text-xl font-bold text-emerald-400 bg-slate-800 px-4 sm:px-6 py-2 rounded-full border border-slate-700 shadow-lg mb-1 sm:mb-2 text-center">
                 "Session Total: $"
                 {move || format!("{:.2}", running_total())}
-                <span class="text-xs text-slate-500 ml-2">{move || format!("({} cards)", scanned_cards.get().len())}</span>
+                <span class="text-xs text-slate-500 ml-2">{move || format!("({} cards)", card_count())}</span>
             </div>
 
+            {move || session_id.get().is_some().then(|| view! {
+                <div class="flex flex-row items-center gap-2 w-full max-w-lg px-2 sm:px-0 text-sm">
+                    <select
+                        on:change=move |ev| switch_session(event_target_value(&ev))
+                        class="flex-1 min-w-0 py-2 px-3 bg-slate-800 text-white border border-slate-700 rounded-full outline-none cursor-pointer"
+                        title="Saved Sessions"
+                    >
+                        {move || {
+                            let current = session_id.get();
+                            sessions.get().into_iter().map(|s| {
+                                let selected = current.as_deref() == Some(s.id.as_str());
+                                view! {
+                                    <option value=s.id selected=selected>
+                                        {format!("{} ({} cards)", s.name, s.count)}
+                                    </option>
+                                }
+                            }).collect_view()
+                        }}
+                    </select>
+                    <button
+                        on:click=new_session
+                        class="px-4 py-2 bg-slate-800 hover:bg-slate-700 rounded-full font-bold border border-slate-700"
+                        title="New Session"
+                    >
+                        "New"
+                    </button>
+                    <button
+                        on:click=set_sync
+                        class=move || format!(
+                            "px-4 py-2 bg-slate-800 hover:bg-slate-700 rounded-full font-bold border border-slate-700 {}",
+                            if syncing.get() { "text-emerald-400" } else { "text-slate-400" }
+                        )
+                        title="Sync sessions to the server"
+                    >
+                        "Sync"
+                    </button>
+                    <button
+                        on:click=delete_session
+                        class="px-4 py-2 bg-slate-800 hover:bg-red-900/50 text-red-400 rounded-full font-bold border border-slate-700"
+                        title="Delete Session"
+                    >
+                        "Delete"
+                    </button>
+                </div>
+            })}
+
             <div class="text-xs sm:text-sm font-bold text-purple-300 bg-slate-800/50 px-4 py-1 rounded-full border border-slate-700 tracking-wide mb-2 sm:mb-4 text-center">
                 "Global Scans: "
                 <span class="text-white">{move || global_total.get()}</span>
             </div>
 
+            <button
+                on:click=toggle_on_device
+                disabled=index_loading
+                class=move || format!(
+                    "text-xs font-bold px-4 py-1 rounded-full border transition-all {}",
+                    if on_device.get() { "bg-purple-600 border-purple-400 text-white" } else { "bg-slate-800 border-slate-700 text-slate-400 hover:text-white" }
+                )
+                title="Identify cards on this device, without the server"
+            >
+                {move || match (index_loading.get(), on_device.get(), index_cards.get()) {
+                    (true, _, _) => "Downloading card index...".to_string(),
+                    (false, true, Some(cards)) => format!("On-device matching: ON ({} cards)", cards),
+                    _ => "On-device matching: OFF".to_string(),
+                }}
+            </button>
+
+            {move || (queued_scans.get() > 0).then(|| view! {
+                <div class="text-xs font-bold text-amber-300 bg-amber-900/30 px-4 py-1 rounded-full border border-amber-700/50 text-center">
+                    {move || format!(
+                        "{} scans waiting for a connection{}",
+                        queued_scans.get(),
+                        if flushing.get() { " - sending..." } else { "" }
+                    )}
+                </div>
+            })}
+
             <div class=move || format!(
                 "relative rounded-2xl overflow-hidden border-4 shadow-2xl max-w-lg w-full transition-colors duration-500 {}",
                 match scan_status.get() {
@@ -526,24 +1069,33 @@ pub fn App() -> impl IntoView {
                 <div class="flex flex-wrap sm:flex-nowrap w-full sm:w-auto gap-2 sm:gap-4 justify-between">
                     <div class="w-full sm:w-auto flex flex-row items-stretch overflow-hidden rounded-2xl sm:rounded-full shadow-lg shadow-emerald-500/20 transform transition-all hover:scale-105 relative z-40">
                         <select
-                            on:change=move |ev| {
-                                let val = event_target_value(&ev);
-                                set_csv_format.set(if val == "dreamborn" { CsvFormat::Dreamborn } else { CsvFormat::Standard });
-                            }
+                            on:change=move |ev| set_export_format.set(event_target_value(&ev))
                             class="flex-1 sm:flex-none py-3 sm:py-2 px-3 sm:px-2 bg-emerald-700 text-white border-r border-emerald-600 outline-none text-xs font-bold text-center cursor-pointer appearance-none min-w-[80px]"
                         >
                             <option value="standard">"Standard CSV"</option>
                             <option value="dreamborn">"Dreamborn CSV"</option>
+                            <option value="json">"JSON"</option>
+                            <option value="tcgplayer">"TCGplayer CSV"</option>
+                            <option value="cardmarket">"Cardmarket CSV"</option>
+                            <option value="lorcast">"Lorcast CSV"</option>
+                            <option value="template">"Custom CSV"</option>
                         </select>
                         <button
-                            on:click=download_csv
+                            on:click=download_export
                             class="flex-none px-6 sm:px-4 flex flex-row justify-center items-center gap-2 py-3 sm:py-2 bg-emerald-600 hover:bg-emerald-500 font-bold transition-all"
                         >
                             <svg xmlns="http://www.w3.org/2000/svg" class="w-5 h-5 sm:w-5 sm:h-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
                                 <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M4 16v1a3 3 0 003 3h10a3 3 0 003-3v-1m-4-4l-4 4m0 0l-4-4m4 4V4" />
                             </svg>
-                            <span class="text-[10px] sm:text-base uppercase whitespace-nowrap">"CSV"</span>
+                            <span class="text-[10px] sm:text-base uppercase whitespace-nowrap">"Export"</span>
                         </button>
+                        <label
+                            class="flex-none px-4 sm:px-3 flex justify-center items-center py-3 sm:py-2 bg-emerald-700 hover:bg-emerald-600 border-l border-emerald-600 font-bold transition-all cursor-pointer"
+                            title="Import a Standard or Dreamborn CSV into this session"
+                        >
+                            <input type="file" accept=".csv,text/csv" class="hidden" on:change=import_csv />
+                            <span class="text-[10px] sm:text-base uppercase whitespace-nowrap">"Import"</span>
+                        </label>
                     </div>
 
                     <button
@@ -581,6 +1133,18 @@ pub fn App() -> impl IntoView {
                 </div>
             </div>
 
+            {move || (export_format.get() == "template").then(|| view! {
+                <div class="max-w-lg w-full mt-2 px-2 sm:px-0">
+                    <input
+                        type="text"
+                        prop:value=export_template
+                        on:input=move |ev| set_export_template.set(event_target_value(&ev))
+                        class="w-full bg-slate-800 border border-slate-700 rounded-xl px-3 py-2 text-xs font-mono text-white outline-none focus:border-emerald-500"
+                        title="Comma-separated columns, each a field or Header=field. Fields: id, name, subtitle, full_name, set, group, number, rarity, variant, count, price, scanned_at, image_url"
+                    />
+                </div>
+            })}
+
             <div class="max-w-lg w-full mt-4">
                 {move || scan_result.get().map(|res| {
                     if let Some(card) = res.card {
@@ -603,7 +1167,7 @@ pub fn App() -> impl IntoView {
                                             let is_currently_foil = last_item.is_foil;
 
                                             let toggle_foil = move |_| {
-                                                set_scanned_cards.update(|list| {
+                                                edit_list("foil change", &|list| {
                                                     if let Some(last_mut) = list.last_mut() {
                                                         last_mut.is_foil = !last_mut.is_foil;
                                                     }
@@ -644,8 +1208,71 @@ pub fn App() -> impl IntoView {
                         }
                     }
                 })}
+                {move || scan_result.get().and_then(|res| res.scan_id).map(|scan_id| {
+                    if correcting.get() {
+                        view! {
+                            <form
+                                class="mt-3 flex flex-row items-center gap-2 text-sm"
+                                on:submit=move |ev| {
+                                    ev.prevent_default();
+                                    submit_label(scan_id);
+                                }
+                            >
+                                <input
+                                    type="text"
+                                    placeholder="Set"
+                                    class="w-16 px-2 py-2 rounded-lg bg-slate-800 border border-slate-700 text-white"
+                                    on:input=move |ev| set_label_set.set(event_target_value(&ev))
+                                    prop:value=label_set
+                                />
+                                <input
+                                    type="number"
+                                    placeholder="Number"
+                                    class="w-24 px-2 py-2 rounded-lg bg-slate-800 border border-slate-700 text-white"
+                                    on:input=move |ev| set_label_number.set(event_target_value(&ev))
+                                    prop:value=label_number
+                                />
+                                <button type="submit" class="px-4 py-2 bg-purple-600 hover:bg-purple-700 rounded-lg font-bold">
+                                    "Save"
+                                </button>
+                                <button
+                                    type="button"
+                                    class="px-3 py-2 text-slate-400 hover:text-white"
+                                    on:click=move |_| set_correcting.set(false)
+                                >
+                                    "Cancel"
+                                </button>
+                            </form>
+                        }.into_view()
+                    } else {
+                        view! {
+                            <button
+                                class="mt-3 text-sm text-slate-400 hover:text-white underline"
+                                on:click=move |_| set_correcting.set(true)
+                            >
+                                "Wrong card? Tell us which one it was"
+                            </button>
+                        }.into_view()
+                    }
+                })}
             </div>
 
+            {move || last_undo.get().map(|(action, _)| view! {
+                <button
+                    on:click=undo
+                    class="max-w-lg w-full py-2 bg-slate-800 hover:bg-slate-700 rounded-full text-sm font-bold border border-slate-700"
+                >
+                    {format!("Undo {}", action)}
+                </button>
+            })}
+
+            <CardSearch local_index=local_index on_pick=add_manual />
+
+            <ScannedList
+                items=scanned_cards
+                on_edit=move |edit: ListEdit| edit_list(edit.label(), &|list| edit.apply(list))
+            />
+
             // Debug Logs Overlay
             <div class=move || format!(
                 "fixed bottom-0 left-0 right-0 bg-black/95 text-green-400 font-mono text-xs transition-all duration-500 z-50 flex flex-col border-t border-slate-700 transform {}",
@@ -687,193 +1314,225 @@ fn main() {
     leptos::mount_to_body(App);
 }
 
-pub fn generate_csv(items: &[ScannedItem], format: CsvFormat) -> String {
-    match format {
-        CsvFormat::Dreamborn => {
-            let mut csv = String::from("Set Number,Card Number,Variant,Count\n");
-
-            let mut rows: Vec<((String, u32, bool), usize)> = Vec::new();
-
-            for item in items {
-                let card = &item.card;
-
-                let group_key = card
-                    .promo_grouping
-                    .clone()
-                    .unwrap_or_else(|| format!("0{}", card.set_code));
-
-                let key = (group_key.clone(), card.card_number, item.is_foil);
+/// Why an identify request produced no result.
+enum IdentifyError {
+    /// The request never reached the server; worth retrying once back online
+    Offline(String),
+    /// The server couldn't answer just now (still loading its index, overloaded); the
+    /// same request may succeed later
+    Retryable(String),
+    Failed(String),
+}
 
-                if let Some(pos) = rows.iter().position(|row| row.0 == key) {
-                    rows[pos].1 += 1;
-                } else {
-                    rows.push((key, 1));
-                }
+async fn identify(bytes: Vec<u8>) -> Result<ScanResult, IdentifyError> {
+    let request = Request::post("/api/identify")
+        .body(bytes)
+        .map_err(|e| IdentifyError::Failed(format!("Invalid identify request: {:?}", e)))?;
+    let resp = request
+        .send()
+        .await
+        .map_err(|e| IdentifyError::Offline(format!("API Request failed: {:?}", e)))?;
+
+    if !resp.ok() {
+        let status = resp.status();
+        return Err(match resp.json::<ErrorResponse>().await {
+            Ok(err) if err.retryable => IdentifyError::Retryable(format!(
+                "Identify failed ({}): {} - please try again",
+                err.code, err.message
+            )),
+            Ok(err) => {
+                IdentifyError::Failed(format!("Identify failed ({}): {}", err.code, err.message))
             }
-
-            for (key, count) in rows {
-                let variant = if key.2 { "foil" } else { "normal" };
-                csv.push_str(&format!("{},{},{},{}\n", key.0, key.1, variant, count));
+            // A proxy in front of the server, which is down or restarting
+            Err(_) if status == 429 || status >= 502 => {
+                IdentifyError::Retryable(format!("Identify failed: HTTP {}", status))
             }
+            Err(_) => IdentifyError::Failed(format!("Identify failed: HTTP {}", status)),
+        });
+    }
+    resp.json::<ScanResult>()
+        .await
+        .map_err(|e| IdentifyError::Failed(format!("Invalid identify response: {:?}", e)))
+}
 
-            csv
-        }
-        CsvFormat::Standard => {
-            let mut csv = String::from(
-                "Set Number,Card Number,Variant,Count,Card Name,Rarity,Price,ScannedAt\n",
-            );
-
-            let mut rows: Vec<((String, u32, bool), usize, String, String, String, String)> =
-                Vec::new();
-
-            for item in items {
-                let card = &item.card;
-                let full_name = if card.subtitle.is_empty() {
-                    card.name.clone()
-                } else {
-                    format!("{} - {}", card.name, card.subtitle)
-                };
-
-                let escaped_name = if full_name.contains(',') || full_name.contains('"') {
-                    format!("\"{}\"", full_name.replace('"', "\"\""))
-                } else {
-                    full_name
-                };
+/// Sends an exported CSV to the server, which resolves its rows to catalog cards.
+async fn resolve_import(text: String) -> Result<ImportResult, String> {
+    let resp = Request::post("/api/import/resolve")
+        .header("Content-Type", "text/csv")
+        .body(text)
+        .map_err(|e| format!("Invalid import request: {:?}", e))?
+        .send()
+        .await
+        .map_err(|e| format!("Import failed: {:?}", e))?;
+    if !resp.ok() {
+        let status = resp.status();
+        return Err(match resp.json::<serde_json::Value>().await {
+            Ok(json) if json["error"].is_string() => format!(
+                "Import failed: {}",
+                json["error"].as_str().unwrap_or_default()
+            ),
+            _ => format!("Import failed: HTTP {}", status),
+        });
+    }
+    resp.json::<ImportResult>()
+        .await
+        .map_err(|e| format!("Invalid import response: {:?}", e))
+}
 
-                let price_str = item
-                    .prices
-                    .as_ref()
-                    .and_then(|p| {
-                        if item.is_foil {
-                            p.usd_foil.as_deref().or(p.usd.as_deref())
-                        } else {
-                            p.usd.as_deref()
-                        }
-                    })
-                    .unwrap_or("0");
+/// Uploads a saved session so the server can export it too, if a write token is set.
+/// Failures (e.g. offline) are only logged: the next save uploads the whole session again.
+fn sync_session(id: String) {
+    let Some(token) = storage::sync_token() else {
+        return;
+    };
+    let Some(meta) = storage::list_sessions().into_iter().find(|s| s.id == id) else {
+        return;
+    };
+    let items: Vec<ExportItem> = storage::load_items(&id)
+        .iter()
+        .map(ScannedItem::export_item)
+        .collect();
+    spawn_local(async move {
+        let body = serde_json::json!({
+            "name": meta.name,
+            "created_at": meta.created_at,
+            "items": items,
+        });
+        let result = match Request::put(&format!("/api/sessions/{}", id))
+            .header("Authorization", &format!("Bearer {}", token))
+            .json(&body)
+        {
+            Ok(request) => request.send().await.map_err(|e| format!("{:?}", e)),
+            Err(e) => Err(format!("{:?}", e)),
+        };
+        match result {
+            Ok(resp) if resp.ok() => {}
+            Ok(resp) => log::warn!("Session not synced: HTTP {}", resp.status()),
+            Err(e) => log::warn!("Session not synced: {}", e),
+        }
+    });
+}
 
-                let group_key = card
-                    .promo_grouping
-                    .clone()
-                    .unwrap_or_else(|| card.set_code.clone());
+/// Deletes the server's copy of a session, if a write token is set.
+fn unsync_session(id: String) {
+    let Some(token) = storage::sync_token() else {
+        return;
+    };
+    spawn_local(async move {
+        let url = format!("/api/sessions/{}", id);
+        let request = Request::delete(&url).header("Authorization", &format!("Bearer {}", token));
+        match request.send().await {
+            // Sessions that never synced aren't on the server
+            Ok(resp) if resp.ok() || resp.status() == 404 => {}
+            Ok(resp) => log::warn!("Session not deleted on the server: HTTP {}", resp.status()),
+            Err(e) => log::warn!("Session not deleted on the server: {:?}", e),
+        }
+    });
+}
 
-                let key = (group_key.clone(), card.card_number, item.is_foil);
+/// Current market prices of `card` from Lorcast, if available.
+async fn fetch_prices(card: &inkwell_core::Card) -> Option<LorcastPrices> {
+    let lorcast_url = format!(
+        "https://api.lorcast.com/v0/cards/{}/{}",
+        card.set_code, card.card_number
+    );
+    match Request::get(&lorcast_url).send().await {
+        Ok(res) if res.ok() => res.json::<LorcastCard>().await.ok().map(|lc| lc.prices),
+        _ => None,
+    }
+}
 
-                if let Some(pos) = rows.iter().position(|row| row.0 == key) {
-                    rows[pos].1 += 1;
-                } else {
-                    rows.push((
-                        key,
-                        1,
-                        escaped_name,
-                        card.rarity.clone(),
-                        price_str.to_string(),
-                        item.scanned_at.clone(),
-                    ));
-                }
-            }
+fn now_iso() -> String {
+    js_sys::Date::new_0()
+        .to_iso_string()
+        .as_string()
+        .unwrap_or_default()
+}
 
-            for (key, count, name, rarity, price, scanned_at) in rows {
-                let variant = if key.2 { "foil" } else { "normal" };
-                csv.push_str(&format!(
-                    "{},{},{},{},{},{},{},{}\n",
-                    key.0, key.1, variant, count, name, rarity, price, scanned_at
-                ));
+/// Merges imported rows into the scanned list: copies of a card already listed in the
+/// same finish add to its quantity, anything else is appended. Rows without a scan time
+/// are stamped with `now`.
+pub fn merge_imported(list: &mut Vec<ScannedItem>, items: &[ImportedItem], now: &str) {
+    for item in items {
+        let existing = list
+            .iter_mut()
+            .find(|listed| listed.card.id == item.card.id && listed.is_foil == item.foil);
+        if let Some(listed) = existing {
+            listed.quantity += item.count;
+            if listed.prices.is_none() {
+                listed.prices = imported_prices(item);
             }
-
-            csv
+            continue;
         }
+        list.push(ScannedItem {
+            card: item.card.clone(),
+            prices: imported_prices(item),
+            is_foil: item.foil,
+            quantity: item.count,
+            scanned_at: item.scanned_at.clone().unwrap_or_else(|| now.to_string()),
+        });
     }
 }
 
+/// The price recorded in an import, as the price of the item's finish.
+fn imported_prices(item: &ImportedItem) -> Option<LorcastPrices> {
+    item.price.as_ref().map(|price| LorcastPrices {
+        usd: (!item.foil).then(|| price.clone()),
+        usd_foil: item.foil.then(|| price.clone()),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use inkwell_core::Card;
 
     #[test]
-    fn test_csv_generation() {
-        let items = vec![
-            ScannedItem {
-                card: Card {
-                    id: "1".into(),
-                    name: "Mickey Mouse".into(),
-                    subtitle: "Wayward Sorcerer".into(),
-                    phash: "".into(),
-                    akaze_data: vec![],
-                    image_url: "".into(),
-                    rarity: "Common".into(),
-                    promo_grouping: None,
-                    set_code: "1".into(),
-                    card_number: 123,
-                },
-                prices: Some(LorcastPrices {
-                    usd: Some("1.50".into()),
-                    usd_foil: Some("5.00".into()),
-                }),
-                is_foil: false,
-                scanned_at: "2026-02-23T21:55:00.000Z".into(),
-            },
-            ScannedItem {
-                card: Card {
-                    id: "1".into(), // Duplicate item
-                    name: "Mickey Mouse".into(),
-                    subtitle: "Wayward Sorcerer".into(),
-                    phash: "".into(),
-                    akaze_data: vec![],
-                    image_url: "".into(),
-                    rarity: "Common".into(),
-                    promo_grouping: None,
-                    set_code: "1".into(),
-                    card_number: 123,
-                },
-                prices: None,
-                is_foil: false,
-                scanned_at: "2026-02-23T21:56:00.000Z".into(),
-            },
-            ScannedItem {
-                card: Card {
-                    id: "2".into(),
-                    name: "Donald Duck, The Brave".into(), // contains comma
-                    subtitle: "".into(),
-                    phash: "".into(),
-                    akaze_data: vec![],
-                    image_url: "".into(),
-                    rarity: "Rare".into(),
-                    promo_grouping: Some("P3".into()),
-                    set_code: "6".into(),
-                    card_number: 45,
-                },
-                prices: Some(LorcastPrices {
-                    usd: Some("2.00".into()),
-                    usd_foil: None,
-                }), // missing foil price fallback
-                is_foil: true,
-                scanned_at: "2026-02-23T21:56:00.000Z".into(),
-            },
-        ];
-
-        let csv_standard = generate_csv(&items, CsvFormat::Standard);
-        let lines_std: Vec<&str> = csv_standard.lines().collect();
-        assert_eq!(lines_std.len(), 3);
-        assert_eq!(
-            lines_std[0],
-            "Set Number,Card Number,Variant,Count,Card Name,Rarity,Price,ScannedAt"
-        );
-        assert_eq!(
-            lines_std[1],
-            "1,123,normal,2,Mickey Mouse - Wayward Sorcerer,Common,1.50,2026-02-23T21:55:00.000Z"
-        );
-        assert_eq!(
-            lines_std[2],
-            "P3,45,foil,1,\"Donald Duck, The Brave\",Rare,2.00,2026-02-23T21:56:00.000Z"
+    fn test_merge_imported() {
+        let card = |id: &str| Card {
+            id: id.into(),
+            name: format!("Card {}", id),
+            subtitle: "".into(),
+            phash: "".into(),
+            akaze_data: vec![],
+            image_url: "".into(),
+            rarity: "Common".into(),
+            promo_grouping: None,
+            set_code: "1".into(),
+            card_number: 1,
+        };
+        let imported = |id: &str, foil: bool, count: u32, price: Option<&str>| ImportedItem {
+            line: 2,
+            card: card(id),
+            foil,
+            count,
+            price: price.map(str::to_string),
+            scanned_at: None,
+        };
+
+        let mut list = vec![ScannedItem {
+            card: card("a"),
+            prices: None,
+            is_foil: false,
+            quantity: 1,
+            scanned_at: "2026-04-11T09:00:00.000Z".into(),
+        }];
+        merge_imported(
+            &mut list,
+            &[
+                imported("a", false, 2, Some("1.50")),
+                imported("a", true, 1, Some("4.00")),
+                imported("b", false, 3, None),
+            ],
+            "2026-04-12T10:00:00.000Z",
         );
 
-        let csv_dream = generate_csv(&items, CsvFormat::Dreamborn);
-        let lines_dream: Vec<&str> = csv_dream.lines().collect();
-        assert_eq!(lines_dream.len(), 3);
-        assert_eq!(lines_dream[0], "Set Number,Card Number,Variant,Count");
-        assert_eq!(lines_dream[1], "01,123,normal,2");
-        assert_eq!(lines_dream[2], "P3,45,foil,1");
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].quantity, 3);
+        assert_eq!(list[0].unit_price(), Some("1.50"));
+        assert!(list[1].is_foil);
+        assert_eq!(list[1].unit_price(), Some("4.00"));
+        assert_eq!(list[2].quantity, 3);
+        assert_eq!(list[2].scanned_at, "2026-04-12T10:00:00.000Z");
     }
 }
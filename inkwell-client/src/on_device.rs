@@ -0,0 +1,89 @@
+//! On-device identification: the server's compact index is downloaded once, kept in
+//! the browser's Cache Storage, and matched locally with the pure-Rust extractor, so
+//! scanning keeps working with no connection at all.
+
+use inkwell_core::compact::CompactIndex;
+use inkwell_core::ScanResult;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+const INDEX_URL: &str = "/api/index/compact";
+const CACHE_NAME: &str = "inkwell-index";
+
+async fn open_cache() -> Result<web_sys::Cache, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let caches = window
+        .caches()
+        .map_err(|e| format!("Cache Storage unavailable: {:?}", e))?;
+    let cache = JsFuture::from(caches.open(CACHE_NAME))
+        .await
+        .map_err(|e| format!("could not open cache: {:?}", e))?;
+    Ok(cache.unchecked_into())
+}
+
+async fn decode_response(response: &web_sys::Response) -> Result<CompactIndex, String> {
+    let buffer = JsFuture::from(
+        response
+            .array_buffer()
+            .map_err(|e| format!("could not read index: {:?}", e))?,
+    )
+    .await
+    .map_err(|e| format!("could not read index: {:?}", e))?;
+    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+    CompactIndex::decode(&bytes).map_err(|e| e.to_string())
+}
+
+/// The previously downloaded index, if any.
+pub async fn load_cached() -> Result<Option<CompactIndex>, String> {
+    let cache = open_cache().await?;
+    let cached = JsFuture::from(cache.match_with_str(INDEX_URL))
+        .await
+        .map_err(|e| format!("could not read cache: {:?}", e))?;
+    if cached.is_undefined() {
+        return Ok(None);
+    }
+    decode_response(&cached.unchecked_into()).await.map(Some)
+}
+
+/// Downloads the current index and keeps a copy for offline use.
+pub async fn download() -> Result<CompactIndex, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(INDEX_URL))
+        .await
+        .map_err(|e| format!("download failed: {:?}", e))?
+        .unchecked_into();
+    if !response.ok() {
+        return Err(format!("download failed: HTTP {}", response.status()));
+    }
+
+    let copy = response
+        .clone()
+        .map_err(|e| format!("download failed: {:?}", e))?;
+    let index = decode_response(&response).await?;
+    let cache = open_cache().await?;
+    JsFuture::from(cache.put_with_str(INDEX_URL, &copy))
+        .await
+        .map_err(|e| format!("could not store index: {:?}", e))?;
+    Ok(index)
+}
+
+/// Identifies the card drawn on `canvas`.
+pub fn identify(
+    index: &CompactIndex,
+    canvas: &web_sys::HtmlCanvasElement,
+) -> Result<ScanResult, String> {
+    let ctx = canvas
+        .get_context("2d")
+        .ok()
+        .flatten()
+        .ok_or("no 2d context")?
+        .unchecked_into::<web_sys::CanvasRenderingContext2d>();
+    let (width, height) = (canvas.width(), canvas.height());
+    let pixels = ctx
+        .get_image_data(0.0, 0.0, width as f64, height as f64)
+        .map_err(|e| format!("could not read frame: {:?}", e))?
+        .data()
+        .0;
+    let frame = image::RgbaImage::from_raw(width, height, pixels).ok_or("frame size mismatch")?;
+    Ok(index.identify(&image::DynamicImage::ImageRgba8(frame)))
+}
@@ -0,0 +1,163 @@
+use crate::ScannedItem;
+use leptos::*;
+
+/// A change made from the scanned list, by position in the list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListEdit {
+    ToggleFoil(usize),
+    SetQuantity(usize, u32),
+    Remove(usize),
+}
+
+impl ListEdit {
+    /// What the edit did, for the undo button and logs.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ListEdit::ToggleFoil(_) => "foil change",
+            ListEdit::SetQuantity(..) => "quantity change",
+            ListEdit::Remove(_) => "delete",
+        }
+    }
+
+    pub fn apply(&self, list: &mut Vec<ScannedItem>) {
+        match *self {
+            ListEdit::ToggleFoil(i) => {
+                if let Some(item) = list.get_mut(i) {
+                    item.is_foil = !item.is_foil;
+                }
+            }
+            ListEdit::SetQuantity(i, quantity) => {
+                if let Some(item) = list.get_mut(i) {
+                    item.quantity = quantity.max(1);
+                }
+            }
+            ListEdit::Remove(i) => {
+                if i < list.len() {
+                    list.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// Every scanned card, newest first, with its price and controls to edit it.
+#[component]
+pub fn ScannedList(
+    items: ReadSignal<Vec<ScannedItem>>,
+    #[prop(into)] on_edit: Callback<ListEdit>,
+) -> impl IntoView {
+    view! {
+        <ul class="max-w-lg w-full max-h-96 overflow-y-auto flex flex-col gap-2">
+            {move || {
+                items
+                    .get()
+                    .into_iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, item)| {
+                        let price = item
+                            .unit_price()
+                            .map(|p| format!("${}", p))
+                            .unwrap_or_else(|| "N/A".to_string());
+                        let quantity = item.quantity;
+                        view! {
+                            <li class="flex flex-row items-center gap-3 bg-slate-800 p-2 rounded-xl border border-slate-700">
+                                <img
+                                    src=format!("/{}", item.card.image_url.trim_start_matches('/'))
+                                    alt=item.card.name.clone()
+                                    loading="lazy"
+                                    class="w-10 h-14 object-cover rounded"
+                                />
+                                <div class="flex-1 min-w-0">
+                                    <p class="font-bold text-sm truncate">{item.card.name.clone()}</p>
+                                    <p class="text-xs text-slate-400 truncate">{item.card.subtitle.clone()}</p>
+                                    <p class="text-xs font-mono text-emerald-400">{price}</p>
+                                </div>
+                                <label class="flex items-center gap-1 text-xs text-slate-300 cursor-pointer">
+                                    <input
+                                        type="checkbox"
+                                        class="w-4 h-4 rounded border-slate-600 text-purple-600 bg-slate-700"
+                                        checked=item.is_foil
+                                        on:change=move |_| on_edit.call(ListEdit::ToggleFoil(i))
+                                    />
+                                    "Foil"
+                                </label>
+                                <div class="flex flex-row items-center rounded-full border border-slate-700 overflow-hidden">
+                                    <button
+                                        class="px-2 py-1 bg-slate-700 hover:bg-slate-600 disabled:opacity-30"
+                                        disabled=quantity <= 1
+                                        on:click=move |_| on_edit.call(ListEdit::SetQuantity(i, quantity - 1))
+                                        title="Fewer"
+                                    >
+                                        "-"
+                                    </button>
+                                    <span class="px-2 text-sm font-mono">{quantity}</span>
+                                    <button
+                                        class="px-2 py-1 bg-slate-700 hover:bg-slate-600"
+                                        on:click=move |_| on_edit.call(ListEdit::SetQuantity(i, quantity + 1))
+                                        title="More"
+                                    >
+                                        "+"
+                                    </button>
+                                </div>
+                                <button
+                                    class="p-1 text-red-500 hover:text-red-400"
+                                    on:click=move |_| on_edit.call(ListEdit::Remove(i))
+                                    title="Delete"
+                                >
+                                    <svg xmlns="http://www.w3.org/2000/svg" class="w-5 h-5" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                        <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M6 18L18 6M6 6l12 12" />
+                                    </svg>
+                                </button>
+                            </li>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </ul>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inkwell_core::Card;
+
+    fn item(id: &str) -> ScannedItem {
+        ScannedItem {
+            card: Card {
+                id: id.into(),
+                name: "Mickey Mouse".into(),
+                subtitle: "".into(),
+                phash: "".into(),
+                akaze_data: vec![],
+                image_url: "".into(),
+                rarity: "Common".into(),
+                promo_grouping: None,
+                set_code: "1".into(),
+                card_number: 1,
+            },
+            prices: None,
+            is_foil: false,
+            quantity: 1,
+            scanned_at: "2026-02-23T21:55:00.000Z".into(),
+        }
+    }
+
+    #[test]
+    fn test_list_edits() {
+        let mut list = vec![item("a"), item("b"), item("c")];
+
+        ListEdit::ToggleFoil(1).apply(&mut list);
+        ListEdit::SetQuantity(2, 4).apply(&mut list);
+        ListEdit::SetQuantity(0, 0).apply(&mut list);
+        assert!(list[1].is_foil);
+        assert_eq!(list[2].quantity, 4);
+        assert_eq!(list[0].quantity, 1);
+
+        ListEdit::Remove(0).apply(&mut list);
+        ListEdit::Remove(5).apply(&mut list);
+        let ids: Vec<&str> = list.iter().map(|i| i.card.id.as_str()).collect();
+        assert_eq!(ids, ["b", "c"]);
+    }
+}
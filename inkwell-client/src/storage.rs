@@ -0,0 +1,311 @@
+//! Scanning sessions saved in the browser's `localStorage`, so a refresh or an evicted
+//! tab doesn't lose the scanned list. Each session's items live under their own key
+//! next to a small index of all sessions. Scans captured while offline are listed here
+//! too, but their frames are kept in Cache Storage: a few dozen of them would fill the
+//! few megabytes `localStorage` allows, and then sessions couldn't be saved either.
+
+use crate::ScannedItem;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+const SESSIONS_KEY: &str = "inkwell.sessions";
+const CURRENT_KEY: &str = "inkwell.current_session";
+const QUEUE_KEY: &str = "inkwell.offline_queue";
+const SYNC_TOKEN_KEY: &str = "inkwell.sync_token";
+/// Cache Storage cache holding the frames of queued scans
+const QUEUE_CACHE: &str = "inkwell-queue";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Number of scanned cards, counting quantities
+    pub count: usize,
+}
+
+fn items_key(id: &str) -> String {
+    format!("inkwell.session.{}", id)
+}
+
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .ok_or_else(|| "local storage is not available".to_string())
+}
+
+fn read<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let raw = local_storage().ok()?.get_item(key).ok()??;
+    match serde_json::from_str(&raw) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("Ignoring unreadable {}: {}", key, e);
+            None
+        }
+    }
+}
+
+fn write<T: Serialize + ?Sized>(key: &str, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    local_storage()?
+        .set_item(key, &json)
+        // Typically QuotaExceededError
+        .map_err(|e| format!("could not save {}: {:?}", key, e))
+}
+
+/// Every saved session, most recently updated first.
+pub fn list_sessions() -> Vec<SessionMeta> {
+    let mut sessions: Vec<SessionMeta> = read(SESSIONS_KEY).unwrap_or_default();
+    sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    sessions
+}
+
+pub fn load_items(id: &str) -> Vec<ScannedItem> {
+    read(&items_key(id)).unwrap_or_default()
+}
+
+/// Saves a session's items and refreshes its entry in the session index.
+pub fn save_items(id: &str, items: &[ScannedItem], now: &str) -> Result<(), String> {
+    write(&items_key(id), &persisted(items))?;
+
+    let mut sessions: Vec<SessionMeta> = read(SESSIONS_KEY).unwrap_or_default();
+    if let Some(meta) = sessions.iter_mut().find(|s| s.id == id) {
+        meta.updated_at = now.to_string();
+        meta.count = items.iter().map(|item| item.quantity as usize).sum();
+        write(SESSIONS_KEY, &sessions)?;
+    }
+    Ok(())
+}
+
+/// Items as they are saved: without card descriptors, which older servers sent along
+/// with every match and which would fill the storage quota within a few dozen scans.
+fn persisted(items: &[ScannedItem]) -> Vec<ScannedItem> {
+    items
+        .iter()
+        .map(|item| ScannedItem {
+            card: item.card.without_descriptors(),
+            ..item.clone()
+        })
+        .collect()
+}
+
+/// Adds an item to a session other than the one on screen.
+pub fn append_item(id: &str, item: ScannedItem, now: &str) -> Result<(), String> {
+    let mut items = load_items(id);
+    items.push(item);
+    save_items(id, &items, now)
+}
+
+/// Starts an empty session and makes it the current one.
+pub fn create_session(now: &str) -> Result<SessionMeta, String> {
+    let mut sessions: Vec<SessionMeta> = read(SESSIONS_KEY).unwrap_or_default();
+    let meta = SessionMeta {
+        // Millisecond timestamps are unique enough for sessions created by hand
+        id: format!("{}", js_sys::Date::now() as u64),
+        // "2026-02-23T21:55:00.000Z" -> "Session 2026-02-23 21:55"
+        name: format!(
+            "Session {} {}",
+            now.get(..10).unwrap_or(now),
+            now.get(11..16).unwrap_or_default()
+        ),
+        created_at: now.to_string(),
+        updated_at: now.to_string(),
+        count: 0,
+    };
+    sessions.push(meta.clone());
+    write(SESSIONS_KEY, &sessions)?;
+    write(&items_key(&meta.id), &[] as &[ScannedItem])?;
+    set_current(&meta.id)?;
+    Ok(meta)
+}
+
+/// Removes a session and its items.
+pub fn delete_session(id: &str) -> Result<(), String> {
+    let mut sessions: Vec<SessionMeta> = read(SESSIONS_KEY).unwrap_or_default();
+    sessions.retain(|s| s.id != id);
+    write(SESSIONS_KEY, &sessions)?;
+    local_storage()?
+        .remove_item(&items_key(id))
+        .map_err(|e| format!("could not delete session {}: {:?}", id, e))
+}
+
+pub fn current() -> Option<String> {
+    local_storage().ok()?.get_item(CURRENT_KEY).ok()?
+}
+
+pub fn set_current(id: &str) -> Result<(), String> {
+    local_storage()?
+        .set_item(CURRENT_KEY, id)
+        .map_err(|e| format!("could not switch session: {:?}", e))
+}
+
+/// The session to resume on startup: the last one used if it still exists, otherwise
+/// the most recent one, otherwise a new one.
+pub fn resume(now: &str) -> Result<SessionMeta, String> {
+    let sessions = list_sessions();
+    let last_used = current().and_then(|id| sessions.iter().find(|s| s.id == id).cloned());
+    match last_used.or_else(|| sessions.first().cloned()) {
+        Some(meta) => {
+            set_current(&meta.id)?;
+            Ok(meta)
+        }
+        None => create_session(now),
+    }
+}
+
+/// The server's write token, if the user has set one to sync sessions with.
+pub fn sync_token() -> Option<String> {
+    local_storage()
+        .ok()?
+        .get_item(SYNC_TOKEN_KEY)
+        .ok()?
+        .filter(|t| !t.is_empty())
+}
+
+/// Sets the write token; an empty one stops syncing.
+pub fn set_sync_token(token: &str) -> Result<(), String> {
+    let storage = local_storage()?;
+    if token.is_empty() {
+        storage.remove_item(SYNC_TOKEN_KEY)
+    } else {
+        storage.set_item(SYNC_TOKEN_KEY, token)
+    }
+    .map_err(|e| format!("could not save the sync token: {:?}", e))
+}
+
+/// A frame captured while offline, waiting to be identified.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedScan {
+    /// Session the scan was taken in
+    pub session_id: Option<String>,
+    pub captured_at: String,
+    /// Cache Storage key of the encoded frame, see [`queued_frame`]
+    pub frame: String,
+}
+
+async fn queue_cache() -> Result<web_sys::Cache, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let caches = window
+        .caches()
+        .map_err(|e| format!("Cache Storage unavailable: {:?}", e))?;
+    let cache = JsFuture::from(caches.open(QUEUE_CACHE))
+        .await
+        .map_err(|e| format!("could not open the scan queue: {:?}", e))?;
+    Ok(cache.unchecked_into())
+}
+
+/// Scans waiting to be sent, oldest first.
+pub fn queued_scans() -> Vec<QueuedScan> {
+    read(QUEUE_KEY).unwrap_or_default()
+}
+
+/// Adds a scan of the encoded image `frame` to the end of the queue. Returns the queue
+/// length.
+pub async fn queue_scan(
+    session_id: Option<String>,
+    captured_at: String,
+    frame: &[u8],
+) -> Result<usize, String> {
+    let key = format!(
+        "/queued-scans/{}-{:08x}",
+        js_sys::Date::now() as u64,
+        (js_sys::Math::random() * f64::from(u32::MAX)) as u32
+    );
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(frame));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts)
+        .map_err(|e| format!("could not store the frame: {:?}", e))?;
+    let response = web_sys::Response::new_with_opt_blob(Some(&blob))
+        .map_err(|e| format!("could not store the frame: {:?}", e))?;
+    let cache = queue_cache().await?;
+    JsFuture::from(cache.put_with_str(&key, &response))
+        .await
+        .map_err(|e| format!("could not store the frame: {:?}", e))?;
+
+    let mut queue = queued_scans();
+    queue.push(QueuedScan {
+        session_id,
+        captured_at,
+        frame: key.clone(),
+    });
+    if let Err(e) = write(QUEUE_KEY, &queue) {
+        let _ = JsFuture::from(cache.delete_with_str(&key)).await;
+        return Err(e);
+    }
+    Ok(queue.len())
+}
+
+/// The encoded frame of a queued scan, or `None` if it's gone (e.g. site data was
+/// partly cleared).
+pub async fn queued_frame(scan: &QueuedScan) -> Result<Option<Vec<u8>>, String> {
+    let cache = queue_cache().await?;
+    let cached = JsFuture::from(cache.match_with_str(&scan.frame))
+        .await
+        .map_err(|e| format!("could not read the scan queue: {:?}", e))?;
+    if cached.is_undefined() {
+        return Ok(None);
+    }
+    let response: web_sys::Response = cached.unchecked_into();
+    let buffer = JsFuture::from(
+        response
+            .array_buffer()
+            .map_err(|e| format!("could not read a queued frame: {:?}", e))?,
+    )
+    .await
+    .map_err(|e| format!("could not read a queued frame: {:?}", e))?;
+    Ok(Some(js_sys::Uint8Array::new(&buffer).to_vec()))
+}
+
+/// Drops the oldest queued scan and its frame once it has been handled. Returns the
+/// queue length.
+pub async fn dequeue_scan() -> Result<usize, String> {
+    let mut queue = queued_scans();
+    if queue.is_empty() {
+        return Ok(0);
+    }
+    let done = queue.remove(0);
+    write(QUEUE_KEY, &queue)?;
+    // A frame left behind only takes up space; the scan is already off the queue
+    match queue_cache().await {
+        Ok(cache) => {
+            if let Err(e) = JsFuture::from(cache.delete_with_str(&done.frame)).await {
+                log::warn!("Could not delete queued frame {}: {:?}", done.frame, e);
+            }
+        }
+        Err(e) => log::warn!("{}", e),
+    }
+    Ok(queue.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_items_have_no_descriptors() {
+        let item = ScannedItem {
+            card: inkwell_core::Card {
+                id: "1-12".into(),
+                name: "Mickey Mouse".into(),
+                subtitle: "Wayward Sorcerer".into(),
+                phash: "".into(),
+                akaze_data: vec![7; 61 * 500],
+                image_url: "".into(),
+                rarity: "Common".into(),
+                promo_grouping: None,
+                set_code: "1".into(),
+                card_number: 12,
+            },
+            prices: None,
+            is_foil: true,
+            quantity: 2,
+            scanned_at: "2026-02-23T21:55:00.000Z".into(),
+        };
+        let saved = persisted(&[item]);
+        assert!(saved[0].card.akaze_data.is_empty());
+        assert_eq!((saved[0].is_foil, saved[0].quantity), (true, 2));
+        let json = serde_json::to_string(&saved).unwrap();
+        assert!(!json.contains("akaze_data"));
+    }
+}
@@ -0,0 +1,155 @@
+// Service worker: keeps the app shell available offline. API calls are never cached;
+// scans captured while offline are queued by the app itself and sent once back online.
+
+// Bump to drop everything cached by older versions
+const CACHE = "inkwell-v2";
+// Card images live in their own cache so they can be capped without touching the shell
+const IMAGES = "inkwell-images-v1";
+const MAX_IMAGES = 500;
+const SHELL = ["/", "/index.html", "/manifest.webmanifest", "/icons/icon-192.png", "/icons/icon-512.png"];
+
+// Paths that are answered with the app itself
+function isShell(url) {
+  return url.pathname === "/" || url.pathname === "/index.html";
+}
+
+function isImage(url) {
+  return url.pathname.startsWith("/card_images/");
+}
+
+// Trunk names the wasm and JS bundle after a content hash, so they can't be listed in
+// SHELL: they are taken from the index.html they belong to instead.
+function bundleOf(html) {
+  const assets = new Set();
+  for (const match of html.matchAll(/["'](\/[^"'?#]+\.(?:js|wasm|css))["']/g)) {
+    if (match[1] !== "/sw.js") {
+      assets.add(match[1]);
+    }
+  }
+  return [...assets];
+}
+
+// Removes everything but the shell and the current bundle, so each deploy's hashed
+// files don't pile up.
+function pruneBundles(cache, bundle) {
+  const keep = new Set([...SHELL, ...bundle]);
+  return cache
+    .keys()
+    .then((requests) =>
+      Promise.all(
+        requests
+          .filter((request) => !keep.has(new URL(request.url).pathname))
+          .map((request) => cache.delete(request))
+      )
+    );
+}
+
+// Stores a fetched index.html together with the bundle it loads, so the shell never
+// points at assets that aren't cached, then drops older bundles.
+function cacheShell(cache, response) {
+  return response
+    .clone()
+    .text()
+    .then((html) => {
+      const bundle = bundleOf(html);
+      return cache
+        .addAll(bundle)
+        .then(() => cache.put("/index.html", response))
+        .then(() => pruneBundles(cache, bundle));
+    });
+}
+
+// Drops the oldest images once there are more than MAX_IMAGES; keys come back in
+// insertion order.
+function trimImages(cache) {
+  return cache
+    .keys()
+    .then((requests) =>
+      Promise.all(requests.slice(0, -MAX_IMAGES).map((request) => cache.delete(request)))
+    );
+}
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches.open(CACHE).then((cache) =>
+      cache.addAll(SHELL).then(() =>
+        fetch("/index.html").then((response) => {
+          if (!response.ok) {
+            throw new Error(`index.html: HTTP ${response.status}`);
+          }
+          return cacheShell(cache, response);
+        })
+      )
+    )
+  );
+  self.skipWaiting();
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        // Only our own older caches: the app keeps the on-device index and queued scans
+        // in caches of its own
+        Promise.all(
+          keys
+            .filter((key) => /^inkwell-(v|images-v)\d+$/.test(key) && key !== CACHE && key !== IMAGES)
+            .map((key) => caches.delete(key))
+        )
+      )
+      .then(() => caches.open(CACHE))
+      .then((cache) =>
+        cache
+          .match("/index.html")
+          .then((shell) => shell && shell.text().then((html) => pruneBundles(cache, bundleOf(html))))
+      )
+      .then(() => self.clients.claim())
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  const url = new URL(request.url);
+  // Other origins (card prices, the Tailwind CDN) and our API always go to the network
+  if (request.method !== "GET" || url.origin !== self.location.origin || url.pathname.startsWith("/api/")) {
+    return;
+  }
+
+  if (request.mode === "navigate") {
+    // Network first so deploys show up immediately, the cached shell when offline.
+    // Only a good answer for the shell itself replaces it: an error page or some other
+    // document would otherwise be served for every page from then on.
+    event.respondWith(
+      fetch(request)
+        .then((response) => {
+          if (response.ok && isShell(url)) {
+            const copy = response.clone();
+            caches.open(CACHE).then((cache) => cacheShell(cache, copy)).catch(() => {});
+          }
+          return response;
+        })
+        .catch(() => caches.match("/index.html"))
+    );
+    return;
+  }
+
+  // Build assets and card images: serve from cache and refresh in the background
+  const image = isImage(url);
+  event.respondWith(
+    caches.open(image ? IMAGES : CACHE).then((cache) =>
+      cache.match(request).then((cached) => {
+        const network = fetch(request)
+          .then((response) => {
+            if (response.ok) {
+              const stored = cache.put(request, response.clone());
+              (image ? stored.then(() => trimImages(cache)) : stored).catch(() => {});
+            }
+            return response;
+          })
+          .catch(() => cached);
+        return cached || network;
+      })
+    )
+  );
+});
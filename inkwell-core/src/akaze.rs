@@ -0,0 +1,86 @@
+//! AKAZE features via OpenCV, used by the server to build and match its index.
+//! Only available with the `opencv` feature (on by default) outside WASM.
+
+use image::DynamicImage;
+use opencv::{
+    core::{KeyPoint, Mat, Vector},
+    features2d::AKAZE,
+    prelude::*,
+};
+
+/// Computes AKAZE features for an image.
+/// Returns a tuple of (KeyPoints, Descriptors serialized as Vec<u8>).
+pub fn compute_akaze_features(
+    img: &DynamicImage,
+) -> Result<(Vec<KeyPoint>, Vec<u8>), opencv::Error> {
+    compute_akaze_features_at(img, crate::DEFAULT_WORKING_SIZE)
+}
+
+/// Like [`compute_akaze_features`], scaling the image to fit `working_size` first.
+/// Reference and query images must use the same size for their descriptors to match.
+pub fn compute_akaze_features_at(
+    img: &DynamicImage,
+    working_size: u32,
+) -> Result<(Vec<KeyPoint>, Vec<u8>), opencv::Error> {
+    // Resize to a reasonable working size (optional, but good for performance)
+    let resized = img.resize(
+        working_size,
+        working_size,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let gray = resized.to_luma8();
+
+    // Convert raw pixels to OpenCV Mat
+    let (_width, height) = gray.dimensions();
+
+    // Create Mat from slice (copies data)
+    let mat_1d = Mat::from_slice(gray.as_raw())?;
+
+    // Reshape to correct dimensions: channels=1, rows=height.
+    let mat = mat_1d.reshape(1, height as i32)?;
+
+    // Init AKAZE
+    // Use DESCRIPTOR_MLDB for binary descriptors (Hamming distance) and rotation invariance.
+    let mut akaze = AKAZE::create_def()?;
+
+    // Detect and Compute
+    let mut keypoints = Vector::<KeyPoint>::new();
+    let mut descriptors = Mat::default();
+    let mask = Mat::default();
+
+    akaze.detect_and_compute(&mat, &mask, &mut keypoints, &mut descriptors, false)?;
+
+    // Convert descriptors Mat to Vec<u8> for storage
+    let data_len = descriptors.total() * descriptors.elem_size()?;
+    let mut descriptors_bytes = vec![0u8; data_len];
+    let data_ptr = descriptors.data_bytes()?;
+    descriptors_bytes.copy_from_slice(data_ptr);
+
+    // Convert Vector<KeyPoint> to Vec<KeyPoint>
+    let keypoints_vec: Vec<KeyPoint> = keypoints.to_vec();
+
+    Ok((keypoints_vec, descriptors_bytes))
+}
+
+pub const AKAZE_DESC_SIZE: i32 = 61;
+
+/// Helper to reconstruct Mat from bytes
+pub fn akaze_bytes_to_mat(bytes: &[u8]) -> Result<Mat, opencv::Error> {
+    if bytes.is_empty() {
+        return Ok(Mat::default());
+    }
+
+    // Create Mat from slice (copies data)
+    let mat_1d = Mat::from_slice(bytes)?;
+
+    // Reshape. Rows = bytes.len() / 61. Cols = 61.
+    let rows = bytes.len() as i32 / AKAZE_DESC_SIZE;
+
+    let mat_view = mat_1d.reshape(1, rows)?;
+
+    // We must ensure we return an owned Mat, not a view/BoxedRef.
+    let mut mat_owned = Mat::default();
+    mat_view.copy_to(&mut mat_owned)?;
+
+    Ok(mat_owned)
+}
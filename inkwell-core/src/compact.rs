@@ -0,0 +1,348 @@
+//! Compact, self-contained card index for identifying cards without the server: card
+//! metadata plus [`crate::orb`] descriptors, matched by brute-force Hamming distance.
+//!
+//! Binary layout (little-endian):
+//! `b"INKC"`, format version (u8), descriptor size (u8), two zero bytes, metadata
+//! length (u32), metadata JSON ([`IndexMeta`]), then every card's descriptors in order.
+
+use crate::orb::{self, ORB_DESC_SIZE};
+use crate::{Card, ScanResult};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+const MAGIC: &[u8; 4] = b"INKC";
+/// Bump whenever descriptors or the layout change incompatibly.
+pub const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 12;
+
+/// Matches further apart than this (out of 256 bits) don't vote.
+const MAX_DISTANCE: u32 = 64;
+/// Lowe's ratio test against the closest descriptor of any other card.
+const RATIO: f32 = 0.8;
+/// Votes at which a match is accepted by default.
+pub const DEFAULT_ACCEPT_VOTES: u32 = 20;
+
+type Descriptor = [u64; ORB_DESC_SIZE / 8];
+
+#[derive(Debug)]
+pub enum CompactIndexError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    Metadata(serde_json::Error),
+    DescriptorCount,
+}
+
+impl std::fmt::Display for CompactIndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompactIndexError::Truncated => write!(f, "compact index is truncated"),
+            CompactIndexError::BadMagic => write!(f, "not a compact index"),
+            CompactIndexError::UnsupportedVersion(v) => {
+                write!(f, "unsupported compact index version {}", v)
+            }
+            CompactIndexError::Metadata(e) => write!(f, "invalid compact index metadata: {}", e),
+            CompactIndexError::DescriptorCount => {
+                write!(f, "descriptor data doesn't match the card list")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompactIndexError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IndexMeta {
+    /// Longest side query images are scaled to, matching the references
+    pub working_size: u32,
+    pub accept_votes: u32,
+    pub cards: Vec<IndexCard>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IndexCard {
+    #[serde(flatten)]
+    pub card: Card,
+    /// Number of descriptors stored for the card
+    pub descriptors: u32,
+}
+
+pub struct CompactIndex {
+    pub working_size: u32,
+    pub accept_votes: u32,
+    pub cards: Vec<Card>,
+    descriptors: Vec<Descriptor>,
+    /// `cards` position of each descriptor
+    descriptor_cards: Vec<u32>,
+}
+
+impl CompactIndex {
+    pub fn new(working_size: u32, accept_votes: u32) -> Self {
+        Self {
+            working_size,
+            accept_votes,
+            cards: Vec::new(),
+            descriptors: Vec::new(),
+            descriptor_cards: Vec::new(),
+        }
+    }
+
+    /// Adds a card with its concatenated descriptors; a trailing partial descriptor is ignored.
+    pub fn push(&mut self, mut card: Card, descriptors: &[u8]) {
+        card.akaze_data = Vec::new();
+        let position = self.cards.len() as u32;
+        for chunk in descriptors.chunks_exact(ORB_DESC_SIZE) {
+            self.descriptors.push(pack(chunk));
+            self.descriptor_cards.push(position);
+        }
+        self.cards.push(card);
+    }
+
+    pub fn descriptor_count(&self) -> usize {
+        self.descriptors.len()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut counts = vec![0u32; self.cards.len()];
+        for &card in &self.descriptor_cards {
+            counts[card as usize] += 1;
+        }
+        let meta = IndexMeta {
+            working_size: self.working_size,
+            accept_votes: self.accept_votes,
+            cards: self
+                .cards
+                .iter()
+                .zip(counts)
+                .map(|(card, descriptors)| IndexCard {
+                    card: card.clone(),
+                    descriptors,
+                })
+                .collect(),
+        };
+        let meta = serde_json::to_vec(&meta).expect("card metadata serializes");
+
+        let mut out =
+            Vec::with_capacity(HEADER_LEN + meta.len() + self.descriptors.len() * ORB_DESC_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&[FORMAT_VERSION, ORB_DESC_SIZE as u8, 0, 0]);
+        out.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+        out.extend_from_slice(&meta);
+        // `push` keeps descriptors grouped by card, in card order
+        for word in self.descriptors.iter().flatten() {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, CompactIndexError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(CompactIndexError::Truncated);
+        }
+        if &bytes[..4] != MAGIC {
+            return Err(CompactIndexError::BadMagic);
+        }
+        if bytes[4] != FORMAT_VERSION || bytes[5] as usize != ORB_DESC_SIZE {
+            return Err(CompactIndexError::UnsupportedVersion(bytes[4]));
+        }
+        let meta_len = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let meta_end = HEADER_LEN
+            .checked_add(meta_len)
+            .filter(|end| *end <= bytes.len())
+            .ok_or(CompactIndexError::Truncated)?;
+        let meta: IndexMeta = serde_json::from_slice(&bytes[HEADER_LEN..meta_end])
+            .map_err(CompactIndexError::Metadata)?;
+
+        let data = &bytes[meta_end..];
+        // The counts come from the file: on 32-bit targets (the browser) a corrupt one
+        // could overflow the total
+        let total = meta
+            .cards
+            .iter()
+            .try_fold(0usize, |total, c| total.checked_add(c.descriptors as usize))
+            .and_then(|total| total.checked_mul(ORB_DESC_SIZE))
+            .ok_or(CompactIndexError::DescriptorCount)?;
+        if data.len() != total {
+            return Err(CompactIndexError::DescriptorCount);
+        }
+
+        let mut index = Self::new(meta.working_size, meta.accept_votes);
+        let mut offset = 0;
+        for entry in meta.cards {
+            let len = entry.descriptors as usize * ORB_DESC_SIZE;
+            index.push(entry.card, &data[offset..offset + len]);
+            offset += len;
+        }
+        Ok(index)
+    }
+
+    /// Votes per card (position in `cards`) for the query descriptors, best first.
+    pub fn rank(&self, query: &[u8]) -> Vec<(usize, usize)> {
+        let mut votes = vec![0usize; self.cards.len()];
+        for chunk in query.chunks_exact(ORB_DESC_SIZE) {
+            let q = pack(chunk);
+
+            // Closest descriptor overall, and closest of any *other* card: several
+            // descriptors of the same card being close isn't ambiguous
+            let (mut best, mut best_card, mut other) = (u32::MAX, u32::MAX, u32::MAX);
+            for (descriptor, &card) in self.descriptors.iter().zip(&self.descriptor_cards) {
+                let d = hamming(&q, descriptor);
+                if d < best {
+                    if card != best_card {
+                        other = best;
+                    }
+                    best = d;
+                    best_card = card;
+                } else if d < other && card != best_card {
+                    other = d;
+                }
+            }
+
+            if best <= MAX_DISTANCE && (best as f32) < RATIO * other as f32 {
+                votes[best_card as usize] += 1;
+            }
+        }
+
+        let mut ranking: Vec<(usize, usize)> = votes
+            .into_iter()
+            .enumerate()
+            .filter(|(_, v)| *v > 0)
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranking
+    }
+
+    /// Identifies a card in `img` entirely on this device.
+    pub fn identify(&self, img: &DynamicImage) -> ScanResult {
+        let (_, query) = orb::extract(img, self.working_size, orb::QUERY_FEATURES);
+        let ranking = self.rank(&query);
+        let best_votes = ranking.first().map(|r| r.1).unwrap_or(0);
+        ScanResult {
+            card: ranking
+                .first()
+                .filter(|r| r.1 >= self.accept_votes as usize)
+                .map(|r| self.cards[r.0].clone()),
+            confidence: self.confidence(best_votes),
+            global_total_scans: 0,
+            scan_id: None,
+        }
+    }
+
+    /// Same scale as the server's uncalibrated confidence: accepted matches land at 0.5 or above.
+    pub fn confidence(&self, votes: usize) -> f64 {
+        (votes as f64 / (2.0 * self.accept_votes.max(1) as f64)).min(1.0)
+    }
+}
+
+fn pack(bytes: &[u8]) -> Descriptor {
+    let mut descriptor = [0u64; ORB_DESC_SIZE / 8];
+    for (word, chunk) in descriptor.iter_mut().zip(bytes.chunks_exact(8)) {
+        *word = u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    descriptor
+}
+
+fn hamming(a: &Descriptor, b: &Descriptor) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: &str) -> Card {
+        Card {
+            id: id.to_string(),
+            name: format!("Card {}", id),
+            subtitle: String::new(),
+            phash: String::new(),
+            akaze_data: vec![1, 2, 3],
+            image_url: format!("card_images/{}.jpg", id),
+            rarity: "Common".to_string(),
+            promo_grouping: None,
+            set_code: "1".to_string(),
+            card_number: 1,
+        }
+    }
+
+    /// `count` distinct descriptors derived from `seed`.
+    fn descriptors(seed: u8, count: usize) -> Vec<u8> {
+        (0..count * ORB_DESC_SIZE)
+            .map(|i| {
+                (i as u8)
+                    .wrapping_mul(31)
+                    .wrapping_add(seed)
+                    .rotate_left(seed as u32 % 8)
+                    ^ (i / 7) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let mut index = CompactIndex::new(500, 20);
+        index.push(card("a"), &descriptors(1, 3));
+        index.push(card("b"), &[]);
+        index.push(card("c"), &descriptors(2, 2));
+
+        let bytes = index.encode();
+        let decoded = CompactIndex::decode(&bytes).unwrap();
+        assert_eq!(decoded.working_size, 500);
+        assert_eq!(decoded.descriptor_count(), 5);
+        assert_eq!(
+            decoded
+                .cards
+                .iter()
+                .map(|c| c.id.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert!(decoded.cards[0].akaze_data.is_empty());
+        assert_eq!(decoded.encode(), bytes);
+
+        assert!(matches!(
+            CompactIndex::decode(&bytes[..bytes.len() - 1]),
+            Err(CompactIndexError::DescriptorCount)
+        ));
+        assert!(matches!(
+            CompactIndex::decode(b"nope, not an index"),
+            Err(CompactIndexError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_impossible_descriptor_counts() {
+        let meta = IndexMeta {
+            working_size: 500,
+            accept_votes: 20,
+            cards: ["a", "b", "c"]
+                .map(|id| IndexCard {
+                    card: card(id),
+                    descriptors: u32::MAX,
+                })
+                .into(),
+        };
+        let meta = serde_json::to_vec(&meta).unwrap();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[FORMAT_VERSION, ORB_DESC_SIZE as u8, 0, 0]);
+        bytes.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&meta);
+        bytes.extend_from_slice(&descriptors(1, 2));
+
+        assert!(matches!(
+            CompactIndex::decode(&bytes),
+            Err(CompactIndexError::DescriptorCount)
+        ));
+    }
+
+    #[test]
+    fn test_rank_votes_for_matching_card() {
+        let mut index = CompactIndex::new(500, 2);
+        index.push(card("a"), &descriptors(1, 4));
+        index.push(card("b"), &descriptors(90, 4));
+
+        let ranking = index.rank(&descriptors(90, 4));
+        assert_eq!(ranking.first(), Some(&(1, 4)));
+        assert!(index.confidence(4) >= 0.5);
+    }
+}
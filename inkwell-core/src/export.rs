@@ -0,0 +1,540 @@
+//! Collection exports, shared by the client and the server so both produce the same
+//! files. Every format takes the same [`ExportItem`]s: CSV layouts for importing into
+//! other tools or marketplaces, full JSON, and a user-defined column [`Template`].
+
+use crate::Card;
+use serde::{Deserialize, Serialize};
+
+/// One line of a collection: a card in one finish, with how many copies there are.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportItem {
+    pub card: Card,
+    pub foil: bool,
+    pub quantity: u32,
+    /// Unit price in USD, if known
+    pub price: Option<String>,
+    pub scanned_at: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Dreamborn's columns plus name, rarity, price and scan time
+    Standard,
+    /// Dreamborn collection import
+    Dreamborn,
+    /// Every item with all card fields
+    Json,
+    /// TCGplayer mass entry
+    Tcgplayer,
+    /// Cardmarket stock import
+    Cardmarket,
+    /// Columns named after Lorcast's API fields
+    Lorcast,
+    Template(Template),
+}
+
+impl ExportFormat {
+    /// Names accepted by [`ExportFormat::from_name`], in menu order.
+    pub const NAMES: [&'static str; 7] = [
+        "standard",
+        "dreamborn",
+        "json",
+        "tcgplayer",
+        "cardmarket",
+        "lorcast",
+        "template",
+    ];
+
+    /// Looks a format up by name; `template` needs the column spec (see [`Template::parse`]).
+    pub fn from_name(name: &str, template: Option<&str>) -> Result<Self, ExportError> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "standard" => ExportFormat::Standard,
+            "dreamborn" => ExportFormat::Dreamborn,
+            "json" => ExportFormat::Json,
+            "tcgplayer" => ExportFormat::Tcgplayer,
+            "cardmarket" => ExportFormat::Cardmarket,
+            "lorcast" => ExportFormat::Lorcast,
+            "template" => ExportFormat::Template(Template::parse(
+                template.ok_or(ExportError::MissingTemplate)?,
+            )?),
+            _ => return Err(ExportError::UnknownFormat(name.to_string())),
+        })
+    }
+
+    /// Display name for menus.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Standard => "Standard CSV",
+            ExportFormat::Dreamborn => "Dreamborn CSV",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Tcgplayer => "TCGplayer CSV",
+            ExportFormat::Cardmarket => "Cardmarket CSV",
+            ExportFormat::Lorcast => "Lorcast CSV",
+            ExportFormat::Template(_) => "Custom CSV",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            _ => "csv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            _ => "text/csv",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportError {
+    UnknownFormat(String),
+    MissingTemplate,
+    UnknownField(String),
+    EmptyTemplate,
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::UnknownFormat(name) => write!(f, "unknown export format \"{}\"", name),
+            ExportError::MissingTemplate => {
+                write!(f, "the template format needs a column template")
+            }
+            ExportError::UnknownField(name) => write!(
+                f,
+                "unknown template field \"{}\" (expected one of: {})",
+                name,
+                Field::ALL
+                    .iter()
+                    .map(|field| field.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ExportError::EmptyTemplate => write!(f, "the template has no columns"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// A value a [`Template`] column can hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Name,
+    Subtitle,
+    /// "Name - Subtitle", or just the name
+    FullName,
+    Set,
+    /// Promo grouping, or the set for regular cards
+    Group,
+    Number,
+    Rarity,
+    /// "normal" or "foil"
+    Variant,
+    Count,
+    Price,
+    ScannedAt,
+    ImageUrl,
+}
+
+impl Field {
+    pub const ALL: [Field; 13] = [
+        Field::Id,
+        Field::Name,
+        Field::Subtitle,
+        Field::FullName,
+        Field::Set,
+        Field::Group,
+        Field::Number,
+        Field::Rarity,
+        Field::Variant,
+        Field::Count,
+        Field::Price,
+        Field::ScannedAt,
+        Field::ImageUrl,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Name => "name",
+            Field::Subtitle => "subtitle",
+            Field::FullName => "full_name",
+            Field::Set => "set",
+            Field::Group => "group",
+            Field::Number => "number",
+            Field::Rarity => "rarity",
+            Field::Variant => "variant",
+            Field::Count => "count",
+            Field::Price => "price",
+            Field::ScannedAt => "scanned_at",
+            Field::ImageUrl => "image_url",
+        }
+    }
+
+    fn value(self, item: &ExportItem) -> String {
+        let card = &item.card;
+        match self {
+            Field::Id => card.id.clone(),
+            Field::Name => card.name.clone(),
+            Field::Subtitle => card.subtitle.clone(),
+            Field::FullName => full_name(card),
+            Field::Set => card.set_code.clone(),
+            Field::Group => card
+                .promo_grouping
+                .clone()
+                .unwrap_or_else(|| card.set_code.clone()),
+            Field::Number => card.card_number.to_string(),
+            Field::Rarity => card.rarity.clone(),
+            Field::Variant => variant(item.foil).to_string(),
+            Field::Count => item.quantity.to_string(),
+            Field::Price => item.price.clone().unwrap_or_default(),
+            Field::ScannedAt => item.scanned_at.clone(),
+            Field::ImageUrl => card.image_url.clone(),
+        }
+    }
+}
+
+/// CSV columns chosen by the user, one row per card and finish.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    /// (header, field) per column
+    pub columns: Vec<(String, Field)>,
+}
+
+impl Template {
+    /// Parses a comma-separated list of [`Field`] names, each optionally with its own
+    /// header: `"Qty=count,Card=full_name,set,number"`.
+    pub fn parse(spec: &str) -> Result<Self, ExportError> {
+        let columns = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(|column| {
+                let (header, name) = match column.split_once('=') {
+                    Some((header, name)) => (header.trim(), name.trim()),
+                    None => (column, column),
+                };
+                let field = Field::ALL
+                    .into_iter()
+                    .find(|field| field.name().eq_ignore_ascii_case(name))
+                    .ok_or_else(|| ExportError::UnknownField(name.to_string()))?;
+                Ok((header.to_string(), field))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if columns.is_empty() {
+            return Err(ExportError::EmptyTemplate);
+        }
+        Ok(Self { columns })
+    }
+}
+
+/// Renders `items` in `format`.
+pub fn export(items: &[ExportItem], format: &ExportFormat) -> String {
+    match format {
+        ExportFormat::Standard => {
+            let mut csv = String::from(
+                "Set Number,Card Number,Variant,Count,Card Name,Rarity,Price,ScannedAt\n",
+            );
+            for (item, count) in grouped(items, |item| Field::Group.value(item)) {
+                csv.push_str(&csv_row(&[
+                    Field::Group.value(item),
+                    item.card.card_number.to_string(),
+                    variant(item.foil).to_string(),
+                    count.to_string(),
+                    full_name(&item.card),
+                    item.card.rarity.clone(),
+                    item.price.clone().unwrap_or_else(|| "0".to_string()),
+                    item.scanned_at.clone(),
+                ]));
+            }
+            csv
+        }
+        ExportFormat::Dreamborn => {
+            let mut csv = String::from("Set Number,Card Number,Variant,Count\n");
+            // Dreamborn pads set numbers: set 1 is "01"
+            let group = |item: &ExportItem| {
+                item.card
+                    .promo_grouping
+                    .clone()
+                    .unwrap_or_else(|| format!("0{}", item.card.set_code))
+            };
+            for (item, count) in grouped(items, group) {
+                csv.push_str(&csv_row(&[
+                    group(item),
+                    item.card.card_number.to_string(),
+                    variant(item.foil).to_string(),
+                    count.to_string(),
+                ]));
+            }
+            csv
+        }
+        ExportFormat::Json => {
+            let items: Vec<ExportItem> = items
+                .iter()
+                .map(|item| {
+                    let mut item = item.clone();
+                    item.card.akaze_data = Vec::new();
+                    item
+                })
+                .collect();
+            serde_json::to_string_pretty(&items).expect("export items serialize")
+        }
+        ExportFormat::Tcgplayer => {
+            let mut csv = String::from("Quantity,Name,Set,Card Number,Printing,Condition\n");
+            for (item, count) in grouped(items, |item| item.card.id.clone()) {
+                csv.push_str(&csv_row(&[
+                    count.to_string(),
+                    full_name(&item.card),
+                    set_name(&item.card),
+                    item.card.card_number.to_string(),
+                    if item.foil { "Cold Foil" } else { "Normal" }.to_string(),
+                    "Near Mint".to_string(),
+                ]));
+            }
+            csv
+        }
+        ExportFormat::Cardmarket => {
+            let mut csv = String::from("Amount,Name,Expansion,Number,Language,Condition,Foil\n");
+            for (item, count) in grouped(items, |item| item.card.id.clone()) {
+                csv.push_str(&csv_row(&[
+                    count.to_string(),
+                    full_name(&item.card),
+                    set_name(&item.card),
+                    item.card.card_number.to_string(),
+                    "English".to_string(),
+                    "NM".to_string(),
+                    if item.foil { "Y" } else { "N" }.to_string(),
+                ]));
+            }
+            csv
+        }
+        ExportFormat::Lorcast => {
+            let mut csv = String::from("set_code,collector_number,name,version,foil,quantity\n");
+            for (item, count) in grouped(items, |item| item.card.id.clone()) {
+                csv.push_str(&csv_row(&[
+                    item.card.set_code.clone(),
+                    item.card.card_number.to_string(),
+                    item.card.name.clone(),
+                    item.card.subtitle.clone(),
+                    item.foil.to_string(),
+                    count.to_string(),
+                ]));
+            }
+            csv
+        }
+        ExportFormat::Template(template) => {
+            let headers: Vec<String> = template
+                .columns
+                .iter()
+                .map(|(header, _)| header.clone())
+                .collect();
+            let mut csv = csv_row(&headers);
+            for (item, count) in grouped(items, |item| item.card.id.clone()) {
+                let item = ExportItem {
+                    quantity: count,
+                    ..item.clone()
+                };
+                let values: Vec<String> = template
+                    .columns
+                    .iter()
+                    .map(|(_, field)| field.value(&item))
+                    .collect();
+                csv.push_str(&csv_row(&values));
+            }
+            csv
+        }
+    }
+}
+
+/// Merges items with the same key and finish, keeping the first of each with the total count.
+fn grouped(items: &[ExportItem], key: impl Fn(&ExportItem) -> String) -> Vec<(&ExportItem, u32)> {
+    let mut rows: Vec<((String, u32, bool), &ExportItem, u32)> = Vec::new();
+    for item in items {
+        let row_key = (key(item), item.card.card_number, item.foil);
+        match rows.iter_mut().find(|row| row.0 == row_key) {
+            Some(row) => row.2 += item.quantity,
+            None => rows.push((row_key, item, item.quantity)),
+        }
+    }
+    rows.into_iter()
+        .map(|(_, item, count)| (item, count))
+        .collect()
+}
+
+fn variant(foil: bool) -> &'static str {
+    if foil { "foil" } else { "normal" }
+}
+
+fn full_name(card: &Card) -> String {
+    if card.subtitle.is_empty() {
+        card.name.clone()
+    } else {
+        format!("{} - {}", card.name, card.subtitle)
+    }
+}
+
+/// Marketplace name of the card's set, or "Set <code>" for sets not listed here.
+fn set_name(card: &Card) -> String {
+    let name = match card.set_code.as_str() {
+        "1" => "The First Chapter",
+        "2" => "Rise of the Floodborn",
+        "3" => "Into the Inklands",
+        "4" => "Ursula's Return",
+        "5" => "Shimmering Skies",
+        "6" => "Azurite Sea",
+        "7" => "Archazia's Island",
+        "8" => "Reign of Jafar",
+        "9" => "Fabled",
+        code => return format!("Set {}", code),
+    };
+    name.to_string()
+}
+
+fn csv_row(fields: &[String]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: &str, name: &str, subtitle: &str, promo_grouping: Option<&str>) -> Card {
+        Card {
+            id: id.into(),
+            name: name.into(),
+            subtitle: subtitle.into(),
+            phash: "".into(),
+            akaze_data: vec![1, 2, 3],
+            image_url: "".into(),
+            rarity: "Common".into(),
+            promo_grouping: promo_grouping.map(str::to_string),
+            set_code: "1".into(),
+            card_number: 123,
+        }
+    }
+
+    fn items() -> Vec<ExportItem> {
+        let mickey = card("1", "Mickey Mouse", "Wayward Sorcerer", None);
+        let donald = Card {
+            rarity: "Rare".into(),
+            set_code: "6".into(),
+            card_number: 45,
+            // Name contains a comma
+            ..card("2", "Donald Duck, The Brave", "", Some("P3"))
+        };
+        vec![
+            ExportItem {
+                card: mickey.clone(),
+                foil: false,
+                quantity: 1,
+                price: Some("1.50".into()),
+                scanned_at: "2026-02-23T21:55:00.000Z".into(),
+            },
+            // Duplicate item
+            ExportItem {
+                card: mickey,
+                foil: false,
+                quantity: 1,
+                price: None,
+                scanned_at: "2026-02-23T21:56:00.000Z".into(),
+            },
+            ExportItem {
+                card: donald,
+                foil: true,
+                quantity: 3,
+                price: Some("2.00".into()),
+                scanned_at: "2026-02-23T21:56:00.000Z".into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_csv_generation() {
+        let items = items();
+        let csv_standard = export(&items, &ExportFormat::Standard);
+        let lines_std: Vec<&str> = csv_standard.lines().collect();
+        assert_eq!(lines_std.len(), 3);
+        assert_eq!(
+            lines_std[0],
+            "Set Number,Card Number,Variant,Count,Card Name,Rarity,Price,ScannedAt"
+        );
+        assert_eq!(
+            lines_std[1],
+            "1,123,normal,2,Mickey Mouse - Wayward Sorcerer,Common,1.50,2026-02-23T21:55:00.000Z"
+        );
+        assert_eq!(
+            lines_std[2],
+            "P3,45,foil,3,\"Donald Duck, The Brave\",Rare,2.00,2026-02-23T21:56:00.000Z"
+        );
+
+        let csv_dream = export(&items, &ExportFormat::Dreamborn);
+        let lines_dream: Vec<&str> = csv_dream.lines().collect();
+        assert_eq!(lines_dream.len(), 3);
+        assert_eq!(lines_dream[0], "Set Number,Card Number,Variant,Count");
+        assert_eq!(lines_dream[1], "01,123,normal,2");
+        assert_eq!(lines_dream[2], "P3,45,foil,3");
+    }
+
+    #[test]
+    fn test_marketplace_formats() {
+        let items = items();
+        assert_eq!(
+            export(&items, &ExportFormat::Tcgplayer),
+            "Quantity,Name,Set,Card Number,Printing,Condition\n\
+             2,Mickey Mouse - Wayward Sorcerer,The First Chapter,123,Normal,Near Mint\n\
+             3,\"Donald Duck, The Brave\",Azurite Sea,45,Cold Foil,Near Mint\n"
+        );
+        assert_eq!(
+            export(&items, &ExportFormat::Cardmarket).lines().nth(1),
+            Some("2,Mickey Mouse - Wayward Sorcerer,The First Chapter,123,English,NM,N")
+        );
+        assert_eq!(
+            export(&items, &ExportFormat::Lorcast).lines().nth(2),
+            Some("6,45,\"Donald Duck, The Brave\",,true,3")
+        );
+
+        let json: Vec<ExportItem> =
+            serde_json::from_str(&export(&items, &ExportFormat::Json)).unwrap();
+        assert_eq!(json.len(), 3);
+        assert_eq!(json[2].card.promo_grouping.as_deref(), Some("P3"));
+        assert!(json[0].card.akaze_data.is_empty());
+    }
+
+    #[test]
+    fn test_template() {
+        let format =
+            ExportFormat::from_name("template", Some("Qty=count, Card=full_name,variant")).unwrap();
+        assert_eq!(
+            export(&items(), &format),
+            "Qty,Card,variant\n2,Mickey Mouse - Wayward Sorcerer,normal\n3,\"Donald Duck, The Brave\",foil\n"
+        );
+
+        assert_eq!(
+            Template::parse("count,colour"),
+            Err(ExportError::UnknownField("colour".into()))
+        );
+        assert_eq!(Template::parse(" , "), Err(ExportError::EmptyTemplate));
+        assert_eq!(
+            ExportFormat::from_name("template", None),
+            Err(ExportError::MissingTemplate)
+        );
+    }
+}
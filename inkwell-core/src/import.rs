@@ -0,0 +1,260 @@
+//! Reading back collection CSV files in the layouts the client exports: Standard
+//! (`Set Number,Card Number,Variant,Count,Card Name,Rarity,Price,ScannedAt`) and
+//! Dreamborn (`Set Number,Card Number,Variant,Count`). Columns are found by header
+//! name, so reordered or extra columns are fine.
+//!
+//! Parsing only reads the rows; resolving them to catalog [`Card`]s needs the card
+//! database and happens on the server, which answers with an [`ImportResult`].
+
+use crate::Card;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Standard,
+    Dreamborn,
+}
+
+/// One parsed data row.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportRow {
+    /// 1-based line in the file
+    pub line: usize,
+    /// Promo grouping (e.g. "P3") or set number; Dreamborn pads sets, as in "01"
+    pub group: String,
+    pub card_number: u32,
+    pub foil: bool,
+    pub count: u32,
+    /// Unit price from the Standard layout, if one was recorded
+    pub price: Option<String>,
+    /// When the card was scanned, from the Standard layout
+    pub scanned_at: Option<String>,
+}
+
+/// A row that could not be imported and why.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnresolvedRow {
+    pub line: usize,
+    /// The row as it appeared in the file
+    pub text: String,
+    pub reason: String,
+}
+
+/// A row resolved to a catalog card.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportedItem {
+    pub line: usize,
+    pub card: Card,
+    pub foil: bool,
+    pub count: u32,
+    pub price: Option<String>,
+    pub scanned_at: Option<String>,
+}
+
+/// What the server answers to an import: the rows it could resolve and the rest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub format: ImportFormat,
+    pub items: Vec<ImportedItem>,
+    pub unresolved: Vec<UnresolvedRow>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParsedCsv {
+    pub format: ImportFormat,
+    pub rows: Vec<ImportRow>,
+    /// Rows that couldn't be parsed
+    pub unresolved: Vec<UnresolvedRow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    Empty,
+    /// The header lacks a required column
+    MissingColumn(&'static str),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Empty => write!(f, "the file has no header row"),
+            ImportError::MissingColumn(column) => {
+                write!(f, "the header has no \"{}\" column", column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Parses a Standard or Dreamborn CSV export; the layout is told apart by its header.
+pub fn parse_csv(text: &str) -> Result<ParsedCsv, ImportError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines.next().ok_or(ImportError::Empty)?;
+    let header: Vec<String> = split_record(header.trim_start_matches('\u{feff}'))
+        .iter()
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let required = |name: &'static str| {
+        column(&name.to_ascii_lowercase()).ok_or(ImportError::MissingColumn(name))
+    };
+
+    let set_col = required("Set Number")?;
+    let number_col = required("Card Number")?;
+    let count_col = required("Count")?;
+    let variant_col = column("variant");
+    let price_col = column("price");
+    let scanned_col = column("scannedat");
+    let format = if price_col.is_some() || scanned_col.is_some() || column("card name").is_some() {
+        ImportFormat::Standard
+    } else {
+        ImportFormat::Dreamborn
+    };
+
+    let mut parsed = ParsedCsv {
+        format,
+        rows: Vec::new(),
+        unresolved: Vec::new(),
+    };
+    for (line, text) in lines {
+        let fields = split_record(text);
+        let field = |col: Option<usize>| {
+            col.and_then(|c| fields.get(c))
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+        };
+        let row = (|| {
+            let group = field(Some(set_col)).ok_or("missing set number")?;
+            let card_number = field(Some(number_col))
+                .and_then(|n| n.parse::<u32>().ok())
+                .ok_or("invalid card number")?;
+            let count = field(Some(count_col))
+                .and_then(|n| n.parse::<u32>().ok())
+                .filter(|n| *n > 0)
+                .ok_or("invalid count")?;
+            let foil = match field(variant_col).map(str::to_ascii_lowercase).as_deref() {
+                None | Some("normal") => false,
+                Some("foil") => true,
+                Some(_) => return Err("unknown variant"),
+            };
+            Ok(ImportRow {
+                line,
+                group: group.to_string(),
+                card_number,
+                foil,
+                count,
+                // Exports write "0" when there was no price
+                price: field(price_col)
+                    .filter(|p| p.parse::<f64>().is_ok_and(|p| p > 0.0))
+                    .map(str::to_string),
+                scanned_at: field(scanned_col).map(str::to_string),
+            })
+        })();
+        match row {
+            Ok(row) => parsed.rows.push(row),
+            Err(reason) => parsed.unresolved.push(UnresolvedRow {
+                line,
+                text: text.to_string(),
+                reason: reason.to_string(),
+            }),
+        }
+    }
+    Ok(parsed)
+}
+
+/// The set code a plain set number refers to: Dreamborn writes set 1 as "01".
+pub fn set_code_for_group(group: &str) -> &str {
+    if group.len() > 1 && group.bytes().all(|b| b.is_ascii_digit()) {
+        let trimmed = group.trim_start_matches('0');
+        if trimmed.is_empty() { "0" } else { trimmed }
+    } else {
+        group
+    }
+}
+
+/// Splits a CSV record, honouring double-quoted fields with `""` escapes.
+fn split_record(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_standard() {
+        let csv = "Set Number,Card Number,Variant,Count,Card Name,Rarity,Price,ScannedAt\r\n\
+            1,123,normal,2,Mickey Mouse - Wayward Sorcerer,Common,1.50,2026-02-23T21:55:00.000Z\r\n\
+            P3,45,foil,3,\"Donald Duck, \"\"The\"\" Brave\",Rare,0,2026-02-23T21:56:00.000Z\r\n\
+            \r\n\
+            1,abc,normal,1,Broken,Common,0,\r\n";
+        let parsed = parse_csv(csv).unwrap();
+        assert_eq!(parsed.format, ImportFormat::Standard);
+        assert_eq!(
+            parsed.rows,
+            [
+                ImportRow {
+                    line: 2,
+                    group: "1".into(),
+                    card_number: 123,
+                    foil: false,
+                    count: 2,
+                    price: Some("1.50".into()),
+                    scanned_at: Some("2026-02-23T21:55:00.000Z".into()),
+                },
+                ImportRow {
+                    line: 3,
+                    group: "P3".into(),
+                    card_number: 45,
+                    foil: true,
+                    count: 3,
+                    price: None,
+                    scanned_at: Some("2026-02-23T21:56:00.000Z".into()),
+                },
+            ]
+        );
+        assert_eq!(parsed.unresolved.len(), 1);
+        assert_eq!(parsed.unresolved[0].line, 5);
+        assert_eq!(parsed.unresolved[0].reason, "invalid card number");
+    }
+
+    #[test]
+    fn test_parse_dreamborn() {
+        let parsed =
+            parse_csv("Set Number,Card Number,Variant,Count\n01,123,normal,2\nP3,45,FOIL,1\n")
+                .unwrap();
+        assert_eq!(parsed.format, ImportFormat::Dreamborn);
+        assert_eq!(parsed.rows.len(), 2);
+        assert_eq!(set_code_for_group(&parsed.rows[0].group), "1");
+        assert_eq!(set_code_for_group(&parsed.rows[1].group), "P3");
+        assert!(parsed.rows[1].foil);
+
+        assert_eq!(
+            parse_csv("Name,Count\nMickey,1\n").unwrap_err(),
+            ImportError::MissingColumn("Set Number")
+        );
+        assert_eq!(parse_csv("\n\n").unwrap_err(), ImportError::Empty);
+    }
+}
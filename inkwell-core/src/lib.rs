@@ -1,79 +1,22 @@
-#[cfg(not(target_arch = "wasm32"))]
-use image::DynamicImage;
-#[cfg(not(target_arch = "wasm32"))]
-use opencv::{
-    core::{KeyPoint, Mat, Vector},
-    features2d::AKAZE,
-    prelude::*,
-};
 use serde::{Deserialize, Serialize};
 
-/// Computes AKAZE features for an image.
-/// Returns a tuple of (KeyPoints, Descriptors serialized as Vec<u8>).
-#[cfg(not(target_arch = "wasm32"))]
-pub fn compute_akaze_features(
-    img: &DynamicImage,
-) -> Result<(Vec<KeyPoint>, Vec<u8>), opencv::Error> {
-    // Resize to a reasonable working size (optional, but good for performance)
-    let resized = img.resize(500, 500, image::imageops::FilterType::Lanczos3);
-    let gray = resized.to_luma8();
-
-    // Convert raw pixels to OpenCV Mat
-    let (_width, height) = gray.dimensions();
-
-    // Create Mat from slice (copies data)
-    let mat_1d = Mat::from_slice(gray.as_raw())?;
-
-    // Reshape to correct dimensions: channels=1, rows=height.
-    let mat = mat_1d.reshape(1, height as i32)?;
-
-    // Init AKAZE
-    // Use DESCRIPTOR_MLDB for binary descriptors (Hamming distance) and rotation invariance.
-    let mut akaze = AKAZE::create_def()?;
-
-    // Detect and Compute
-    let mut keypoints = Vector::<KeyPoint>::new();
-    let mut descriptors = Mat::default();
-    let mask = Mat::default();
-
-    akaze.detect_and_compute(&mat, &mask, &mut keypoints, &mut descriptors, false)?;
-
-    // Convert descriptors Mat to Vec<u8> for storage
-    let data_len = descriptors.total() * descriptors.elem_size()?;
-    let mut descriptors_bytes = vec![0u8; data_len];
-    let data_ptr = descriptors.data_bytes()?;
-    descriptors_bytes.copy_from_slice(data_ptr);
-
-    // Convert Vector<KeyPoint> to Vec<KeyPoint>
-    let keypoints_vec: Vec<KeyPoint> = keypoints.to_vec();
-
-    Ok((keypoints_vec, descriptors_bytes))
-}
-
-#[cfg(not(target_arch = "wasm32"))]
-pub const AKAZE_DESC_SIZE: i32 = 61;
-
-/// Helper to reconstruct Mat from bytes
-#[cfg(not(target_arch = "wasm32"))]
-pub fn akaze_bytes_to_mat(bytes: &[u8]) -> Result<Mat, opencv::Error> {
-    if bytes.is_empty() {
-        return Ok(Mat::default());
-    }
-
-    // Create Mat from slice (copies data)
-    let mat_1d = Mat::from_slice(bytes)?;
-
-    // Reshape. Rows = bytes.len() / 61. Cols = 61.
-    let rows = bytes.len() as i32 / AKAZE_DESC_SIZE;
-
-    let mat_view = mat_1d.reshape(1, rows)?;
-
-    // We must ensure we return an owned Mat, not a view/BoxedRef.
-    let mut mat_owned = Mat::default();
-    mat_view.copy_to(&mut mat_owned)?;
+#[cfg(all(feature = "opencv", not(target_arch = "wasm32")))]
+mod akaze;
+#[cfg(feature = "pure-rust")]
+pub mod compact;
+pub mod export;
+pub mod import;
+#[cfg(feature = "pure-rust")]
+pub mod orb;
+pub mod search;
+
+#[cfg(all(feature = "opencv", not(target_arch = "wasm32")))]
+pub use akaze::{
+    AKAZE_DESC_SIZE, akaze_bytes_to_mat, compute_akaze_features, compute_akaze_features_at,
+};
 
-    Ok(mat_owned)
-}
+/// Longest side, in pixels, images are scaled to before feature extraction.
+pub const DEFAULT_WORKING_SIZE: u32 = 500;
 
 /// Preprocesses an image for hashing (Legacy pHash support):
 /// - Resize to 500x500 (Lanczos3)
@@ -107,7 +50,8 @@ pub struct Card {
     /// Example: "8f03c2998f03c299"
     pub phash: String,
 
-    /// AKAZE binary descriptors serialized as bytes
+    /// Binary descriptors the server matches on, serialized as bytes: AKAZE, or the
+    /// pure-Rust ones of [`orb`] in server builds without OpenCV
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub akaze_data: Vec<u8>,
 
@@ -127,6 +71,25 @@ pub struct Card {
     pub card_number: u32,
 }
 
+impl Card {
+    /// A copy without `akaze_data`, which only the matcher needs: for responses and
+    /// anything stored outside the index.
+    pub fn without_descriptors(&self) -> Card {
+        Card {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            subtitle: self.subtitle.clone(),
+            phash: self.phash.clone(),
+            akaze_data: Vec::new(),
+            image_url: self.image_url.clone(),
+            rarity: self.rarity.clone(),
+            promo_grouping: self.promo_grouping.clone(),
+            set_code: self.set_code.clone(),
+            card_number: self.card_number,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ScanResult {
     /// The closest matching card, if any found
@@ -138,6 +101,23 @@ pub struct ScanResult {
     /// Total number of cards successfully scanned globally (persistent)
     #[serde(default)]
     pub global_total_scans: u64,
+
+    /// Server-side log entry for this scan, used to report the correct card
+    #[serde(default)]
+    pub scan_id: Option<i64>,
+}
+
+/// JSON body returned by the API when a request fails.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// Stable machine-readable code (e.g., "undecodable_image")
+    pub code: String,
+
+    /// Human-readable description of what went wrong
+    pub message: String,
+
+    /// Whether sending the same request again may succeed
+    pub retryable: bool,
 }
 
 #[cfg(test)]
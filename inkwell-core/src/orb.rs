@@ -0,0 +1,316 @@
+//! Pure-Rust binary features (ORB-style: FAST corners ranked by Harris response,
+//! intensity-centroid orientation and steered BRIEF descriptors), enabled by the
+//! `pure-rust` feature. Unlike the AKAZE path this needs no OpenCV, so it also runs in
+//! the browser and cross-compiles anywhere. Descriptors are [`ORB_DESC_SIZE`] bytes and
+//! are compared by Hamming distance, see [`crate::compact`].
+
+use image::{DynamicImage, GrayImage, imageops};
+
+/// Bytes per descriptor (256 binary tests).
+pub const ORB_DESC_SIZE: usize = 32;
+
+/// Features kept per reference image in the compact index.
+pub const REFERENCE_FEATURES: usize = 200;
+
+/// Features extracted from a query image.
+pub const QUERY_FEATURES: usize = 300;
+
+/// Minimum intensity difference for a FAST corner.
+const FAST_THRESHOLD: i16 = 20;
+const PYRAMID_LEVELS: usize = 4;
+const PYRAMID_SCALE: f32 = 1.3;
+/// Radius of the patch used for orientation and descriptors.
+const PATCH_RADIUS: i32 = 15;
+/// Keeps rotated test points inside the image (PATCH_RADIUS * sqrt(2), rounded up).
+const BORDER: u32 = 22;
+const HARRIS_K: f32 = 0.04;
+
+/// A detected feature, in the coordinates of the image passed to [`extract`] after
+/// scaling to the working size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    /// Orientation in radians
+    pub angle: f32,
+    pub response: f32,
+    /// Pyramid level the feature was found on
+    pub octave: u8,
+}
+
+/// Extracts up to `max_features` keypoints and their descriptors, scaling the image to
+/// fit `working_size` first. Reference and query images must use the same size.
+/// Descriptors are concatenated, [`ORB_DESC_SIZE`] bytes each.
+pub fn extract(
+    img: &DynamicImage,
+    working_size: u32,
+    max_features: usize,
+) -> (Vec<Keypoint>, Vec<u8>) {
+    let base = img
+        .resize(working_size, working_size, imageops::FilterType::Triangle)
+        .to_luma8();
+    extract_gray(&base, max_features)
+}
+
+/// Like [`extract`] on an image that is already grayscale and at the working size.
+pub fn extract_gray(base: &GrayImage, max_features: usize) -> (Vec<Keypoint>, Vec<u8>) {
+    let pattern = brief_pattern();
+
+    // Split the budget across levels in proportion to their area
+    let factors: Vec<f32> = (0..PYRAMID_LEVELS)
+        .map(|level| PYRAMID_SCALE.powi(-2 * level as i32))
+        .collect();
+    let total: f32 = factors.iter().sum();
+
+    let mut keypoints = Vec::new();
+    let mut descriptors = Vec::new();
+    for (level, factor) in factors.iter().enumerate() {
+        let scale = PYRAMID_SCALE.powi(level as i32);
+        let image = if level == 0 {
+            base.clone()
+        } else {
+            let (w, h) = base.dimensions();
+            let (w, h) = ((w as f32 / scale) as u32, (h as f32 / scale) as u32);
+            if w <= 2 * BORDER || h <= 2 * BORDER {
+                break;
+            }
+            imageops::resize(base, w, h, imageops::FilterType::Triangle)
+        };
+        let budget = (max_features as f32 * factor / total).round() as usize;
+
+        let mut corners = detect_corners(&image);
+        corners.sort_by(|a, b| b.2.total_cmp(&a.2));
+        corners.truncate(budget);
+
+        // BRIEF tests compare single pixels, so they run on a smoothed copy
+        let smoothed = imageops::blur(&image, 2.0);
+        for (x, y, response) in corners {
+            let angle = orientation(&image, x, y);
+            descriptors.extend_from_slice(&describe(&smoothed, x, y, angle, &pattern));
+            keypoints.push(Keypoint {
+                x: x as f32 * scale,
+                y: y as f32 * scale,
+                angle,
+                response,
+                octave: level as u8,
+            });
+        }
+    }
+    (keypoints, descriptors)
+}
+
+/// Offsets of the 16 pixels on the radius-3 circle around a FAST candidate.
+const CIRCLE: [(i32, i32); 16] = [
+    (0, -3),
+    (1, -3),
+    (2, -2),
+    (3, -1),
+    (3, 0),
+    (3, 1),
+    (2, 2),
+    (1, 3),
+    (0, 3),
+    (-1, 3),
+    (-2, 2),
+    (-3, 1),
+    (-3, 0),
+    (-3, -1),
+    (-2, -2),
+    (-1, -3),
+];
+
+/// FAST-9 corners after 3x3 non-maximum suppression, as (x, y, Harris response).
+fn detect_corners(img: &GrayImage) -> Vec<(u32, u32, f32)> {
+    let (w, h) = img.dimensions();
+    if w <= 2 * BORDER || h <= 2 * BORDER {
+        return Vec::new();
+    }
+
+    let mut scores = vec![0u16; (w * h) as usize];
+    for y in BORDER..h - BORDER {
+        for x in BORDER..w - BORDER {
+            scores[(y * w + x) as usize] = fast_score(img, x, y);
+        }
+    }
+
+    let mut corners = Vec::new();
+    for y in BORDER..h - BORDER {
+        for x in BORDER..w - BORDER {
+            let score = scores[(y * w + x) as usize];
+            if score == 0 {
+                continue;
+            }
+            let is_max = (-1..=1).all(|dy: i32| {
+                (-1..=1).all(|dx: i32| {
+                    let neighbor =
+                        scores[((y as i32 + dy) as u32 * w + (x as i32 + dx) as u32) as usize];
+                    (dx == 0 && dy == 0)
+                        || neighbor < score
+                        // Of equal neighbors, keep the first in raster order
+                        || (neighbor == score && (dy > 0 || (dy == 0 && dx > 0)))
+                })
+            });
+            if is_max {
+                corners.push((x, y, harris_response(img, x, y)));
+            }
+        }
+    }
+    corners
+}
+
+/// Sum of absolute differences beyond the threshold over the circle if at least 9
+/// contiguous pixels are all brighter or all darker than the center, otherwise 0.
+fn fast_score(img: &GrayImage, x: u32, y: u32) -> u16 {
+    let center = img.get_pixel(x, y)[0] as i16;
+    let ring = CIRCLE.map(|(dx, dy)| {
+        img.get_pixel((x as i32 + dx) as u32, (y as i32 + dy) as u32)[0] as i16 - center
+    });
+
+    // An arc of 9 always covers at least two of the four compass points
+    let compass = [ring[0], ring[4], ring[8], ring[12]];
+    let brighter = compass.iter().filter(|&&d| d > FAST_THRESHOLD).count();
+    let darker = compass.iter().filter(|&&d| d < -FAST_THRESHOLD).count();
+    if brighter < 2 && darker < 2 {
+        return 0;
+    }
+
+    for sign in [1i16, -1] {
+        let mut run = 0;
+        // Walk the ring twice so arcs wrapping past the start are found
+        for i in 0..32 {
+            if ring[i % 16] * sign > FAST_THRESHOLD {
+                run += 1;
+                if run >= 9 {
+                    return ring
+                        .iter()
+                        .map(|d| (d.abs() - FAST_THRESHOLD).max(0) as u16)
+                        .sum::<u16>()
+                        .max(1);
+                }
+            } else {
+                run = 0;
+            }
+        }
+    }
+    0
+}
+
+/// Harris corner response over a 7x7 window, which ranks corners better than FAST's own score.
+fn harris_response(img: &GrayImage, x: u32, y: u32) -> f32 {
+    let at =
+        |dx: i32, dy: i32| img.get_pixel((x as i32 + dx) as u32, (y as i32 + dy) as u32)[0] as f32;
+    let (mut a, mut b, mut c) = (0.0, 0.0, 0.0);
+    for dy in -3..=3 {
+        for dx in -3..=3 {
+            let ix = at(dx + 1, dy) - at(dx - 1, dy);
+            let iy = at(dx, dy + 1) - at(dx, dy - 1);
+            a += ix * ix;
+            b += iy * iy;
+            c += ix * iy;
+        }
+    }
+    a * b - c * c - HARRIS_K * (a + b) * (a + b)
+}
+
+/// Direction from the keypoint to the intensity centroid of its circular patch.
+fn orientation(img: &GrayImage, x: u32, y: u32) -> f32 {
+    let (mut m10, mut m01) = (0.0f32, 0.0f32);
+    for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+        let half_width = ((PATCH_RADIUS * PATCH_RADIUS - dy * dy) as f32).sqrt() as i32;
+        for dx in -half_width..=half_width {
+            let value = img.get_pixel((x as i32 + dx) as u32, (y as i32 + dy) as u32)[0] as f32;
+            m10 += dx as f32 * value;
+            m01 += dy as f32 * value;
+        }
+    }
+    m01.atan2(m10)
+}
+
+/// 256 binary tests rotated by `angle`; bit `i` is set when the first point of test
+/// `i` is darker than the second.
+fn describe(
+    img: &GrayImage,
+    x: u32,
+    y: u32,
+    angle: f32,
+    pattern: &[[(i8, i8); 2]; 256],
+) -> [u8; ORB_DESC_SIZE] {
+    let (sin, cos) = angle.sin_cos();
+    let sample = |(px, py): (i8, i8)| {
+        let (px, py) = (px as f32, py as f32);
+        let rx = (cos * px - sin * py).round() as i32;
+        let ry = (sin * px + cos * py).round() as i32;
+        img.get_pixel((x as i32 + rx) as u32, (y as i32 + ry) as u32)[0]
+    };
+
+    let mut descriptor = [0u8; ORB_DESC_SIZE];
+    for (i, [p, q]) in pattern.iter().enumerate() {
+        if sample(*p) < sample(*q) {
+            descriptor[i / 8] |= 1 << (i % 8);
+        }
+    }
+    descriptor
+}
+
+/// Test point pairs drawn from an isotropic Gaussian around the keypoint (sigma =
+/// patch size / 5, as in BRIEF). A fixed seed keeps every build's descriptors
+/// compatible, so this must never change without bumping the compact index version.
+fn brief_pattern() -> [[(i8, i8); 2]; 256] {
+    let mut state: u64 = 0x1f2e_3d4c_5b6a_7988;
+    let mut next_f32 = || {
+        // SplitMix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        ((z >> 40) as f32 + 0.5) / (1u64 << 24) as f32
+    };
+    let sigma = (2 * PATCH_RADIUS + 1) as f32 / 5.0;
+    let mut gaussian = || {
+        // Box-Muller
+        let (u, v) = (next_f32(), next_f32());
+        let value = (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos() * sigma;
+        value
+            .round()
+            .clamp(-PATCH_RADIUS as f32, PATCH_RADIUS as f32) as i8
+    };
+
+    let mut pattern = [[(0, 0); 2]; 256];
+    for pair in pattern.iter_mut() {
+        *pair = [(gaussian(), gaussian()), (gaussian(), gaussian())];
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    /// Blocky pseudo-random texture with plenty of corners.
+    fn texture(w: u32, h: u32) -> GrayImage {
+        GrayImage::from_fn(w, h, |x, y| {
+            let cell = (x / 12).wrapping_mul(7919) ^ (y / 12).wrapping_mul(104_729);
+            Luma([(cell.wrapping_mul(2_654_435_761) >> 24) as u8])
+        })
+    }
+
+    #[test]
+    fn test_extract_is_deterministic_and_bounded() {
+        let img = texture(240, 320);
+        let (keypoints, descriptors) = extract_gray(&img, 150);
+
+        assert!(!keypoints.is_empty());
+        assert!(keypoints.len() <= 150 + PYRAMID_LEVELS);
+        assert_eq!(descriptors.len(), keypoints.len() * ORB_DESC_SIZE);
+        assert_eq!(extract_gray(&img, 150).1, descriptors);
+    }
+
+    #[test]
+    fn test_flat_image_has_no_features() {
+        let img = GrayImage::from_pixel(200, 200, Luma([128]));
+        let (keypoints, descriptors) = extract_gray(&img, 100);
+        assert!(keypoints.is_empty() && descriptors.is_empty());
+    }
+}
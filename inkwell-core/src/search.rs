@@ -0,0 +1,204 @@
+//! Forgiving card search for typing a card in by hand: every word of the query must
+//! match the card's name or subtitle (as a word prefix, a substring, or with a typo),
+//! or its set and number, written `123`, `#123`, `1/123` or `P3-45`.
+
+use crate::Card;
+use crate::import::set_code_for_group;
+
+/// How well `query` matches `card`, higher is better; `None` if some word of the query
+/// matches nothing.
+pub fn score(query: &str, card: &Card) -> Option<u32> {
+    let words: Vec<String> = normalize(&card.name)
+        .into_iter()
+        .chain(normalize(&card.subtitle))
+        .collect();
+    let mut total = 0;
+    let mut any = false;
+    for token in query.split_whitespace() {
+        let token = token.to_lowercase();
+        if let Some((group, number)) = token.split_once(['/', '-']).filter(|(g, n)| {
+            !g.is_empty() && !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())
+        }) {
+            // A set reference never falls back to the text
+            if !group_matches(group, card) || number.parse() != Ok(card.card_number) {
+                return None;
+            }
+            total += 8;
+            any = true;
+            continue;
+        }
+        if let Some(number) = token.strip_prefix('#') {
+            if number.parse() != Ok(card.card_number) {
+                return None;
+            }
+            total += 6;
+            any = true;
+            continue;
+        }
+        if token.parse() == Ok(card.card_number) {
+            total += 6;
+            any = true;
+            continue;
+        }
+        for part in normalize(&token) {
+            total += words
+                .iter()
+                .map(|word| word_score(&part, word))
+                .max()
+                .filter(|&score| score > 0)?;
+            any = true;
+        }
+    }
+    any.then_some(total)
+}
+
+/// The best `limit` matches of `query`, best first; equally good ones keep their order.
+pub fn search<'a>(
+    query: &str,
+    cards: impl IntoIterator<Item = &'a Card>,
+    limit: usize,
+) -> Vec<&'a Card> {
+    let mut matches: Vec<(u32, &Card)> = cards
+        .into_iter()
+        .filter_map(|card| score(query, card).map(|score| (score, card)))
+        .collect();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, card)| card)
+        .collect()
+}
+
+/// Whether a set reference such as "1", "01" or "P3" names the card's set or promo grouping.
+fn group_matches(group: &str, card: &Card) -> bool {
+    match &card.promo_grouping {
+        Some(promo) => promo.eq_ignore_ascii_case(group),
+        None => card
+            .set_code
+            .eq_ignore_ascii_case(set_code_for_group(group)),
+    }
+}
+
+/// Lowercase words, with apostrophes dropped ("Mickey's" is "mickeys") and any other
+/// punctuation separating words.
+fn normalize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+/// How well a query word matches a word of the card; 0 for no match.
+fn word_score(token: &str, word: &str) -> u32 {
+    if word == token {
+        return 6;
+    }
+    if word.starts_with(token) {
+        return 5;
+    }
+    let len = token.chars().count();
+    if len >= 3 && word.contains(token) {
+        return 3;
+    }
+    let typos = match len {
+        0..=3 => return 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    // The whole word misspelled, or the start of it while still typing
+    let prefix: String = word.chars().take(len).collect();
+    if distance(token, word) <= typos || distance(token, &prefix) <= typos {
+        2
+    } else {
+        0
+    }
+}
+
+/// Edit distance counting a swap of two neighbouring letters as one edit.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: &str, name: &str, subtitle: &str, set: &str, number: u32) -> Card {
+        Card {
+            id: id.into(),
+            name: name.into(),
+            subtitle: subtitle.into(),
+            phash: String::new(),
+            akaze_data: Vec::new(),
+            image_url: String::new(),
+            rarity: "Common".into(),
+            promo_grouping: None,
+            set_code: set.into(),
+            card_number: number,
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let cards = [
+            card("a", "Mickey Mouse", "Wayward Sorcerer", "1", 12),
+            card("b", "Mickey Mouse", "Brave Little Tailor", "1", 115),
+            card("c", "Minnie Mouse", "Beloved Princess", "1", 13),
+            Card {
+                promo_grouping: Some("P3".into()),
+                ..card("d", "Mickey Mouse", "Friendly Face", "5", 7)
+            },
+        ];
+        let ids = |query: &str| -> Vec<&str> {
+            search(query, &cards, 10)
+                .iter()
+                .map(|c| c.id.as_str())
+                .collect()
+        };
+
+        assert_eq!(ids("mickey brave"), ["b"]);
+        assert_eq!(ids("Mick tail"), ["b"]);
+        // Typos, while typing and in whole words
+        assert_eq!(ids("mikcey sorc"), ["a"]);
+        assert_eq!(ids("mouse wayward sorcerrer"), ["a"]);
+        assert_eq!(ids("min"), ["c"]);
+
+        assert_eq!(ids("1/115"), ["b"]);
+        assert_eq!(ids("01-13"), ["c"]);
+        assert_eq!(ids("p3/7"), ["d"]);
+        assert!(ids("5/7").is_empty());
+        assert_eq!(ids("mickey #12"), ["a"]);
+        assert_eq!(ids("mouse 13"), ["c"]);
+        assert!(ids("goofy").is_empty());
+        assert!(ids("  ").is_empty());
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("mickey", "mickey"), 0);
+        assert_eq!(distance("mikcey", "mickey"), 1);
+        assert_eq!(distance("mikey", "mickey"), 1);
+        assert_eq!(distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{ingest, jobs::TriggerError, references, scans, AppState};
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use inkwell_server::{features, index};
+use serde::Deserialize;
+use sqlx::Row;
+
+/// Routes mounted under `/api/admin`, all guarded by [`require_admin`].
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/ingestion", get(ingestion_status))
+        .route("/ingestion/run", post(trigger_ingestion))
+        .route("/ingestion/runs", get(ingestion_history))
+        .route("/ingestion/runs/:id", get(ingestion_run))
+        .route("/ingestion/failed", get(failed_cards))
+        .route("/scans", get(list_scans))
+        .route("/scans/:id", get(scan_detail))
+        .route("/scans/:id/image", get(scan_image))
+        .route("/scans/:id/label", delete(reject_label))
+        .route("/scans/:id/label/approve", post(approve_label))
+        .route("/scans/:id/reference", post(add_scan_reference))
+        .route("/cards/:id/references", get(card_references))
+        .route("/references/:id", delete(delete_reference))
+        .route("/index/reload", post(reload_index))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+/// Rejects requests that don't carry `Authorization: Bearer <admin.token>`.
+async fn require_admin(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let admin = &state.config.admin;
+    check_bearer(req, next, &[admin.token.as_deref()]).await
+}
+
+/// Rejects requests that carry neither `admin.write_token` nor `admin.token`; for the
+/// public routes that change data.
+pub async fn require_write(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let admin = &state.config.admin;
+    check_bearer(
+        req,
+        next,
+        &[admin.write_token.as_deref(), admin.token.as_deref()],
+    )
+    .await
+}
+
+/// Runs the request if its bearer token is one of the configured `accepted` ones; with
+/// none configured, the routes are disabled.
+async fn check_bearer(req: Request, next: Next, accepted: &[Option<&str>]) -> Response {
+    let accepted: Vec<&str> = accepted.iter().flatten().copied().collect();
+    if accepted.is_empty() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token)
+            if accepted
+                .iter()
+                .any(|expected| constant_time_eq(token.as_bytes(), expected.as_bytes())) =>
+        {
+            next.run(req).await
+        }
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn internal_error(e: sqlx::Error) -> StatusCode {
+    tracing::error!("Admin query failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Current ingestion state: the running job (if any), the last completed run and
+/// how much of the catalog has usable features.
+async fn ingestion_status(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let running = ingest::latest_run(&state.pool, Some("running"))
+        .await
+        .map_err(internal_error)?;
+    let last_completed = ingest::latest_run(&state.pool, Some("completed"))
+        .await
+        .map_err(internal_error)?;
+    let last_run = ingest::latest_run(&state.pool, None)
+        .await
+        .map_err(internal_error)?;
+
+    let row = sqlx::query(&format!(
+        "SELECT COUNT(*) AS total, COALESCE(SUM({0} IS NULL OR length({0}) = 0), 0) AS missing FROM cards",
+        features::DESCRIPTOR_COLUMN
+    ))
+    .fetch_one(&state.pool)
+    .await
+    .map_err(internal_error)?;
+    let total: i64 = row.get("total");
+    let missing: i64 = row.get("missing");
+
+    let (indexed, views) = {
+        let identifier = state.identifier.read().await;
+        (identifier.len(), identifier.view_count())
+    };
+    let reference_views = references::count_by_kind(&state.pool)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(serde_json::json!({
+        "running": running,
+        "last_completed": last_completed,
+        "last_run": last_run,
+        "catalog": {
+            "cards": total,
+            "features": features::NAME,
+            "missing_features": missing,
+            "indexed": indexed,
+            "views": views,
+            "reference_views": reference_views,
+        },
+    })))
+}
+
+/// Starts an ingestion run immediately; responds 409 if one is already in progress.
+async fn trigger_ingestion(State(state): State<AppState>) -> (StatusCode, Json<serde_json::Value>) {
+    match state.ingestion.trigger().await {
+        Ok(run_id) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "run_id": run_id })),
+        ),
+        Err(e @ TriggerError::AlreadyRunning) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+        Err(e @ TriggerError::Database(_)) => {
+            tracing::error!("{}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+}
+
+fn default_limit() -> i64 {
+    20
+}
+
+async fn ingestion_history(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<ingest::IngestionRun>>, StatusCode> {
+    let limit = params.limit.clamp(1, 200);
+    let offset = params.offset.max(0);
+    let runs = ingest::list_runs(&state.pool, limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(runs))
+}
+
+async fn ingestion_run(
+    State(state): State<AppState>,
+    Path(run_id): Path<i64>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let run = ingest::fetch_run(&state.pool, run_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let errors = ingest::list_run_errors(&state.pool, run_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(serde_json::json!({
+        "run": run,
+        "errors": errors,
+    })))
+}
+
+/// Cards that failed in an earlier run and are queued to be retried first.
+async fn failed_cards(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ingest::FailedCard>>, StatusCode> {
+    let cards = ingest::list_failed_cards(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(cards))
+}
+
+#[derive(Deserialize)]
+struct ScanParams {
+    #[serde(default = "default_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+    /// Only scans with this outcome (`match`, `no_match` or `error`)
+    outcome: Option<String>,
+    /// Only scans with (`true`) or without (`false`) a user-reported card
+    labeled: Option<bool>,
+    /// Only labeled scans whose label is (`true`) or is still waiting to be (`false`)
+    /// approved
+    approved: Option<bool>,
+}
+
+/// Most recent scans first.
+async fn list_scans(
+    State(state): State<AppState>,
+    Query(params): Query<ScanParams>,
+) -> Result<Json<Vec<scans::Scan>>, StatusCode> {
+    let limit = params.limit.clamp(1, 200);
+    let offset = params.offset.max(0);
+    let scans = scans::list_scans(
+        &state.pool,
+        params.outcome.as_deref(),
+        params.labeled,
+        params.approved,
+        limit,
+        offset,
+    )
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(scans))
+}
+
+async fn scan_detail(
+    State(state): State<AppState>,
+    Path(scan_id): Path<i64>,
+) -> Result<Json<scans::Scan>, StatusCode> {
+    let scan = scans::fetch_scan(&state.pool, scan_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(scan))
+}
+
+/// The stored capture of a scan; 404 if captures were disabled or it has been removed.
+async fn scan_image(
+    State(state): State<AppState>,
+    Path(scan_id): Path<i64>,
+) -> Result<Response, StatusCode> {
+    let scan = scans::fetch_scan(&state.pool, scan_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (Some(dir), Some(file)) = (&state.config.paths.captured_images, &scan.image_path) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let bytes = tokio::fs::read(std::path::Path::new(dir).join(file))
+        .await
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                StatusCode::NOT_FOUND
+            } else {
+                tracing::error!("Failed to read capture {}: {}", file, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    let content_type = match scan.image_format.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("bmp") => "image/bmp",
+        Some("tiff") => "image/tiff",
+        _ => "application/octet-stream",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], bytes).into_response())
+}
+
+fn reference_error(e: Box<dyn std::error::Error + Send + Sync>) -> StatusCode {
+    tracing::error!("Failed to update reference views: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Approves a user's label, so the scan can become a reference view and part of the
+/// `verify export-labels` dataset. 409 if the scan has no label.
+async fn approve_label(
+    State(state): State<AppState>,
+    Path(scan_id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    scans::fetch_scan(&state.pool, scan_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !scans::approve_label(&state.pool, scan_id)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    tracing::info!("Label of scan {} approved", scan_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Discards a user's label.
+async fn reject_label(
+    State(state): State<AppState>,
+    Path(scan_id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    if !scans::clear_label(&state.pool, scan_id)
+        .await
+        .map_err(internal_error)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::info!("Label of scan {} rejected", scan_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Adds the stored capture of a scan with an approved label as a reference view of the
+/// labeled card.
+async fn add_scan_reference(
+    State(state): State<AppState>,
+    Path(scan_id): Path<i64>,
+) -> Result<(StatusCode, Json<serde_json::Value>), StatusCode> {
+    let scan = scans::fetch_scan(&state.pool, scan_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (Some(card_id), Some(_)) = (scan.label_card_id, scan.label_approved_at) else {
+        return Err(StatusCode::CONFLICT);
+    };
+    let (Some(dir), Some(file)) = (&state.config.paths.captured_images, &scan.image_path) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let bytes = tokio::fs::read(std::path::Path::new(dir).join(file))
+        .await
+        .map_err(|e| {
+            tracing::warn!("Failed to read capture {}: {}", file, e);
+            StatusCode::NOT_FOUND
+        })?;
+    let descriptors = references::extract_view(bytes, state.config.matching.working_size)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Scan {} can't be used as a reference: {}", scan_id, e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+    let reference_id = references::add_view(
+        &state.pool,
+        &state.identifier,
+        &card_id,
+        "capture",
+        Some(file),
+        Some(scan_id),
+        &descriptors,
+    )
+    .await
+    .map_err(reference_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "reference_id": reference_id,
+            "card_id": card_id,
+            "features": descriptors.len() / features::DESCRIPTOR_SIZE,
+        })),
+    ))
+}
+
+async fn card_references(
+    State(state): State<AppState>,
+    Path(card_id): Path<String>,
+) -> Result<Json<Vec<index::ReferenceView>>, StatusCode> {
+    let views = index::list_references(&state.pool, &card_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(views))
+}
+
+async fn delete_reference(
+    State(state): State<AppState>,
+    Path(reference_id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    let card_id = index::delete_reference(&state.pool, reference_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    references::refresh(&state.pool, &state.identifier, &card_id)
+        .await
+        .map_err(reference_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reloads every card and reference view from the database, e.g. after `add-references`.
+async fn reload_index(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let fresh = index::load_index(&state.pool)
+        .await
+        .map_err(internal_error)?;
+    let mut identifier = state.identifier.write().await;
+    identifier.replace_index(fresh);
+    state.compact_index.invalidate().await;
+
+    Ok(Json(serde_json::json!({
+        "indexed": identifier.len(),
+        "views": identifier.view_count(),
+    })))
+}
@@ -0,0 +1,218 @@
+//! Synthetic variants of reference renders that imitate phone captures: slight rotation
+//! and perspective, blur, white-balance shifts and sleeve glare. Ingestion extracts
+//! features from them and stores them as `augmented` reference views.
+
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+
+/// `kind` of the reference views generated from these variants.
+pub const AUGMENTED_KIND: &str = "augmented";
+
+/// Fill for the area a warp uncovers, close to the dark surroundings of a typical scan.
+const BACKGROUND: Rgb<u8> = Rgb([40, 40, 40]);
+
+/// Deterministic variants of `img`: the same image and seed always give the same output,
+/// so re-ingesting a card reproduces its views.
+pub fn variants(img: &DynamicImage, count: usize, seed: u64) -> Vec<DynamicImage> {
+    let base = img.to_rgb8();
+    let mut rng = SplitMix64(seed);
+    (0..count)
+        .map(|_| DynamicImage::ImageRgb8(variant(&base, &mut rng)))
+        .collect()
+}
+
+/// Seed for a card's variants, stable across runs and platforms (FNV-1a of the id).
+pub fn seed_for(card_id: &str) -> u64 {
+    card_id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn variant(base: &RgbImage, rng: &mut SplitMix64) -> RgbImage {
+    let (w, h) = (base.width() as f64, base.height() as f64);
+
+    // Rotate by up to 8 degrees around the center, then move each corner by up to 6%
+    let angle = rng.range(-8.0, 8.0).to_radians();
+    let (sin, cos) = angle.sin_cos();
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    let corners = [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)];
+    let mut moved = [(0.0, 0.0); 4];
+    for (dst, &(x, y)) in moved.iter_mut().zip(&corners) {
+        let (dx, dy) = (x - cx, y - cy);
+        *dst = (
+            cx + dx * cos - dy * sin + rng.range(-0.06, 0.06) * w,
+            cy + dx * sin + dy * cos + rng.range(-0.06, 0.06) * h,
+        );
+    }
+    let mut out = match homography(&moved, &corners) {
+        Some(output_to_input) => warp(base, &output_to_input),
+        None => base.clone(),
+    };
+
+    let sigma = rng.range(0.0, 1.5) as f32;
+    if sigma > 0.3 {
+        out = imageops::blur(&out, sigma);
+    }
+
+    let gains = [
+        rng.range(0.85, 1.15),
+        rng.range(0.85, 1.15),
+        rng.range(0.85, 1.15),
+    ];
+    let brightness = rng.range(-20.0, 20.0);
+    for pixel in out.pixels_mut() {
+        for (c, gain) in pixel.0.iter_mut().zip(gains) {
+            *c = (*c as f64 * gain + brightness).clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    if rng.next_f64() < 0.5 {
+        let center = (rng.range(0.1, 0.9) * w, rng.range(0.1, 0.9) * h);
+        let radius = rng.range(0.1, 0.3) * w;
+        let strength = rng.range(0.4, 0.8);
+        add_glare(&mut out, center, radius, strength);
+    }
+
+    out
+}
+
+/// Brightens a soft disc towards white, like a light reflected off a sleeve.
+fn add_glare(img: &mut RgbImage, center: (f64, f64), radius: f64, strength: f64) {
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let d = ((x as f64 - center.0).powi(2) + (y as f64 - center.1).powi(2)).sqrt();
+        let falloff = (1.0 - d / radius).max(0.0).powi(2) * strength;
+        for c in pixel.0.iter_mut() {
+            *c = (*c as f64 + (255.0 - *c as f64) * falloff) as u8;
+        }
+    }
+}
+
+/// Resamples `img` with bilinear interpolation, where `output_to_input` maps each output
+/// pixel to the point it takes its color from.
+fn warp(img: &RgbImage, output_to_input: &[f64; 9]) -> RgbImage {
+    let (w, h) = img.dimensions();
+    RgbImage::from_fn(w, h, |x, y| {
+        let (sx, sy) = project(output_to_input, x as f64 + 0.5, y as f64 + 0.5);
+        sample(img, sx - 0.5, sy - 0.5)
+    })
+}
+
+fn sample(img: &RgbImage, x: f64, y: f64) -> Rgb<u8> {
+    let (w, h) = img.dimensions();
+    if x < 0.0 || y < 0.0 || x > (w - 1) as f64 || y > (h - 1) as f64 {
+        return BACKGROUND;
+    }
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+    let mut out = [0u8; 3];
+    for (c, value) in out.iter_mut().enumerate() {
+        let top =
+            img.get_pixel(x0, y0)[c] as f64 * (1.0 - fx) + img.get_pixel(x1, y0)[c] as f64 * fx;
+        let bottom =
+            img.get_pixel(x0, y1)[c] as f64 * (1.0 - fx) + img.get_pixel(x1, y1)[c] as f64 * fx;
+        *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    Rgb(out)
+}
+
+fn project(m: &[f64; 9], x: f64, y: f64) -> (f64, f64) {
+    let w = m[6] * x + m[7] * y + m[8];
+    (
+        (m[0] * x + m[1] * y + m[2]) / w,
+        (m[3] * x + m[4] * y + m[5]) / w,
+    )
+}
+
+/// The perspective transform (row-major 3x3, `m[8] == 1`) taking each `from` point to
+/// the matching `to` point, or `None` if the points are degenerate.
+fn homography(from: &[(f64, f64); 4], to: &[(f64, f64); 4]) -> Option<[f64; 9]> {
+    // Two equations per correspondence in the eight unknowns m[0..8]
+    let mut a = [[0.0; 9]; 8];
+    for (i, (&(x, y), &(u, v))) in from.iter().zip(to).enumerate() {
+        a[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+        a[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+    }
+
+    // Gaussian elimination with partial pivoting on the augmented matrix
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|&r, &s| a[r][col].abs().total_cmp(&a[s][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in a.iter_mut().skip(col + 1) {
+            let factor = row[col] / pivot_row[col];
+            for (value, p) in row.iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * p;
+            }
+        }
+    }
+    let mut m = [0.0; 9];
+    m[8] = 1.0;
+    for row in (0..8).rev() {
+        let known: f64 = (row + 1..8).map(|c| a[row][c] * m[c]).sum();
+        m[row] = (a[row][8] - known) / a[row][row];
+    }
+    Some(m)
+}
+
+/// Small, seedable PRNG; we only need reproducible jitter, not statistical quality.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_homography_maps_corners() {
+        let from = [(0.0, 0.0), (100.0, 0.0), (100.0, 140.0), (0.0, 140.0)];
+        let to = [(5.0, -3.0), (96.0, 4.0), (108.0, 150.0), (-2.0, 133.0)];
+        let m = homography(&from, &to).unwrap();
+        for (&(x, y), &(u, v)) in from.iter().zip(&to) {
+            let (px, py) = project(&m, x, y);
+            assert!((px - u).abs() < 1e-6 && (py - v).abs() < 1e-6);
+        }
+
+        let degenerate = [(0.0, 0.0); 4];
+        assert!(homography(&degenerate, &to).is_none());
+    }
+
+    #[test]
+    fn test_variants_are_reproducible() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(60, 84, |x, y| {
+            Rgb([(x * 4) as u8, (y * 3) as u8, ((x + y) * 2) as u8])
+        }));
+        let seed = seed_for("1-12-345");
+        let first = variants(&img, 3, seed);
+        let again = variants(&img, 3, seed);
+
+        assert_eq!(first.len(), 3);
+        for (a, b) in first.iter().zip(&again) {
+            assert_eq!(a.to_rgb8().into_raw(), b.to_rgb8().into_raw());
+            assert_eq!(a.to_rgb8().dimensions(), (60, 84));
+        }
+        assert_ne!(first[0].to_rgb8().into_raw(), first[1].to_rgb8().into_raw());
+        assert_ne!(seed, seed_for("1-12-346"));
+    }
+}
@@ -1,142 +1,930 @@
 use image::io::Reader as ImageReader;
-use inkwell_core::{akaze_bytes_to_mat, Card, ScanResult};
-use opencv::{
-    core::{DMatch, Mat, Vector, NORM_HAMMING},
-    features2d::BFMatcher,
-    prelude::*,
+use inkwell_core::{compact::CompactIndex, orb, Card};
+use inkwell_server::{
+    augment::AUGMENTED_KIND,
+    calibration::Calibration,
+    config::Config,
+    dataset::{collect_images, label_for},
+    features,
+    identify::Identifier,
+    index,
 };
-use sqlx::{sqlite::SqlitePoolOptions, Row};
+use serde::Serialize;
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
+use std::collections::HashMap;
 use std::env;
+use std::path::Path;
+use std::time::Instant;
+
+const TOP_K: usize = 5;
+
+const USAGE: &str = "Usage:
+  verify <image_path>
+      Identify a single image and print the result.
+  verify eval <dir> [--json <path|->] [--compare-augmented | --compare-orb]
+      Run every image under <dir> through the pipeline and report accuracy. With
+      --compare-augmented, also evaluate without the synthetic views generated at
+      ingest (ingest.augment_variants) and report the difference. With
+      --compare-orb, also evaluate the compact index and matcher used for on-device
+      identification and report the difference. Only one comparison runs at a time.
+  verify calibrate <dir> [--out <path>]
+      Fit confidence calibration on the labeled images under <dir> and write the
+      parameters (default: calibration.json) for the server's CALIBRATION_FILE.
+  verify export-labels <dir>
+      Copy every scan users have labeled with the correct card, together with its
+      stored capture, into <dir> as an eval/calibrate data set.
+
+Labels for eval come from a sidecar `<image>.json` ({\"card_id\": \"1-12-345\"}, or
+null for an image that should not match anything) or else from the file name:
+`<card_id>.jpg` or `<card_id>__<anything>.jpg`. Files named `negative*` are
+expected not to match.
+
+The database and [matching] settings come from the server's configuration
+(INKWELL_CONFIG or ./inkwell.toml, plus environment overrides).";
+
+async fn connect(config: &Config) -> Result<Pool<Sqlite>, Box<dyn std::error::Error>> {
+    Ok(SqlitePoolOptions::new()
+        .connect(&config.database.url)
+        .await?)
+}
+
+/// Loads the identifier the server would use, leaving out reference views of the
+/// `excluded_kinds`.
+async fn load_identifier(
+    config: &Config,
+    excluded_kinds: &[&str],
+) -> Result<Identifier, Box<dyn std::error::Error>> {
+    let pool = connect(config).await?;
+
+    println!("Loading cards from DB...");
+    let calibration = match &config.matching.calibration_file {
+        Some(path) => Some(Calibration::load(path).map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let identifier = Identifier::new(index::load_index_excluding(&pool, excluded_kinds).await?)
+        .with_calibration(calibration)
+        .with_params(config.matching.params());
+    println!(
+        "Loaded {} cards with {} reference views ({} confidence, accepting at {:.2}).",
+        identifier.len(),
+        identifier.view_count(),
+        if identifier.is_calibrated() {
+            "calibrated"
+        } else {
+            "uncalibrated"
+        },
+        identifier.params().accept_threshold
+    );
+    Ok(identifier)
+}
+
+/// What `eval` runs images through.
+#[derive(Clone, Copy)]
+enum Pipeline<'a> {
+    /// The server's matcher, on AKAZE or (without OpenCV) ORB features
+    Server(&'a Identifier),
+    /// The pure-Rust ORB matcher clients run on-device
+    Orb(&'a CompactIndex),
+}
+
+impl Pipeline<'_> {
+    fn cards(&self) -> &[Card] {
+        match self {
+            Pipeline::Server(identifier) => identifier.cards(),
+            Pipeline::Orb(index) => &index.cards,
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
 
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: verify <image_path>");
-        return Ok(());
+    let config = Config::load(None)?;
+
+    match args.get(1).map(String::as_str) {
+        Some("eval") => {
+            let (dir, options) = dir_and_options(
+                &args,
+                &["--json"],
+                &["--compare-augmented", "--compare-orb"],
+            );
+            let json_out = options.get("--json").copied().flatten();
+            match (
+                options.contains_key("--compare-augmented"),
+                options.contains_key("--compare-orb"),
+            ) {
+                (true, true) => Err(
+                    "--compare-augmented and --compare-orb can't be combined; run them separately"
+                        .into(),
+                ),
+                (true, false) => {
+                    run_augmentation_comparison(&config, Path::new(dir), json_out).await
+                }
+                (false, true) => run_orb_comparison(&config, Path::new(dir), json_out).await,
+                (false, false) => {
+                    let identifier = load_identifier(&config, &[]).await?;
+                    let report = evaluate(Pipeline::Server(&identifier), Path::new(dir))?;
+                    write_json(&report, json_out)
+                }
+            }
+        }
+        Some("calibrate") => {
+            let (dir, options) = dir_and_options(&args, &["--out"], &[]);
+            let identifier = load_identifier(&config, &[]).await?;
+            run_calibrate(
+                &identifier,
+                Path::new(dir),
+                options
+                    .get("--out")
+                    .copied()
+                    .flatten()
+                    .unwrap_or("calibration.json"),
+            )
+        }
+        Some("export-labels") => {
+            let [_, _, dir] = args.as_slice() else {
+                usage_error()
+            };
+            export_labels(&config, Path::new(dir)).await
+        }
+        Some("-h") | Some("--help") | None => {
+            eprintln!("{}", USAGE);
+            Ok(())
+        }
+        Some(image_path) => {
+            let identifier = load_identifier(&config, &[]).await?;
+            identify_one(&identifier, image_path)
+        }
+    }
+}
+
+/// Parses `<dir>` and any of the given options following the subcommand, exiting with
+/// usage on anything else. Each of `flags` takes a value; `switches` map to `None`.
+fn dir_and_options<'a>(
+    args: &'a [String],
+    flags: &[&str],
+    switches: &[&str],
+) -> (&'a str, HashMap<&'a str, Option<&'a str>>) {
+    let Some(dir) = args.get(2) else {
+        usage_error()
+    };
+    let mut options = HashMap::new();
+    let mut rest = args[3..].iter();
+    while let Some(arg) = rest.next() {
+        let arg = arg.as_str();
+        if flags.contains(&arg) {
+            let Some(value) = rest.next() else {
+                usage_error()
+            };
+            options.insert(arg, Some(value.as_str()));
+        } else if switches.contains(&arg) {
+            options.insert(arg, None);
+        } else {
+            usage_error()
+        }
     }
-    let image_path = &args[1];
+    (dir.as_str(), options)
+}
 
-    // Connect to DB
-    let database_url =
-        std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:inkwell.db".to_string());
-    let pool = SqlitePoolOptions::new().connect(&database_url).await?;
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
 
-    // Load Cards
-    println!("Loading cards from DB...");
-    // Select akaze_data
-    let rows = sqlx::query("SELECT id, name, subtitle, phash, image_url, akaze_data, rarity, promo_grouping, set_code, card_number FROM cards")
-        .fetch_all(&pool)
-        .await?;
-
-    let mut train_vec = Vector::<Mat>::new();
-    let mut cards = Vec::new();
-
-    for row in rows {
-        let akaze_data: Vec<u8> = row.get("akaze_data");
-        // Only load if akaze_data exists
-        if akaze_data.is_empty() {
+/// Writes each approved labeled scan's capture as `<card_id>__scan_<id>.<ext>` with a sidecar
+/// recording the label, so the directory can be fed straight to `eval` or `calibrate`.
+async fn export_labels(config: &Config, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(captures_dir) = &config.paths.captured_images else {
+        return Err("paths.captured_images (CAPTURED_IMAGES_DIR) is not set".into());
+    };
+    let pool = connect(config).await?;
+    let rows = sqlx::query(
+        "SELECT id, card_id, label_card_id, image_path FROM scans WHERE label_card_id IS NOT NULL AND label_approved_at IS NOT NULL ORDER BY id",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    std::fs::create_dir_all(dir)?;
+    let (mut exported, mut missing) = (0, 0);
+    for row in &rows {
+        let id: i64 = row.get("id");
+        let label: String = row.get("label_card_id");
+        let predicted: Option<String> = row.get("card_id");
+        let Some(image_path) = row.get::<Option<String>, _>("image_path") else {
+            missing += 1;
             continue;
+        };
+
+        let source = Path::new(captures_dir).join(&image_path);
+        let extension = source.extension().and_then(|e| e.to_str()).unwrap_or("bin");
+        let target = dir.join(format!("{}__scan_{}.{}", label, id, extension));
+        if let Err(e) = std::fs::copy(&source, &target) {
+            eprintln!("Warning: scan {}: {}: {}", id, source.display(), e);
+            missing += 1;
+            continue;
+        }
+        std::fs::write(
+            target.with_extension("json"),
+            serde_json::to_string_pretty(&serde_json::json!({
+                "card_id": label,
+                "scan_id": id,
+                "predicted": predicted,
+            }))?,
+        )?;
+        exported += 1;
+    }
+
+    println!(
+        "Exported {} labeled scans to {} ({} without a stored capture).",
+        exported,
+        dir.display(),
+        missing
+    );
+    Ok(())
+}
+
+fn identify_one(
+    identifier: &Identifier,
+    image_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "Computing {} features for {}...",
+        features::NAME,
+        image_path
+    );
+    let raw_img = ImageReader::open(image_path)?.decode()?;
+    let ranking = identifier.rank(&raw_img)?;
+    println!(
+        "{} features computed in {:?}",
+        ranking.query_features, ranking.extract_time
+    );
+    println!("Matched in {:?}", ranking.match_time);
+
+    // Report
+    match ranking.candidates.first() {
+        None => println!("No match found."),
+        Some(best) => {
+            let card = &identifier.cards()[best.card_idx];
+            let confidence = identifier.confidence(&ranking);
+            if identifier.accepts(&ranking) {
+                println!("Match Found:");
+                println!("  Name: {} ({})", card.name, card.subtitle);
+                println!("  ID: {}", card.id);
+                println!("  Good Matches: {}", best.votes);
+                println!("  Confidence: {:.2}", confidence);
+                println!(
+                    "JSON: {}",
+                    serde_json::to_string(&identifier.scan_result(&ranking))?
+                );
+            } else {
+                println!(
+                    "Best match {} had only {} good matches (confidence {:.2}). Below threshold.",
+                    card.name, best.votes, confidence
+                );
+            }
         }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SampleResult {
+    path: String,
+    expected: Option<String>,
+    /// Top candidates as (card id, votes), best first
+    top: Vec<(String, usize)>,
+    confidence: f64,
+    /// Whether the pipeline would have returned a card (enough good matches and at
+    /// least the configured confidence)
+    accepted: bool,
+    /// Whether the best card had enough good matches, whatever its confidence. The
+    /// threshold table filters on this, since `accepted` already applies the
+    /// configured threshold.
+    enough_votes: bool,
+    extract_ms: f64,
+    match_ms: f64,
+    error: Option<String>,
+}
 
-        let card = Card {
-            id: row.get("id"),
-            name: row.get("name"),
-            subtitle: row.get("subtitle"),
-            phash: row.get("phash"),
-            akaze_data: akaze_data.clone(),
-            image_url: row.get("image_url"),
-            rarity: row.get("rarity"),
-            promo_grouping: row.get("promo_grouping"),
-            set_code: row.get("set_code"),
-            card_number: row.get("card_number"),
+impl SampleResult {
+    fn predicted(&self) -> Option<&str> {
+        self.top.first().map(|(id, _)| id.as_str())
+    }
+}
+
+#[derive(Serialize)]
+struct Confusion {
+    expected: Option<String>,
+    predicted: Option<String>,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct ThresholdStats {
+    threshold: f64,
+    /// Samples that would have been returned as a match at this threshold
+    accepted: usize,
+    correct: usize,
+    false_positives: usize,
+    /// False positives over all evaluated samples
+    false_positive_rate: f64,
+    /// Correct over accepted
+    precision: f64,
+    /// Correct over labeled positive samples
+    recall: f64,
+}
+
+#[derive(Serialize)]
+struct LatencyStats {
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+    mean_ms: f64,
+}
+
+#[derive(Serialize)]
+struct EvalReport {
+    total: usize,
+    positives: usize,
+    negatives: usize,
+    errors: usize,
+    top1_accuracy: f64,
+    top5_accuracy: f64,
+    /// Accuracy of what the server would actually return (accepted and correct, or
+    /// rejected for negatives)
+    pipeline_accuracy: f64,
+    confusions: Vec<Confusion>,
+    thresholds: Vec<ThresholdStats>,
+    latency_total: LatencyStats,
+    latency_extract: LatencyStats,
+    latency_match: LatencyStats,
+    samples: Vec<SampleResult>,
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+fn latency_stats(mut values_ms: Vec<f64>) -> LatencyStats {
+    if values_ms.is_empty() {
+        return LatencyStats {
+            p50_ms: 0.0,
+            p90_ms: 0.0,
+            p99_ms: 0.0,
+            max_ms: 0.0,
+            mean_ms: 0.0,
         };
+    }
+    values_ms.sort_by(|a, b| a.total_cmp(b));
+    // Nearest-rank percentile
+    let pct = |p: f64| {
+        let rank = ((p / 100.0) * values_ms.len() as f64).ceil() as usize;
+        values_ms[rank.clamp(1, values_ms.len()) - 1]
+    };
+    LatencyStats {
+        p50_ms: pct(50.0),
+        p90_ms: pct(90.0),
+        p99_ms: pct(99.0),
+        max_ms: *values_ms.last().unwrap(),
+        mean_ms: values_ms.iter().sum::<f64>() / values_ms.len() as f64,
+    }
+}
 
-        if let Ok(m) = akaze_bytes_to_mat(&akaze_data) {
-            train_vec.push(m);
-            cards.push(card);
+fn build_report(samples: Vec<SampleResult>) -> EvalReport {
+    let evaluated: Vec<&SampleResult> = samples.iter().filter(|s| s.error.is_none()).collect();
+    let positives = evaluated.iter().filter(|s| s.expected.is_some()).count();
+    let negatives = evaluated.len() - positives;
+
+    let mut top1 = 0;
+    let mut top5 = 0;
+    let mut pipeline_correct = 0;
+    let mut confusion_counts: HashMap<(Option<String>, Option<String>), usize> = HashMap::new();
+
+    for s in &evaluated {
+        let predicted = s.predicted();
+        match &s.expected {
+            Some(expected) => {
+                if predicted == Some(expected.as_str()) {
+                    top1 += 1;
+                    if s.accepted {
+                        pipeline_correct += 1;
+                    }
+                }
+                if s.top.iter().take(TOP_K).any(|(id, _)| id == expected) {
+                    top5 += 1;
+                }
+            }
+            None => {
+                if !s.accepted {
+                    pipeline_correct += 1;
+                }
+            }
+        }
+
+        // What the server would have answered, compared with the label
+        let answered = if s.accepted { predicted } else { None };
+        if answered != s.expected.as_deref() {
+            *confusion_counts
+                .entry((s.expected.clone(), answered.map(str::to_string)))
+                .or_insert(0) += 1;
         }
     }
-    println!("Loaded {} cards.", cards.len());
 
-    // Hash Input Image
-    println!("Computing AKAZE for {}...", image_path);
-    let start_hash = std::time::Instant::now();
-    let raw_img = ImageReader::open(image_path)?.decode()?;
+    let mut confusions: Vec<Confusion> = confusion_counts
+        .into_iter()
+        .map(|((expected, predicted), count)| Confusion {
+            expected,
+            predicted,
+            count,
+        })
+        .collect();
+    confusions.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.expected.cmp(&b.expected))
+            .then_with(|| a.predicted.cmp(&b.predicted))
+    });
+
+    let thresholds = (0..=20)
+        .map(|step| {
+            let threshold = step as f64 / 20.0;
+            let accepted: Vec<&&SampleResult> = evaluated
+                .iter()
+                .filter(|s| s.enough_votes && s.confidence >= threshold)
+                .collect();
+            let correct = accepted
+                .iter()
+                .filter(|s| s.expected.as_deref() == s.predicted())
+                .count();
+            let false_positives = accepted.len() - correct;
+            ThresholdStats {
+                threshold,
+                accepted: accepted.len(),
+                correct,
+                false_positives,
+                false_positive_rate: ratio(false_positives, evaluated.len()),
+                precision: ratio(correct, accepted.len()),
+                recall: ratio(correct, positives),
+            }
+        })
+        .collect();
+
+    let extract: Vec<f64> = evaluated.iter().map(|s| s.extract_ms).collect();
+    let matching: Vec<f64> = evaluated.iter().map(|s| s.match_ms).collect();
+    let total: Vec<f64> = evaluated
+        .iter()
+        .map(|s| s.extract_ms + s.match_ms)
+        .collect();
 
-    let (_kp, query_desc_bytes) = inkwell_core::compute_akaze_features(&raw_img)?;
-    if query_desc_bytes.is_empty() {
-        println!("No features found in query image.");
-        return Ok(());
+    EvalReport {
+        total: samples.len(),
+        positives,
+        negatives,
+        errors: samples.len() - evaluated.len(),
+        top1_accuracy: ratio(top1, positives),
+        top5_accuracy: ratio(top5, positives),
+        pipeline_accuracy: ratio(pipeline_correct, evaluated.len()),
+        confusions,
+        thresholds,
+        latency_total: latency_stats(total),
+        latency_extract: latency_stats(extract),
+        latency_match: latency_stats(matching),
+        samples,
     }
+}
+
+fn evaluate_one(pipeline: Pipeline, path: &Path) -> SampleResult {
+    let mut result = SampleResult {
+        path: path.display().to_string(),
+        expected: None,
+        top: Vec::new(),
+        confidence: 0.0,
+        accepted: false,
+        enough_votes: false,
+        extract_ms: 0.0,
+        match_ms: 0.0,
+        error: None,
+    };
 
-    let query_mat = akaze_bytes_to_mat(&query_desc_bytes)?;
-    println!("Hash computed in {:?}", start_hash.elapsed());
+    let outcome = (|| -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        result.expected = label_for(path)?;
+        let img = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+        match pipeline {
+            Pipeline::Server(identifier) => {
+                let ranking = identifier.rank(&img)?;
 
-    // Find Best Match
-    let start_match = std::time::Instant::now();
-    let mut matcher = BFMatcher::create(NORM_HAMMING, false)?;
+                result.top = identifier.top_candidates(&ranking, TOP_K);
+                if let Some(best) = ranking.candidates.first() {
+                    result.confidence = identifier.confidence(&ranking);
+                    result.accepted = identifier.accepts(&ranking);
+                    result.enough_votes = best.votes >= identifier.params().min_good_matches;
+                }
+                result.extract_ms = ranking.extract_time.as_secs_f64() * 1000.0;
+                result.match_ms = ranking.match_time.as_secs_f64() * 1000.0;
+            }
+            Pipeline::Orb(index) => {
+                let start = Instant::now();
+                let (_, query) = orb::extract(&img, index.working_size, orb::QUERY_FEATURES);
+                result.extract_ms = start.elapsed().as_secs_f64() * 1000.0;
 
-    matcher.add(&train_vec)?;
-    matcher.train()?;
+                let start = Instant::now();
+                let ranking = index.rank(&query);
+                result.match_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+                result.top = ranking
+                    .iter()
+                    .take(TOP_K)
+                    .map(|&(card, votes)| (index.cards[card].id.clone(), votes))
+                    .collect();
+                if let Some(&(_, votes)) = ranking.first() {
+                    result.confidence = index.confidence(votes);
+                    result.enough_votes = votes >= index.accept_votes as usize;
+                    result.accepted = result.enough_votes;
+                }
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = outcome {
+        result.error = Some(e.to_string());
+    }
+    result
+}
 
-    // KNN MATCH ON BATCHED DATA
-    let mut matches = Vector::<Vector<DMatch>>::new();
-    matcher.knn_match(&query_mat, &mut matches, 2, &Mat::default(), false)?;
+fn evaluate_dir(
+    pipeline: Pipeline,
+    dir: &Path,
+) -> Result<Vec<SampleResult>, Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    collect_images(dir, &mut paths)?;
+    paths.sort();
+    println!(
+        "Evaluating {} images from {}...",
+        paths.len(),
+        dir.display()
+    );
 
-    let mut votes = std::collections::HashMap::new();
-    let ratio_thresh = 0.75;
+    let known_ids: std::collections::HashSet<String> =
+        pipeline.cards().iter().map(|c| c.id.clone()).collect();
 
-    // Process matches
-    for m in matches {
-        let m = m.to_vec();
-        if let [m0, m1, ..] = m.as_slice() {
-            if m0.distance < ratio_thresh * m1.distance {
-                let img_idx = m0.img_idx as usize;
-                *votes.entry(img_idx).or_insert(0) += 1;
+    let mut samples = Vec::with_capacity(paths.len());
+    for (i, path) in paths.iter().enumerate() {
+        let sample = evaluate_one(pipeline, path);
+        if let Some(expected) = &sample.expected {
+            if !known_ids.contains(expected) {
+                eprintln!(
+                    "Warning: {} is labeled {} which is not in the index",
+                    sample.path, expected
+                );
             }
         }
+        if let Some(e) = &sample.error {
+            eprintln!("Error: {}: {}", sample.path, e);
+        }
+        if (i + 1) % 50 == 0 {
+            println!("  {}/{}", i + 1, paths.len());
+        }
+        samples.push(sample);
     }
+    Ok(samples)
+}
 
-    let mut max_good_matches = 0;
-    let mut best_card: Option<Card> = None;
+fn evaluate(pipeline: Pipeline, dir: &Path) -> Result<EvalReport, Box<dyn std::error::Error>> {
+    let samples = evaluate_dir(pipeline, dir)?;
+    let report = build_report(samples);
+    print_report(&report);
+    Ok(report)
+}
 
-    for (card_idx, vote_count) in votes {
-        if vote_count > max_good_matches {
-            max_good_matches = vote_count;
-            best_card = cards.get(card_idx).cloned();
+fn write_json(
+    report: &impl Serialize,
+    json_out: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match json_out {
+        Some("-") => println!("{}", serde_json::to_string_pretty(report)?),
+        Some(path) => {
+            std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+            println!("Wrote JSON report to {}", path);
         }
+        None => {}
     }
-    println!("Matched in {:?}", start_match.elapsed());
+    Ok(())
+}
 
-    // Report
-    const MIN_GOOD_MATCHES: usize = 50;
-    if let Some(card) = best_card {
-        if max_good_matches >= MIN_GOOD_MATCHES {
-            let confidence = (max_good_matches as f64 / 100.0).min(1.0);
-            println!("Match Found:");
-            println!("  Name: {} ({})", card.name, card.subtitle);
-            println!("  ID: {}", card.id);
-            println!("  Good Matches: {}", max_good_matches);
-            println!("  Confidence: {:.2}", confidence);
-
-            let result = ScanResult {
-                card: Some(card),
-                confidence,
-                global_total_scans: 0,
-            };
-            println!("JSON: {}", serde_json::to_string(&result)?);
-        } else {
+/// Evaluates `dir` once with only the renders and real reference views, then again with
+/// the synthetic views added, to show what augmentation buys.
+async fn run_augmentation_comparison(
+    config: &Config,
+    dir: &Path,
+    json_out: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("== Without augmented views ==");
+    let baseline = {
+        let identifier = load_identifier(config, &[AUGMENTED_KIND]).await?;
+        evaluate(Pipeline::Server(&identifier), dir)?
+    };
+
+    println!();
+    println!("== With augmented views ==");
+    let identifier = load_identifier(config, &[]).await?;
+    let augmented = evaluate(Pipeline::Server(&identifier), dir)?;
+
+    print_comparison("Augmentation effect", &baseline, &augmented);
+    write_json(
+        &serde_json::json!({ "without_augmented": baseline, "with_augmented": augmented }),
+        json_out,
+    )
+}
+
+/// Evaluates `dir` with the server's pipeline and with the compact index clients match
+/// on-device. Against a server built with OpenCV this shows what dropping AKAZE would
+/// cost. The compact index only holds each card's render, so compare against a database
+/// without extra views for a like-for-like result.
+async fn run_orb_comparison(
+    config: &Config,
+    dir: &Path,
+    json_out: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("== Server ({}) ==", features::NAME);
+    let server = {
+        let identifier = load_identifier(config, &[]).await?;
+        evaluate(Pipeline::Server(&identifier), dir)?
+    };
+
+    println!();
+    println!("== On-device (ORB) ==");
+    let pool = connect(config).await?;
+    let index = index::load_compact_index(&pool, config.matching.working_size).await?;
+    println!(
+        "Loaded {} cards with {} ORB descriptors, accepting at {} votes.",
+        index.cards.len(),
+        index.descriptor_count(),
+        index.accept_votes
+    );
+    if index.cards.is_empty() {
+        return Err("no cards have ORB features yet; run ingestion first".into());
+    }
+    let orb = evaluate(Pipeline::Orb(&index), dir)?;
+
+    print_comparison(
+        &format!("Server ({}) -> on-device", features::NAME),
+        &server,
+        &orb,
+    );
+    write_json(
+        &serde_json::json!({ "server": server, "on_device": orb }),
+        json_out,
+    )
+}
+
+fn print_comparison(title: &str, before: &EvalReport, after: &EvalReport) {
+    println!();
+    println!("{}:", title);
+    for (name, before, after) in [
+        ("Top-1 accuracy", before.top1_accuracy, after.top1_accuracy),
+        (
+            "Pipeline accuracy",
+            before.pipeline_accuracy,
+            after.pipeline_accuracy,
+        ),
+    ] {
+        println!(
+            "  {:<20} {:>5.1}% -> {:>5.1}%  ({:+.1} points)",
+            name,
+            before * 100.0,
+            after * 100.0,
+            (after - before) * 100.0
+        );
+    }
+    for (name, before, after) in [
+        (
+            "Extract latency p50",
+            before.latency_extract.p50_ms,
+            after.latency_extract.p50_ms,
+        ),
+        (
+            "Match latency p50",
+            before.latency_match.p50_ms,
+            after.latency_match.p50_ms,
+        ),
+    ] {
+        println!("  {:<20} {:>5.1}ms -> {:>5.1}ms", name, before, after);
+    }
+}
+
+/// Calibration inputs for samples that produced a candidate: (best votes,
+/// second-best votes, whether the best candidate is the labeled card).
+fn calibration_samples(samples: &[SampleResult]) -> Vec<(usize, usize, bool)> {
+    samples
+        .iter()
+        .filter(|s| s.error.is_none())
+        .filter_map(|s| {
+            let (_, best) = s.top.first()?;
+            let second = s.top.get(1).map_or(0, |(_, votes)| *votes);
+            let correct = s.expected.is_some() && s.expected.as_deref() == s.predicted();
+            Some((*best, second, correct))
+        })
+        .collect()
+}
+
+fn run_calibrate(
+    identifier: &Identifier,
+    dir: &Path,
+    out: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut samples = evaluate_dir(Pipeline::Server(identifier), dir)?;
+    let training = calibration_samples(&samples);
+    let calibration = Calibration::fit(&training).ok_or(
+        "calibration needs images whose best candidate is right and images where it is wrong",
+    )?;
+
+    std::fs::write(out, serde_json::to_string_pretty(&calibration)?)?;
+    println!(
+        "Fitted calibration on {} samples: intercept {:.3}, weights {:?}",
+        calibration.samples, calibration.intercept, calibration.weights
+    );
+    println!("Wrote calibration to {}", out);
+
+    // Show how the fitted confidence behaves on the same images
+    for s in samples.iter_mut().filter(|s| s.error.is_none()) {
+        if let Some((_, best)) = s.top.first() {
+            let second = s.top.get(1).map_or(0, |(_, votes)| *votes);
+            s.confidence = calibration.predict(*best, second);
+            let params = identifier.params();
+            s.enough_votes = *best >= params.min_good_matches;
+            s.accepted = s.enough_votes && s.confidence >= params.accept_threshold;
+        }
+    }
+    print_report(&build_report(samples));
+
+    Ok(())
+}
+
+fn print_report(report: &EvalReport) {
+    println!();
+    println!(
+        "Samples: {} ({} positive, {} negative, {} errors)",
+        report.total, report.positives, report.negatives, report.errors
+    );
+    println!("Top-1 accuracy:    {:.1}%", report.top1_accuracy * 100.0);
+    println!(
+        "Top-{} accuracy:    {:.1}%",
+        TOP_K,
+        report.top5_accuracy * 100.0
+    );
+    println!(
+        "Pipeline accuracy: {:.1}%",
+        report.pipeline_accuracy * 100.0
+    );
+
+    println!();
+    println!("Threshold  Accepted  Precision  Recall  FP rate");
+    for t in &report.thresholds {
+        println!(
+            "{:>9.2}  {:>8}  {:>8.1}%  {:>5.1}%  {:>6.2}%",
+            t.threshold,
+            t.accepted,
+            t.precision * 100.0,
+            t.recall * 100.0,
+            t.false_positive_rate * 100.0
+        );
+    }
+
+    println!();
+    println!(
+        "Latency (total):   p50 {:.1}ms  p90 {:.1}ms  p99 {:.1}ms  max {:.1}ms",
+        report.latency_total.p50_ms,
+        report.latency_total.p90_ms,
+        report.latency_total.p99_ms,
+        report.latency_total.max_ms
+    );
+    println!(
+        "Latency (extract): p50 {:.1}ms  p90 {:.1}ms  p99 {:.1}ms",
+        report.latency_extract.p50_ms, report.latency_extract.p90_ms, report.latency_extract.p99_ms
+    );
+    println!(
+        "Latency (match):   p50 {:.1}ms  p90 {:.1}ms  p99 {:.1}ms",
+        report.latency_match.p50_ms, report.latency_match.p90_ms, report.latency_match.p99_ms
+    );
+
+    if !report.confusions.is_empty() {
+        println!();
+        println!("Confusions (expected -> answered):");
+        for c in report.confusions.iter().take(20) {
             println!(
-                "Best match {} had only {} good matches. Below threshold.",
-                card.name, max_good_matches
+                "  {:>4}x  {} -> {}",
+                c.count,
+                c.expected.as_deref().unwrap_or("<none>"),
+                c.predicted.as_deref().unwrap_or("<no match>")
             );
         }
-    } else {
-        println!("No match found.");
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A result scored like the AKAZE build's defaults, whichever features this build uses.
+    fn sample(expected: Option<&str>, top: &[(&str, usize)]) -> SampleResult {
+        let votes = top.first().map(|t| t.1).unwrap_or(0);
+        let confidence = (votes as f64 / 100.0).min(1.0);
+        SampleResult {
+            path: String::new(),
+            expected: expected.map(str::to_string),
+            top: top.iter().map(|(id, v)| (id.to_string(), *v)).collect(),
+            confidence,
+            accepted: votes >= 50,
+            enough_votes: votes >= 50,
+            extract_ms: 10.0,
+            match_ms: 5.0,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_calibration_samples() {
+        let samples = vec![
+            sample(Some("a"), &[("a", 90), ("b", 10)]),
+            sample(Some("a"), &[("b", 60), ("a", 55)]),
+            sample(None, &[("a", 70)]),
+            sample(Some("c"), &[]),
+        ];
+        assert_eq!(
+            calibration_samples(&samples),
+            vec![(90, 10, true), (60, 55, false), (70, 0, false)]
+        );
+    }
+
+    #[test]
+    fn test_report_metrics() {
+        let report = build_report(vec![
+            sample(Some("a"), &[("a", 90), ("b", 10)]),
+            sample(Some("a"), &[("b", 60), ("a", 55)]),
+            sample(Some("c"), &[("d", 20)]),
+            sample(None, &[("a", 70)]),
+        ]);
+
+        assert_eq!(report.positives, 3);
+        assert_eq!(report.negatives, 1);
+        assert!((report.top1_accuracy - 1.0 / 3.0).abs() < 1e-9);
+        assert!((report.top5_accuracy - 2.0 / 3.0).abs() < 1e-9);
+        assert!((report.pipeline_accuracy - 0.25).abs() < 1e-9);
+
+        let at_zero = &report.thresholds[0];
+        assert_eq!(at_zero.accepted, 3);
+        assert_eq!(at_zero.correct, 1);
+        assert_eq!(at_zero.false_positives, 2);
+
+        let at_080 = report
+            .thresholds
+            .iter()
+            .find(|t| (t.threshold - 0.8).abs() < 1e-9)
+            .unwrap();
+        assert_eq!(at_080.accepted, 1);
+        assert_eq!(at_080.false_positives, 0);
+
+        assert_eq!(report.confusions.len(), 3);
+        assert_eq!(report.latency_total.p50_ms, 15.0);
+    }
+
+    #[test]
+    fn test_thresholds_below_the_configured_one() {
+        // Accepted as if the configured threshold were 0.8
+        let samples = [
+            sample(Some("a"), &[("a", 90)]),
+            sample(Some("b"), &[("b", 60)]),
+            sample(None, &[("a", 70)]),
+        ]
+        .into_iter()
+        .map(|mut s| {
+            s.accepted = s.enough_votes && s.confidence >= 0.8;
+            s
+        })
+        .collect();
+        let report = build_report(samples);
+
+        let at = |threshold: f64| {
+            report
+                .thresholds
+                .iter()
+                .find(|t| (t.threshold - threshold).abs() < 1e-9)
+                .unwrap()
+        };
+        assert_eq!(at(0.5).accepted, 3);
+        assert_eq!(at(0.5).correct, 2);
+        assert_eq!(at(0.5).false_positives, 1);
+        assert_eq!(at(0.65).accepted, 2);
+        assert_eq!(at(0.8).accepted, 1);
+        assert!((report.pipeline_accuracy - 2.0 / 3.0).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Acceptance threshold used when none is configured. The uncalibrated confidence is
+/// `votes / (2 * min_good_matches)`, so this is reached exactly at `min_good_matches`
+/// votes: 50 with AKAZE, 20 with the pure-Rust features.
+pub const DEFAULT_ACCEPT_THRESHOLD: f64 = 0.5;
+
+/// Number of inputs to the calibration model, see [`features`].
+pub const FEATURE_COUNT: usize = 3;
+
+/// Model inputs derived from the votes of the best and second-best candidates:
+/// the inlier count, the vote margin (both log-scaled) and the second-best ratio.
+pub fn features(best: usize, second: usize) -> [f64; FEATURE_COUNT] {
+    let second = second.min(best);
+    [
+        (best as f64).ln_1p(),
+        ((best - second) as f64).ln_1p(),
+        if best == 0 {
+            1.0
+        } else {
+            second as f64 / best as f64
+        },
+    ]
+}
+
+/// Logistic model mapping a ranking to the probability that its top card is correct.
+/// Fitted by `verify calibrate` on a labeled image set.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Calibration {
+    pub intercept: f64,
+    /// Coefficients in the order returned by [`features`]
+    pub weights: [f64; FEATURE_COUNT],
+    /// Number of labeled samples the model was fitted on
+    #[serde(default)]
+    pub samples: usize,
+}
+
+impl Calibration {
+    pub fn load(path: &str) -> Result<Self, BoxError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read calibration file {}: {}", path, e))?;
+        let calibration: Calibration = serde_json::from_str(&text)
+            .map_err(|e| format!("invalid calibration file {}: {}", path, e))?;
+        if !calibration.intercept.is_finite() || calibration.weights.iter().any(|w| !w.is_finite())
+        {
+            return Err(format!("calibration file {} has non-finite parameters", path).into());
+        }
+        Ok(calibration)
+    }
+
+    pub fn predict(&self, best: usize, second: usize) -> f64 {
+        let x = features(best, second);
+        sigmoid(self.intercept + dot(&self.weights, &x))
+    }
+
+    /// Fits the model to `(best votes, second-best votes, top card was correct)` samples
+    /// with Newton's method and a small ridge penalty. Returns `None` unless both
+    /// outcomes are present.
+    pub fn fit(samples: &[(usize, usize, bool)]) -> Option<Self> {
+        const ITERATIONS: usize = 50;
+        const RIDGE: f64 = 1e-3;
+        const N: usize = FEATURE_COUNT + 1;
+
+        let positives = samples.iter().filter(|s| s.2).count();
+        if positives == 0 || positives == samples.len() {
+            return None;
+        }
+
+        // Design rows with a leading 1 for the intercept
+        let rows: Vec<([f64; N], f64)> = samples
+            .iter()
+            .map(|&(best, second, correct)| {
+                let x = features(best, second);
+                let mut row = [1.0; N];
+                row[1..].copy_from_slice(&x);
+                (row, if correct { 1.0 } else { 0.0 })
+            })
+            .collect();
+
+        let mut beta = [0.0; N];
+        for _ in 0..ITERATIONS {
+            let mut gradient = [0.0; N];
+            let mut hessian = [[0.0; N]; N];
+            for (row, y) in &rows {
+                let p = sigmoid(dot(&beta, row));
+                let w = (p * (1.0 - p)).max(1e-9);
+                for (i, xi) in row.iter().enumerate() {
+                    gradient[i] += (p - y) * xi;
+                    for (j, xj) in row.iter().enumerate() {
+                        hessian[i][j] += w * xi * xj;
+                    }
+                }
+            }
+            // Penalize the weights but not the intercept
+            let ridge = RIDGE * rows.len() as f64;
+            for (i, b) in beta.iter().enumerate().skip(1) {
+                gradient[i] += ridge * b;
+                hessian[i][i] += ridge;
+            }
+
+            let step = solve(hessian, gradient)?;
+            for (b, s) in beta.iter_mut().zip(step) {
+                *b -= s;
+            }
+            if step.iter().all(|s| s.abs() < 1e-8) {
+                break;
+            }
+        }
+
+        let mut weights = [0.0; FEATURE_COUNT];
+        weights.copy_from_slice(&beta[1..]);
+        Some(Calibration {
+            intercept: beta[0],
+            weights,
+            samples: samples.len(),
+        })
+    }
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+fn dot<const N: usize>(a: &[f64; N], b: &[f64; N]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Solves `a * x = b` by Gaussian elimination with partial pivoting.
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col];
+        let pivot_rhs = b[col];
+        for (row, rhs) in a.iter_mut().zip(b.iter_mut()).skip(col + 1) {
+            let factor = row[col] / pivot_row[col];
+            for (v, p) in row.iter_mut().zip(pivot_row).skip(col) {
+                *v -= factor * p;
+            }
+            *rhs -= factor * pivot_rhs;
+        }
+    }
+
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let rest: f64 = (row + 1..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_orders_confident_rankings_higher() {
+        // Clear winners are right, close calls are mostly wrong
+        let mut samples = Vec::new();
+        for i in 0..40 {
+            samples.push((80 + i, 5 + i % 7, true));
+            samples.push((30 + i % 10, 25 + i % 5, i % 4 == 0));
+        }
+        samples.push((60, 55, true));
+        samples.push((120, 10, false));
+
+        let calibration = Calibration::fit(&samples).unwrap();
+        let strong = calibration.predict(100, 8);
+        let weak = calibration.predict(32, 28);
+        assert!(strong > 0.9, "strong = {}", strong);
+        assert!(weak < 0.5, "weak = {}", weak);
+        assert_eq!(calibration.samples, samples.len());
+    }
+
+    #[test]
+    fn test_fit_needs_both_outcomes() {
+        assert!(Calibration::fit(&[(80, 5, true), (90, 1, true)]).is_none());
+    }
+
+    #[test]
+    fn test_solve() {
+        let x = solve([[2.0, 1.0], [1.0, 3.0]], [3.0, 5.0]).unwrap();
+        assert!((x[0] - 0.8).abs() < 1e-9);
+        assert!((x[1] - 1.4).abs() < 1e-9);
+    }
+}
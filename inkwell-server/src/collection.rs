@@ -0,0 +1,121 @@
+//! CSV imports (see [`inkwell_core::import`]): resolving exported rows to catalog cards,
+//! and the server-side collection (`collection` table) they can be merged into and
+//! exported from.
+
+use inkwell_core::export::ExportItem;
+use inkwell_core::import::{ImportResult, ImportedItem, ParsedCsv, UnresolvedRow};
+use inkwell_core::Card;
+use inkwell_server::index;
+use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite};
+use std::collections::HashMap;
+
+/// Looks up the card of every parsed row; rows of unknown cards join the unresolved ones.
+pub async fn resolve(pool: &Pool<Sqlite>, parsed: ParsedCsv) -> Result<ImportResult, sqlx::Error> {
+    let mut result = ImportResult {
+        format: parsed.format,
+        items: Vec::with_capacity(parsed.rows.len()),
+        unresolved: parsed.unresolved,
+    };
+    // Exports hold one row per finish, so the same card often comes up twice
+    let mut cards: HashMap<(String, u32), Option<Card>> = HashMap::new();
+    for row in parsed.rows {
+        let key = (row.group.clone(), row.card_number);
+        let card = match cards.get(&key) {
+            Some(card) => card.clone(),
+            None => {
+                let card = index::fetch_card_by_group(pool, &row.group, row.card_number).await?;
+                cards.insert(key, card.clone());
+                card
+            }
+        };
+        match card {
+            Some(card) => result.items.push(ImportedItem {
+                line: row.line,
+                card,
+                foil: row.foil,
+                count: row.count,
+                price: row.price,
+                scanned_at: row.scanned_at,
+            }),
+            None => result.unresolved.push(UnresolvedRow {
+                line: row.line,
+                text: format!("{},{}", row.group, row.card_number),
+                reason: format!("no card {} #{} in the catalog", row.group, row.card_number),
+            }),
+        }
+    }
+    result.unresolved.sort_by_key(|row| row.line);
+    Ok(result)
+}
+
+/// Adds imported items to the collection: quantities of cards already in it (in the same
+/// finish) go up, and a price from the file replaces the stored one. Returns the number
+/// of copies added.
+pub async fn merge(pool: &Pool<Sqlite>, items: &[ImportedItem]) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut copies = 0;
+    for item in items {
+        sqlx::query(
+            "INSERT INTO collection (card_id, quantity, is_foil, price, added_at)
+             VALUES (?, ?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))
+             ON CONFLICT(card_id, is_foil) DO UPDATE SET
+                quantity = collection.quantity + excluded.quantity,
+                price = COALESCE(excluded.price, collection.price)",
+        )
+        .bind(&item.card.id)
+        .bind(item.count)
+        .bind(item.foil)
+        .bind(&item.price)
+        .bind(&item.scanned_at)
+        .execute(&mut *tx)
+        .await?;
+        copies += item.count as u64;
+    }
+    tx.commit().await?;
+    Ok(copies)
+}
+
+/// Every card in the collection, ready for [`inkwell_core::export::export`].
+pub async fn export_items(pool: &Pool<Sqlite>) -> Result<Vec<ExportItem>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT card_id, is_foil, quantity, price, added_at AS scanned_at
+         FROM collection ORDER BY added_at, id",
+    )
+    .fetch_all(pool)
+    .await?;
+    with_cards(pool, &rows).await
+}
+
+/// Turns rows with `card_id`, `is_foil`, `quantity`, `price` and `scanned_at` columns
+/// into export items. Rows of cards no longer in the catalog are left out.
+pub async fn with_cards(
+    pool: &Pool<Sqlite>,
+    rows: &[SqliteRow],
+) -> Result<Vec<ExportItem>, sqlx::Error> {
+    let mut ids: Vec<String> = rows.iter().map(|row| row.get("card_id")).collect();
+    ids.sort();
+    ids.dedup();
+    let cards: HashMap<String, _> = index::fetch_card_metadata(pool, &ids)
+        .await?
+        .into_iter()
+        .map(|card| (card.id.clone(), card))
+        .collect();
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let card_id: String = row.get("card_id");
+            let Some(card) = cards.get(&card_id) else {
+                tracing::warn!("Leaving unknown card {} out of the export", card_id);
+                return None;
+            };
+            Some(ExportItem {
+                card: card.clone(),
+                foil: row.get("is_foil"),
+                quantity: row.get("quantity"),
+                price: row.get("price"),
+                scanned_at: row.get("scanned_at"),
+            })
+        })
+        .collect())
+}
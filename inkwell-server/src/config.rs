@@ -0,0 +1,528 @@
+use crate::{
+    calibration::DEFAULT_ACCEPT_THRESHOLD,
+    identify::{MatchParams, MIN_GOOD_MATCHES, RATIO_THRESH},
+};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+/// Config file read when neither `--config` nor `INKWELL_CONFIG` names one. It's
+/// optional: without it the defaults (plus environment overrides) apply.
+pub const DEFAULT_CONFIG_PATH: &str = "inkwell.toml";
+
+/// Default ingestion schedule: every day at 04:00 UTC.
+/// Fields are `sec min hour day-of-month month day-of-week [year]`.
+pub const DEFAULT_SCHEDULE: &str = "0 0 4 * * *";
+
+/// Each variant is matched like a separate card image, so this bounds the index growth.
+const MAX_AUGMENT_VARIANTS: usize = 32;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub paths: PathsConfig,
+    pub matching: MatchingConfig,
+    pub ingest: IngestConfig,
+    pub scans: ScansConfig,
+    pub admin: AdminConfig,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the HTTP server binds to
+    pub addr: String,
+    /// Serve Prometheus metrics at `/metrics`
+    pub metrics: bool,
+    /// Reverse proxies (e.g. a local cloudflared) whose `CF-Connecting-IP` and
+    /// `X-Forwarded-For` headers are believed. Anyone else can set them, so requests
+    /// from other peers are logged with the peer address.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Where reference card images are downloaded to and served from
+    pub card_images: String,
+    /// Built web client, served for every other path
+    pub dist: String,
+    /// When set, every uploaded scan is saved here and linked from the scan log
+    pub captured_images: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchingConfig {
+    /// Lowe's ratio test threshold
+    pub ratio_thresh: f32,
+    /// Votes the best card needs before it can be returned at all
+    pub min_good_matches: usize,
+    /// Longest side images are scaled to before feature extraction. Changing it
+    /// requires re-extracting reference features.
+    pub working_size: u32,
+    /// Minimum confidence for a match to be returned
+    pub accept_threshold: f64,
+    /// Parameters written by `verify calibrate`
+    pub calibration_file: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IngestConfig {
+    /// Cron expression (with seconds), or `off`
+    pub schedule: String,
+    pub on_startup: bool,
+    /// Cards processed at once
+    pub concurrency: usize,
+    /// Upper bound on request starts per second, shared by all concurrent downloads
+    pub requests_per_second: u32,
+    pub request_timeout_secs: u64,
+    /// Total attempts per request, including the first one
+    pub max_attempts: u32,
+    /// Synthetic variants (rotation, perspective, blur, color shift, glare) of each card
+    /// render added as extra reference views; 0 disables augmentation
+    pub augment_variants: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScansConfig {
+    /// Scan log entries without an approved label (and their captures) older than this
+    /// are deleted; 0 keeps them
+    pub retention_days: u32,
+    /// Oldest captures without an approved label are deleted once they exceed this; 0
+    /// means no limit. Their log entries are kept.
+    pub max_capture_mb: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token required by `/api/admin` routes; admin routes are disabled when unset.
+    pub token: Option<String>,
+    /// Bearer token for the public routes that change data (e.g. collection imports),
+    /// which also accept `token`; they are disabled when neither is set.
+    pub write_token: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: "0.0.0.0:4000".to_string(),
+            metrics: true,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:inkwell.db".to_string(),
+            max_connections: 5,
+        }
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        Self {
+            card_images: "card_images".to_string(),
+            dist: "dist".to_string(),
+            captured_images: None,
+        }
+    }
+}
+
+impl Default for MatchingConfig {
+    fn default() -> Self {
+        Self {
+            ratio_thresh: RATIO_THRESH,
+            min_good_matches: MIN_GOOD_MATCHES,
+            working_size: inkwell_core::DEFAULT_WORKING_SIZE,
+            accept_threshold: DEFAULT_ACCEPT_THRESHOLD,
+            calibration_file: None,
+        }
+    }
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            schedule: DEFAULT_SCHEDULE.to_string(),
+            on_startup: true,
+            concurrency: 10,
+            requests_per_second: 8,
+            request_timeout_secs: 30,
+            max_attempts: 4,
+            augment_variants: 0,
+        }
+    }
+}
+
+impl Default for ScansConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            max_capture_mb: 1024,
+        }
+    }
+}
+
+impl MatchingConfig {
+    pub fn params(&self) -> MatchParams {
+        MatchParams {
+            ratio_thresh: self.ratio_thresh,
+            min_good_matches: self.min_good_matches,
+            working_size: self.working_size,
+            accept_threshold: self.accept_threshold,
+        }
+    }
+}
+
+impl IngestConfig {
+    pub fn schedule(&self) -> Result<Option<Schedule>, cron::error::Error> {
+        parse_schedule(&self.schedule)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
+
+impl Config {
+    /// Reads the config file (if any), applies environment overrides and validates
+    /// the result. `path` comes from `--config`; without it `INKWELL_CONFIG` or
+    /// [`DEFAULT_CONFIG_PATH`] is used, and a missing default file is not an error.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let explicit = path
+            .map(str::to_string)
+            .or_else(|| std::env::var("INKWELL_CONFIG").ok());
+
+        let mut config = match &explicit {
+            Some(path) => Self::from_file(Path::new(path))?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env(|key| std::env::var(key).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Overrides file values with environment variables. The names predate the
+    /// config file, so existing deployments keep working unchanged.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid {} {:?}: {}", key, value, e))
+        }
+
+        fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+            match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => Ok(true),
+                "0" | "false" | "no" | "off" => Ok(false),
+                _ => Err(format!(
+                    "invalid {} {:?}: expected true or false",
+                    key, value
+                )),
+            }
+        }
+
+        // Empty strings unset optional values
+        let optional = |v: String| Some(v).filter(|v| !v.is_empty());
+
+        if let Some(v) = var("INKWELL_ADDR") {
+            self.server.addr = v;
+        }
+        if let Some(v) = var("INKWELL_METRICS") {
+            self.server.metrics = parse_bool("INKWELL_METRICS", &v)?;
+        }
+        if let Some(v) = var("TRUSTED_PROXIES") {
+            self.server.trusted_proxies = v
+                .split(',')
+                .filter(|ip| !ip.trim().is_empty())
+                .map(|ip| parse("TRUSTED_PROXIES", ip))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(v) = var("DATABASE_URL") {
+            self.database.url = v;
+        }
+        if let Some(v) = var("DATABASE_MAX_CONNECTIONS") {
+            self.database.max_connections = parse("DATABASE_MAX_CONNECTIONS", &v)?;
+        }
+        if let Some(v) = var("CARD_IMAGES_DIR") {
+            self.paths.card_images = v;
+        }
+        if let Some(v) = var("DIST_DIR") {
+            self.paths.dist = v;
+        }
+        if let Some(v) = var("CAPTURED_IMAGES_DIR") {
+            self.paths.captured_images = optional(v);
+        }
+        if let Some(v) = var("MATCH_RATIO_THRESH") {
+            self.matching.ratio_thresh = parse("MATCH_RATIO_THRESH", &v)?;
+        }
+        if let Some(v) = var("MATCH_MIN_GOOD_MATCHES") {
+            self.matching.min_good_matches = parse("MATCH_MIN_GOOD_MATCHES", &v)?;
+        }
+        if let Some(v) = var("MATCH_WORKING_SIZE") {
+            self.matching.working_size = parse("MATCH_WORKING_SIZE", &v)?;
+        }
+        if let Some(v) = var("MATCH_ACCEPT_THRESHOLD") {
+            self.matching.accept_threshold = parse("MATCH_ACCEPT_THRESHOLD", &v)?;
+        }
+        if let Some(v) = var("CALIBRATION_FILE") {
+            self.matching.calibration_file = optional(v);
+        }
+        if let Some(v) = var("INGEST_SCHEDULE") {
+            self.ingest.schedule = v;
+        }
+        if let Some(v) = var("INGEST_ON_STARTUP") {
+            self.ingest.on_startup = parse_bool("INGEST_ON_STARTUP", &v)?;
+        }
+        if let Some(v) = var("INGEST_CONCURRENCY") {
+            self.ingest.concurrency = parse("INGEST_CONCURRENCY", &v)?;
+        }
+        if let Some(v) = var("INGEST_REQUESTS_PER_SECOND") {
+            self.ingest.requests_per_second = parse("INGEST_REQUESTS_PER_SECOND", &v)?;
+        }
+        if let Some(v) = var("INGEST_REQUEST_TIMEOUT_SECS") {
+            self.ingest.request_timeout_secs = parse("INGEST_REQUEST_TIMEOUT_SECS", &v)?;
+        }
+        if let Some(v) = var("INGEST_MAX_ATTEMPTS") {
+            self.ingest.max_attempts = parse("INGEST_MAX_ATTEMPTS", &v)?;
+        }
+        if let Some(v) = var("INGEST_AUGMENT_VARIANTS") {
+            self.ingest.augment_variants = parse("INGEST_AUGMENT_VARIANTS", &v)?;
+        }
+        if let Some(v) = var("SCAN_RETENTION_DAYS") {
+            self.scans.retention_days = parse("SCAN_RETENTION_DAYS", &v)?;
+        }
+        if let Some(v) = var("SCAN_MAX_CAPTURE_MB") {
+            self.scans.max_capture_mb = parse("SCAN_MAX_CAPTURE_MB", &v)?;
+        }
+        if let Some(v) = var("ADMIN_TOKEN") {
+            self.admin.token = optional(v);
+        }
+        if let Some(v) = var("WRITE_TOKEN") {
+            self.admin.write_token = optional(v);
+        }
+        Ok(())
+    }
+
+    /// Checks every value, reporting all problems at once.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = self.server.addr.parse::<SocketAddr>() {
+            problems.push(format!("server.addr {:?}: {}", self.server.addr, e));
+        }
+        if !self.database.url.starts_with("sqlite:") {
+            problems.push(format!(
+                "database.url {:?} must start with \"sqlite:\"",
+                self.database.url
+            ));
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.paths.card_images.is_empty() {
+            problems.push("paths.card_images must not be empty".to_string());
+        }
+        if self.paths.dist.is_empty() {
+            problems.push("paths.dist must not be empty".to_string());
+        }
+        let ratio = self.matching.ratio_thresh;
+        if ratio.is_nan() || ratio <= 0.0 || ratio > 1.0 {
+            problems.push(format!(
+                "matching.ratio_thresh must be within (0, 1], got {}",
+                ratio
+            ));
+        }
+        if self.matching.min_good_matches == 0 {
+            problems.push("matching.min_good_matches must be at least 1".to_string());
+        }
+        if !(64..=4096).contains(&self.matching.working_size) {
+            problems.push(format!(
+                "matching.working_size must be within 64..=4096, got {}",
+                self.matching.working_size
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.matching.accept_threshold) {
+            problems.push(format!(
+                "matching.accept_threshold must be within 0..=1, got {}",
+                self.matching.accept_threshold
+            ));
+        }
+        if let Some(path) = &self.matching.calibration_file {
+            if !Path::new(path).is_file() {
+                problems.push(format!(
+                    "matching.calibration_file {:?} does not exist",
+                    path
+                ));
+            }
+        }
+        if let Err(e) = self.ingest.schedule() {
+            problems.push(format!("ingest.schedule {:?}: {}", self.ingest.schedule, e));
+        }
+        if self.ingest.concurrency == 0 {
+            problems.push("ingest.concurrency must be at least 1".to_string());
+        }
+        if self.ingest.requests_per_second == 0 {
+            problems.push("ingest.requests_per_second must be at least 1".to_string());
+        }
+        if self.ingest.request_timeout_secs == 0 {
+            problems.push("ingest.request_timeout_secs must be at least 1".to_string());
+        }
+        if self.ingest.max_attempts == 0 {
+            problems.push("ingest.max_attempts must be at least 1".to_string());
+        }
+        if self.ingest.augment_variants > MAX_AUGMENT_VARIANTS {
+            problems.push(format!(
+                "ingest.augment_variants must be at most {}, got {}",
+                MAX_AUGMENT_VARIANTS, self.ingest.augment_variants
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "invalid configuration:\n  {}",
+                problems.join("\n  ")
+            ))
+        }
+    }
+
+    /// The effective configuration as TOML, with secrets masked.
+    pub fn to_toml_redacted(&self) -> String {
+        let mut shown = self.clone();
+        for token in [&mut shown.admin.token, &mut shown.admin.write_token] {
+            if token.is_some() {
+                *token = Some("<redacted>".to_string());
+            }
+        }
+        toml::to_string_pretty(&shown).expect("config serializes to TOML")
+    }
+}
+
+/// Parses a cron expression, treating `off`/`none`/empty as "no schedule".
+pub fn parse_schedule(expr: &str) -> Result<Option<Schedule>, cron::error::Error> {
+    let expr = expr.trim();
+    if expr.is_empty() || expr.eq_ignore_ascii_case("off") || expr.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    expr.parse().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_schedule() {
+        assert!(parse_schedule("off").unwrap().is_none());
+        assert!(parse_schedule("  ").unwrap().is_none());
+        assert!(parse_schedule(DEFAULT_SCHEDULE).unwrap().is_some());
+        assert!(parse_schedule("0 30 */6 * * Mon-Fri").unwrap().is_some());
+        assert!(parse_schedule("every day").is_err());
+    }
+
+    #[test]
+    fn test_defaults_are_valid_and_round_trip() {
+        let config = Config::default();
+        config.validate().unwrap();
+
+        let parsed: Config = toml::from_str(&config.to_toml_redacted()).unwrap();
+        assert_eq!(parsed.server.addr, "0.0.0.0:4000");
+        assert_eq!(parsed.matching.min_good_matches, MIN_GOOD_MATCHES);
+    }
+
+    #[test]
+    fn test_file_then_env_overrides() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [server]
+            addr = "127.0.0.1:8080"
+
+            [matching]
+            ratio_thresh = 0.8
+            min_good_matches = 40
+            "#,
+        )
+        .unwrap();
+        // Sections and keys left out keep their defaults
+        assert_eq!(config.database.max_connections, 5);
+
+        let env = HashMap::from([
+            ("MATCH_MIN_GOOD_MATCHES", "30"),
+            ("INGEST_ON_STARTUP", "off"),
+            ("ADMIN_TOKEN", ""),
+            ("WRITE_TOKEN", "s3cret"),
+            ("TRUSTED_PROXIES", "127.0.0.1, ::1"),
+        ]);
+        config
+            .apply_env(|key| env.get(key).map(|v| v.to_string()))
+            .unwrap();
+
+        assert_eq!(config.server.addr, "127.0.0.1:8080");
+        assert_eq!(
+            config.server.trusted_proxies,
+            [
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+        assert_eq!(config.matching.ratio_thresh, 0.8);
+        assert_eq!(config.matching.min_good_matches, 30);
+        assert!(!config.ingest.on_startup);
+        assert!(config.admin.token.is_none());
+        assert_eq!(config.admin.write_token.as_deref(), Some("s3cret"));
+        assert!(!config.to_toml_redacted().contains("s3cret"));
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let mut config = Config::default();
+        config.server.addr = "localhost".to_string();
+        config.matching.ratio_thresh = 1.5;
+        config.ingest.schedule = "whenever".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("server.addr"));
+        assert!(err.contains("matching.ratio_thresh"));
+        assert!(err.contains("ingest.schedule"));
+
+        assert!(toml::from_str::<Config>("[server]\nport = 1").is_err());
+        assert!(config
+            .apply_env(|key| (key == "INGEST_CONCURRENCY").then(|| "many".to_string()))
+            .is_err());
+    }
+}
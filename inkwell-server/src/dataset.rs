@@ -0,0 +1,66 @@
+//! Folders of labeled card images, as used by the `verify` tool and reference imports.
+//!
+//! Labels come from a sidecar `<image>.json` (`{"card_id": "1-12-345"}`, or null for an
+//! image that should not match anything) or else from the file name: `<card_id>.jpg` or
+//! `<card_id>__<anything>.jpg`. Files named `negative*` are expected not to match.
+
+use std::path::{Path, PathBuf};
+
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Expected card id for an evaluation image; `None` means nothing should match.
+pub fn label_for(path: &Path) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let sidecar = path.with_extension("json");
+    if sidecar.exists() {
+        #[derive(serde::Deserialize)]
+        struct Sidecar {
+            card_id: Option<String>,
+        }
+        let sidecar: Sidecar = serde_json::from_str(&std::fs::read_to_string(&sidecar)?)?;
+        return Ok(sidecar.card_id);
+    }
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("file name is not valid UTF-8")?;
+    if stem.starts_with("negative") {
+        return Ok(None);
+    }
+    let card_id = stem.split("__").next().unwrap_or(stem);
+    Ok(Some(card_id.to_string()))
+}
+
+/// Appends every image under `dir`, recursively, to `out`.
+pub fn collect_images(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_images(&path, out)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_from_file_name() {
+        assert_eq!(
+            label_for(Path::new("/nonexistent/1-12-345__sleeved.jpg")).unwrap(),
+            Some("1-12-345".to_string())
+        );
+        assert_eq!(
+            label_for(Path::new("/nonexistent/negative_03.png")).unwrap(),
+            None
+        );
+    }
+}
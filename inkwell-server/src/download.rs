@@ -0,0 +1,171 @@
+use reqwest::{header, Client, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tokio::{sync::Mutex, time::Instant};
+
+/// How failed requests are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before retrying after the given (1-based) failed attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Spaces requests out so that at most one starts per `interval`, across all tasks.
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests_per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// An HTTP client with per-request timeouts, a global rate limit and retries with
+/// exponential backoff for transient failures (connection errors, timeouts, 429 and 5xx).
+pub struct Downloader {
+    client: Client,
+    limiter: RateLimiter,
+    policy: RetryPolicy,
+}
+
+impl Downloader {
+    pub fn new(
+        timeout: Duration,
+        requests_per_second: u32,
+        policy: RetryPolicy,
+    ) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout.min(Duration::from_secs(10)))
+            .build()?;
+        Ok(Self {
+            client,
+            limiter: RateLimiter::new(requests_per_second),
+            policy,
+        })
+    }
+
+    /// Sends the request built by `build`, retrying transient failures. The final
+    /// response is returned as-is, so callers still check its status.
+    pub async fn send<F>(&self, build: F) -> Result<Response, reqwest::Error>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            self.limiter.acquire().await;
+            let result = build(&self.client).send().await;
+            let can_retry = attempt < self.policy.max_attempts;
+
+            let delay = match &result {
+                Ok(resp) if can_retry && is_retryable_status(resp.status()) => {
+                    tracing::debug!(
+                        "{} returned {} (attempt {}/{})",
+                        resp.url(),
+                        resp.status(),
+                        attempt,
+                        self.policy.max_attempts
+                    );
+                    retry_after(resp).unwrap_or_else(|| self.policy.backoff(attempt))
+                }
+                Err(e) if can_retry && is_transient(e) => {
+                    tracing::debug!(
+                        "Request failed (attempt {}/{}): {}",
+                        attempt,
+                        self.policy.max_attempts,
+                        e
+                    );
+                    self.policy.backoff(attempt)
+                }
+                _ => return result,
+            };
+
+            tokio::time::sleep(delay.min(self.policy.max_backoff)).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.is_request()
+}
+
+/// Parses a `Retry-After` header given in seconds.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(5),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(4));
+        assert_eq!(policy.backoff(5), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::NOT_MODIFIED));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_spaces_requests() {
+        let limiter = RateLimiter::new(4);
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,124 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use inkwell_core::ErrorResponse;
+
+/// Everything that can go wrong while identifying an uploaded image.
+/// Not finding a match is not an error: it's a `ScanResult` without a card.
+#[derive(Debug)]
+pub enum IdentifyError {
+    /// The request body was empty.
+    EmptyBody,
+    /// The upload isn't in an image format we can read.
+    UnsupportedFormat(String),
+    /// The upload looked like an image but could not be decoded.
+    Undecodable(String),
+    /// The image decoded but contained no usable features (blank, blurry, too dark...).
+    NoFeatures,
+    /// The card index hasn't been populated yet.
+    IndexUnavailable,
+    /// Feature extraction failed inside OpenCV.
+    FeatureExtraction(String),
+    /// Building or running the matcher failed.
+    Matcher(String),
+    /// The blocking identification task panicked or was cancelled.
+    Internal(String),
+}
+
+impl IdentifyError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            IdentifyError::EmptyBody | IdentifyError::Undecodable(_) => StatusCode::BAD_REQUEST,
+            IdentifyError::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            IdentifyError::NoFeatures => StatusCode::UNPROCESSABLE_ENTITY,
+            IdentifyError::IndexUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            IdentifyError::FeatureExtraction(_)
+            | IdentifyError::Matcher(_)
+            | IdentifyError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            IdentifyError::EmptyBody => "empty_body",
+            IdentifyError::UnsupportedFormat(_) => "unsupported_image_format",
+            IdentifyError::Undecodable(_) => "undecodable_image",
+            IdentifyError::NoFeatures => "no_features",
+            IdentifyError::IndexUnavailable => "index_unavailable",
+            IdentifyError::FeatureExtraction(_) => "feature_extraction_failed",
+            IdentifyError::Matcher(_) => "matcher_failed",
+            IdentifyError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Problems with the upload itself won't go away by resending it; server-side
+    /// failures might.
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            IdentifyError::IndexUnavailable
+                | IdentifyError::Matcher(_)
+                | IdentifyError::Internal(_)
+        )
+    }
+}
+
+impl std::fmt::Display for IdentifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentifyError::EmptyBody => write!(f, "Request body is empty"),
+            IdentifyError::UnsupportedFormat(e) => write!(f, "Unsupported image format: {}", e),
+            IdentifyError::Undecodable(e) => write!(f, "Failed to decode image: {}", e),
+            IdentifyError::NoFeatures => write!(f, "No features found in image"),
+            IdentifyError::IndexUnavailable => write!(f, "Card index is not loaded yet"),
+            IdentifyError::FeatureExtraction(e) => write!(f, "Feature extraction failed: {}", e),
+            IdentifyError::Matcher(e) => write!(f, "Matching failed: {}", e),
+            IdentifyError::Internal(e) => write!(f, "Internal error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IdentifyError {}
+
+impl IntoResponse for IdentifyError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("Identification failed: {}", self);
+        } else {
+            tracing::warn!("Rejected identification request: {}", self);
+        }
+
+        let body = ErrorResponse {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            retryable: self.retryable(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_errors_are_not_retryable() {
+        for err in [
+            IdentifyError::EmptyBody,
+            IdentifyError::UnsupportedFormat("x".into()),
+            IdentifyError::Undecodable("x".into()),
+            IdentifyError::NoFeatures,
+        ] {
+            assert!(err.status().is_client_error(), "{}", err.code());
+            assert!(!err.retryable(), "{}", err.code());
+        }
+        assert_eq!(
+            IdentifyError::IndexUnavailable.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert!(IdentifyError::Internal("panic".into()).retryable());
+    }
+}
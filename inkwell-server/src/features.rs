@@ -0,0 +1,312 @@
+//! The descriptors the matcher runs on. With the default `opencv` feature they are
+//! AKAZE features matched by OpenCV's brute-force matcher; without it, the pure-Rust
+//! features of [`inkwell_core::orb`] (the ones the compact index uses) matched in plain
+//! Rust, so the server builds and runs without the OpenCV system libraries.
+//!
+//! Each kind is stored in its own column ([`DESCRIPTOR_COLUMN`]) of `cards` and
+//! `card_references`, so a database can be shared by both builds: each one matches on
+//! the views it has descriptors for.
+
+#[cfg(feature = "opencv")]
+pub use self::akaze::*;
+#[cfg(not(feature = "opencv"))]
+pub use self::orb::*;
+
+/// Failure to extract, decode or match descriptors.
+#[derive(Debug)]
+pub struct FeatureError(String);
+
+impl std::fmt::Display for FeatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for FeatureError {}
+
+#[cfg(feature = "opencv")]
+mod akaze {
+    use super::FeatureError;
+    use image::DynamicImage;
+    use opencv::{
+        core::{DMatch, Mat, Vector, NORM_HAMMING},
+        features2d::BFMatcher,
+        prelude::*,
+    };
+
+    /// Name of the features, for reports.
+    pub const NAME: &str = "AKAZE";
+    /// Column of `cards` and `card_references` holding this build's descriptors.
+    pub const DESCRIPTOR_COLUMN: &str = "akaze_data";
+    /// Bytes per descriptor.
+    pub const DESCRIPTOR_SIZE: usize = inkwell_core::AKAZE_DESC_SIZE as usize;
+    /// Votes the best card needs by default. AKAZE typically extracts 100-1000 features
+    /// from a card photo.
+    pub const MIN_GOOD_MATCHES: usize = 50;
+
+    impl From<opencv::Error> for FeatureError {
+        fn from(e: opencv::Error) -> Self {
+            FeatureError(e.to_string())
+        }
+    }
+
+    /// Descriptors of a reference view, scaled to `working_size` like queries are.
+    pub fn extract_reference(
+        img: &DynamicImage,
+        working_size: u32,
+    ) -> Result<Vec<u8>, FeatureError> {
+        let (_, descriptors) = inkwell_core::compute_akaze_features_at(img, working_size)?;
+        Ok(descriptors)
+    }
+
+    /// Descriptors of a query image.
+    pub fn extract_query(img: &DynamicImage, working_size: u32) -> Result<Vec<u8>, FeatureError> {
+        extract_reference(img, working_size)
+    }
+
+    /// One view's descriptors, decoded for matching.
+    pub struct Descriptors(Mat);
+
+    impl Descriptors {
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, FeatureError> {
+            Ok(Self(inkwell_core::akaze_bytes_to_mat(bytes)?))
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.rows() as usize
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
+    /// Descriptors of several views. Reading a Mat out of a `Vector` only copies its
+    /// header, so views can be laid out again without copying the descriptors.
+    pub struct Views(Vector<Mat>);
+
+    impl Default for Views {
+        fn default() -> Self {
+            Self(Vector::new())
+        }
+    }
+
+    impl Views {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn push(&mut self, view: Descriptors) {
+            self.0.push(view.0);
+        }
+
+        /// Appends every view of `other`.
+        pub fn extend_from(&mut self, other: &Views) {
+            for view in other.0.iter() {
+                self.0.push(view);
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        /// Number of descriptors of view `i`.
+        pub fn rows(&self, i: usize) -> usize {
+            self.0.get(i).map_or(0, |view| view.rows() as usize)
+        }
+
+        /// The `k` nearest descriptors among all views for each query descriptor, as
+        /// `(view, distance)` in ascending distance.
+        pub fn knn_match(
+            &self,
+            query: &Descriptors,
+            k: usize,
+        ) -> Result<Vec<Vec<(usize, f32)>>, FeatureError> {
+            // BFMatcher::add only stores Mat headers, so building one per query is cheap
+            // and keeps concurrent scans from sharing matcher state.
+            let mut matcher = BFMatcher::create(NORM_HAMMING, false)?;
+            matcher.add(&self.0)?;
+            matcher.train()?;
+
+            let mut matches = Vector::<Vector<DMatch>>::new();
+            matcher.knn_match(&query.0, &mut matches, k as i32, &Mat::default(), false)?;
+            Ok(matches
+                .iter()
+                .map(|m| m.iter().map(|m| (m.img_idx as usize, m.distance)).collect())
+                .collect())
+        }
+    }
+
+    impl FromIterator<Descriptors> for Views {
+        fn from_iter<I: IntoIterator<Item = Descriptors>>(iter: I) -> Self {
+            Self(iter.into_iter().map(|view| view.0).collect())
+        }
+    }
+}
+
+#[cfg(not(feature = "opencv"))]
+mod orb {
+    use super::FeatureError;
+    use image::DynamicImage;
+    use inkwell_core::orb::{self, ORB_DESC_SIZE};
+    use std::sync::Arc;
+
+    /// Name of the features, for reports.
+    pub const NAME: &str = "ORB";
+    /// Column of `cards` and `card_references` holding this build's descriptors.
+    pub const DESCRIPTOR_COLUMN: &str = "orb_data";
+    /// Bytes per descriptor.
+    pub const DESCRIPTOR_SIZE: usize = ORB_DESC_SIZE;
+    /// Votes the best card needs by default. Queries have at most
+    /// [`orb::QUERY_FEATURES`] features, matched against at most
+    /// [`orb::REFERENCE_FEATURES`] per view.
+    pub const MIN_GOOD_MATCHES: usize = 20;
+
+    type Packed = [u64; ORB_DESC_SIZE / 8];
+
+    /// Descriptors of a reference view, the same as the compact index holds.
+    pub fn extract_reference(
+        img: &DynamicImage,
+        working_size: u32,
+    ) -> Result<Vec<u8>, FeatureError> {
+        Ok(orb::extract(img, working_size, orb::REFERENCE_FEATURES).1)
+    }
+
+    /// Descriptors of a query image.
+    pub fn extract_query(img: &DynamicImage, working_size: u32) -> Result<Vec<u8>, FeatureError> {
+        Ok(orb::extract(img, working_size, orb::QUERY_FEATURES).1)
+    }
+
+    /// One view's descriptors, decoded for matching. Cheap to clone.
+    #[derive(Clone)]
+    pub struct Descriptors(Arc<[Packed]>);
+
+    impl Descriptors {
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, FeatureError> {
+            if !bytes.len().is_multiple_of(ORB_DESC_SIZE) {
+                return Err(FeatureError(format!(
+                    "{} bytes of descriptors aren't a multiple of {}",
+                    bytes.len(),
+                    ORB_DESC_SIZE
+                )));
+            }
+            Ok(Self(bytes.chunks_exact(ORB_DESC_SIZE).map(pack).collect()))
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
+    fn pack(bytes: &[u8]) -> Packed {
+        let mut words = [0u64; ORB_DESC_SIZE / 8];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        words
+    }
+
+    fn hamming(a: &Packed, b: &Packed) -> u32 {
+        a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum()
+    }
+
+    /// Descriptors of several views.
+    #[derive(Default)]
+    pub struct Views(Vec<Descriptors>);
+
+    impl Views {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn push(&mut self, view: Descriptors) {
+            self.0.push(view);
+        }
+
+        /// Appends every view of `other`.
+        pub fn extend_from(&mut self, other: &Views) {
+            self.0.extend(other.0.iter().cloned());
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        /// Number of descriptors of view `i`.
+        pub fn rows(&self, i: usize) -> usize {
+            self.0.get(i).map_or(0, Descriptors::len)
+        }
+
+        /// The `k` nearest descriptors among all views for each query descriptor, as
+        /// `(view, distance)` in ascending distance.
+        pub fn knn_match(
+            &self,
+            query: &Descriptors,
+            k: usize,
+        ) -> Result<Vec<Vec<(usize, f32)>>, FeatureError> {
+            Ok(query
+                .0
+                .iter()
+                .map(|q| {
+                    let mut nearest: Vec<(usize, u32)> = Vec::with_capacity(k + 1);
+                    for (view, descriptors) in self.0.iter().enumerate() {
+                        for d in descriptors.0.iter() {
+                            let distance = hamming(q, d);
+                            if nearest.len() == k
+                                && nearest.last().is_none_or(|&(_, far)| distance >= far)
+                            {
+                                continue;
+                            }
+                            let at = nearest.partition_point(|&(_, near)| near <= distance);
+                            nearest.insert(at, (view, distance));
+                            nearest.truncate(k);
+                        }
+                    }
+                    nearest
+                        .into_iter()
+                        .map(|(view, distance)| (view, distance as f32))
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    impl FromIterator<Descriptors> for Views {
+        fn from_iter<I: IntoIterator<Item = Descriptors>>(iter: I) -> Self {
+            Self(iter.into_iter().collect())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn view(fill: &[u8]) -> Descriptors {
+            let bytes: Vec<u8> = fill.iter().flat_map(|&b| [b; ORB_DESC_SIZE]).collect();
+            Descriptors::from_bytes(&bytes).unwrap()
+        }
+
+        #[test]
+        fn test_knn_match_orders_by_distance() {
+            let views: Views = [view(&[0x00, 0xff]), view(&[0x01])].into_iter().collect();
+            let matches = views.knn_match(&view(&[0x00]), 2).unwrap();
+            // 0x01 differs in one bit per byte
+            assert_eq!(matches, vec![vec![(0, 0.0), (1, 32.0)]]);
+            assert!(views.knn_match(&view(&[0x00]), 0).unwrap()[0].is_empty());
+            assert!(Descriptors::from_bytes(&[0; 5]).is_err());
+        }
+    }
+}
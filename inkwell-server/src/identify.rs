@@ -0,0 +1,461 @@
+use crate::{
+    calibration::{Calibration, DEFAULT_ACCEPT_THRESHOLD},
+    error::IdentifyError,
+    features::{self, Descriptors, FeatureError},
+    index::{self, GlobalIndex},
+};
+use image::{io::Reader as ImageReader, DynamicImage};
+use inkwell_core::{Card, ScanResult};
+use sqlx::{Pool, Sqlite};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+/// Default minimum votes for the best card; also where the uncalibrated confidence
+/// reaches the default acceptance threshold. Depends on the features, see
+/// [`features::MIN_GOOD_MATCHES`].
+pub const MIN_GOOD_MATCHES: usize = features::MIN_GOOD_MATCHES;
+
+/// Lowe's ratio test: a descriptor match only counts if it is clearly closer than the
+/// runner-up.
+pub const RATIO_THRESH: f32 = 0.75;
+
+/// Neighbours fetched per query descriptor, so the nearest one from a *different* card
+/// is usually among them even when a card has several reference views.
+const KNN_NEIGHBOURS: usize = 8;
+
+/// Tuning values for [`Identifier`], normally taken from the `[matching]` config section.
+#[derive(Clone, Copy, Debug)]
+pub struct MatchParams {
+    pub ratio_thresh: f32,
+    /// Votes the best card needs before it can be returned at all
+    pub min_good_matches: usize,
+    /// Longest side query images are scaled to; must match the reference features
+    pub working_size: u32,
+    /// Minimum confidence for the best card to be returned
+    pub accept_threshold: f64,
+}
+
+impl Default for MatchParams {
+    fn default() -> Self {
+        Self {
+            ratio_thresh: RATIO_THRESH,
+            min_good_matches: MIN_GOOD_MATCHES,
+            working_size: inkwell_core::DEFAULT_WORKING_SIZE,
+            accept_threshold: DEFAULT_ACCEPT_THRESHOLD,
+        }
+    }
+}
+
+/// A reference card and the number of query descriptors that voted for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Position of the card in [`Identifier::cards`]
+    pub card_idx: usize,
+    pub votes: usize,
+}
+
+/// Every card that received votes for a query image, best first.
+#[derive(Debug)]
+pub struct Ranking {
+    pub candidates: Vec<Candidate>,
+    /// Descriptors extracted from the query image
+    pub query_features: usize,
+    pub extract_time: Duration,
+    pub match_time: Duration,
+}
+
+/// The matching pipeline: features from the query image (AKAZE, or ORB without OpenCV,
+/// see [`features`]), brute-force Hamming kNN against every reference view, ratio
+/// test, and one vote for the view's card per surviving match.
+pub struct Identifier {
+    index: GlobalIndex,
+    calibration: Option<Calibration>,
+    params: MatchParams,
+}
+
+impl Identifier {
+    pub fn new(index: GlobalIndex) -> Self {
+        Self {
+            index,
+            calibration: None,
+            params: MatchParams::default(),
+        }
+    }
+
+    /// Scores matches with a fitted model instead of the
+    /// [`uncalibrated_confidence`](Self::uncalibrated_confidence) heuristic.
+    pub fn with_calibration(mut self, calibration: Option<Calibration>) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    pub fn with_params(mut self, params: MatchParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn params(&self) -> &MatchParams {
+        &self.params
+    }
+
+    pub fn is_calibrated(&self) -> bool {
+        self.calibration.is_some()
+    }
+
+    /// Builds the index from every card in the database that has features.
+    pub async fn load(pool: &Pool<Sqlite>) -> Result<Self, sqlx::Error> {
+        Ok(Self::new(index::load_index(pool).await?))
+    }
+
+    /// Builds an identifier over the given cards; those without usable descriptors are skipped.
+    pub fn from_cards(cards: Vec<Card>) -> Self {
+        Self::new(GlobalIndex::build(cards, HashMap::new()).0)
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.index.cards
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.cards.is_empty()
+    }
+
+    /// Number of reference views across all cards, official renders included.
+    pub fn view_count(&self) -> usize {
+        self.index.view_count()
+    }
+
+    /// See [`GlobalIndex::apply_changes`].
+    pub fn apply_changes(
+        &mut self,
+        ids: &[String],
+        cards: Vec<Card>,
+        references: HashMap<String, Vec<Vec<u8>>>,
+    ) {
+        self.index.apply_changes(ids, cards, references);
+    }
+
+    /// Swaps in a freshly loaded index, keeping calibration and parameters.
+    pub fn replace_index(&mut self, index: GlobalIndex) {
+        self.index = index;
+    }
+
+    /// See [`GlobalIndex::set_references`].
+    pub fn set_references(
+        &mut self,
+        card_id: &str,
+        descriptors: &[Vec<u8>],
+    ) -> Result<bool, FeatureError> {
+        self.index.set_references(card_id, descriptors)
+    }
+
+    /// Extracts features from `img` and ranks the indexed cards by ratio-test votes.
+    /// An image without features yields an empty ranking with `query_features == 0`.
+    pub fn rank(&self, img: &DynamicImage) -> Result<Ranking, IdentifyError> {
+        if self.is_empty() {
+            return Err(IdentifyError::IndexUnavailable);
+        }
+
+        let start_extract = Instant::now();
+        let query_desc_bytes = features::extract_query(img, self.params.working_size)
+            .map_err(|e| IdentifyError::FeatureExtraction(e.to_string()))?;
+        let extract_time = start_extract.elapsed();
+        metrics::histogram!("inkwell_feature_extraction_duration_seconds")
+            .record(extract_time.as_secs_f64());
+
+        let start_match = Instant::now();
+        if query_desc_bytes.is_empty() {
+            return Ok(Ranking {
+                candidates: Vec::new(),
+                query_features: 0,
+                extract_time,
+                match_time: Duration::ZERO,
+            });
+        }
+
+        let query = Descriptors::from_bytes(&query_desc_bytes)
+            .map_err(|e| IdentifyError::FeatureExtraction(e.to_string()))?;
+        let matches = self
+            .index
+            .train_vec
+            .knn_match(&query, KNN_NEIGHBOURS)
+            .map_err(|e| IdentifyError::Matcher(e.to_string()))?;
+
+        let mut votes = HashMap::new();
+        for m in matches {
+            let neighbours: Vec<(usize, f32)> = m
+                .into_iter()
+                .map(|(view, distance)| (self.index.card_of_view(view), distance))
+                .collect();
+            if let Some(card_idx) = ratio_vote(&neighbours, self.params.ratio_thresh) {
+                *votes.entry(card_idx).or_insert(0) += 1;
+            }
+        }
+
+        let mut candidates: Vec<Candidate> = votes
+            .into_iter()
+            .map(|(card_idx, votes)| Candidate { card_idx, votes })
+            .collect();
+        candidates.sort_by(|a, b| b.votes.cmp(&a.votes).then(a.card_idx.cmp(&b.card_idx)));
+
+        let match_time = start_match.elapsed();
+        metrics::histogram!("inkwell_matching_duration_seconds").record(match_time.as_secs_f64());
+
+        Ok(Ranking {
+            candidates,
+            query_features: query.len(),
+            extract_time,
+            match_time,
+        })
+    }
+
+    /// Identifies the card in `img`. Not finding one is a `ScanResult` without a card.
+    pub fn identify(&self, img: &DynamicImage) -> Result<ScanResult, IdentifyError> {
+        self.identify_ranked(img).map(|(result, _)| result)
+    }
+
+    /// Like [`Identifier::identify`], also returning the ranking the result is based on.
+    pub fn identify_ranked(
+        &self,
+        img: &DynamicImage,
+    ) -> Result<(ScanResult, Ranking), IdentifyError> {
+        let ranking = self.rank(img)?;
+        if ranking.query_features == 0 {
+            return Err(IdentifyError::NoFeatures);
+        }
+        Ok((self.scan_result(&ranking), ranking))
+    }
+
+    /// Ids and votes of the best `k` candidates.
+    pub fn top_candidates(&self, ranking: &Ranking, k: usize) -> Vec<(String, usize)> {
+        ranking
+            .candidates
+            .iter()
+            .take(k)
+            .map(|c| (self.index.cards[c.card_idx].id.clone(), c.votes))
+            .collect()
+    }
+
+    /// Estimated probability that the top candidate is the right card.
+    pub fn confidence(&self, ranking: &Ranking) -> f64 {
+        let Some(best) = ranking.candidates.first() else {
+            return 0.0;
+        };
+        match &self.calibration {
+            Some(calibration) => {
+                let second = ranking.candidates.get(1).map_or(0, |c| c.votes);
+                calibration.predict(best.votes, second)
+            }
+            None => self.uncalibrated_confidence(best.votes),
+        }
+    }
+
+    /// Confidence without a fitted model: scales linearly up to twice the configured
+    /// minimum votes, so a card with just enough votes sits at 0.5.
+    pub fn uncalibrated_confidence(&self, votes: usize) -> f64 {
+        let full = 2 * self.params.min_good_matches.max(1);
+        (votes as f64 / full as f64).min(1.0)
+    }
+
+    /// Whether the top candidate of `ranking` would be returned as a match.
+    pub fn accepts(&self, ranking: &Ranking) -> bool {
+        ranking.candidates.first().is_some_and(|best| {
+            best.votes >= self.params.min_good_matches
+                && self.confidence(ranking) >= self.params.accept_threshold
+        })
+    }
+
+    /// Turns a ranking into what the API returns: the best card if it is confident enough.
+    pub fn scan_result(&self, ranking: &Ranking) -> ScanResult {
+        let no_match = ScanResult {
+            card: None,
+            confidence: 0.0,
+            global_total_scans: 0,
+            scan_id: None,
+        };
+
+        let Some(best) = ranking.candidates.first() else {
+            tracing::info!("No match found.");
+            return no_match;
+        };
+        let card = &self.index.cards[best.card_idx];
+        let confidence = self.confidence(ranking);
+
+        if self.accepts(ranking) {
+            tracing::info!(
+                "Match found: {} ({} good matches, confidence {:.2})",
+                card.name,
+                best.votes,
+                confidence
+            );
+            ScanResult {
+                // Descriptors are tens of KB the client has no use for
+                card: Some(card.without_descriptors()),
+                confidence,
+                global_total_scans: 0,
+                scan_id: None,
+            }
+        } else {
+            tracing::info!(
+                "Best match {} had only {} good matches (confidence {:.2}). Below threshold.",
+                card.name,
+                best.votes,
+                confidence
+            );
+            no_match
+        }
+    }
+}
+
+/// File extension for the image format detected from `bytes`, if it's one we know.
+pub fn sniff_extension(bytes: &[u8]) -> Option<&'static str> {
+    use image::ImageFormat;
+    Some(match image::guess_format(bytes).ok()? {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::WebP => "webp",
+        ImageFormat::Gif => "gif",
+        ImageFormat::Bmp => "bmp",
+        ImageFormat::Tiff => "tiff",
+        _ => "img",
+    })
+}
+
+/// Decodes an uploaded image, sniffing the format from its contents.
+pub fn decode_image(bytes: &[u8]) -> Result<DynamicImage, IdentifyError> {
+    let img_reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| IdentifyError::UnsupportedFormat(e.to_string()))?;
+    if img_reader.format().is_none() {
+        return Err(IdentifyError::UnsupportedFormat(
+            "unrecognized image data".to_string(),
+        ));
+    }
+    img_reader
+        .decode()
+        .map_err(|e| IdentifyError::Undecodable(e.to_string()))
+}
+
+/// The card a query descriptor votes for, given its nearest neighbours as
+/// `(card, distance)` in ascending distance: the nearest must pass the ratio test
+/// against the nearest descriptor of another card. Several views of the same card
+/// being close isn't ambiguous, but doesn't earn a free vote either. When every
+/// neighbour is of the same card, the farthest stands in for the other card, which is
+/// at least that far.
+fn ratio_vote(neighbours: &[(usize, f32)], ratio: f32) -> Option<usize> {
+    let (&(card, best), rest) = neighbours.split_first()?;
+    let other = rest
+        .iter()
+        .find(|(other_card, _)| *other_card != card)
+        .or(rest.last())?
+        .1;
+    (best < ratio * other).then_some(card)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma};
+
+    /// Deterministic blocky noise, so there are plenty of distinct features.
+    fn noise_image(seed: u32) -> DynamicImage {
+        let buf = ImageBuffer::from_fn(360, 500, |x, y| {
+            let mut h = (x / 6).wrapping_mul(73856093) ^ (y / 6).wrapping_mul(19349663) ^ seed;
+            h ^= h >> 13;
+            h = h.wrapping_mul(0x5bd1e995);
+            h ^= h >> 15;
+            Luma([h as u8])
+        });
+        DynamicImage::ImageLuma8(buf)
+    }
+
+    fn card_for(id: &str, img: &DynamicImage) -> Card {
+        let akaze_data =
+            features::extract_reference(img, inkwell_core::DEFAULT_WORKING_SIZE).unwrap();
+        Card {
+            id: id.to_string(),
+            name: id.to_string(),
+            subtitle: String::new(),
+            phash: String::new(),
+            akaze_data,
+            image_url: String::new(),
+            rarity: "Common".to_string(),
+            promo_grouping: None,
+            set_code: "1".to_string(),
+            card_number: 1,
+        }
+    }
+
+    #[test]
+    fn test_identifies_reference_image() {
+        let a = noise_image(1);
+        let b = noise_image(2);
+        let mut empty = card_for("empty", &b);
+        empty.akaze_data.clear();
+        let identifier = Identifier::from_cards(vec![card_for("a", &a), card_for("b", &b), empty]);
+        assert_eq!(identifier.len(), 2);
+
+        let result = identifier.identify(&a).unwrap();
+        assert_eq!(result.card.map(|c| c.id).as_deref(), Some("a"));
+        assert!(result.confidence > 0.5);
+    }
+
+    #[test]
+    fn test_ratio_vote_compares_against_another_card() {
+        // Close views of the same card don't count as the runner-up
+        assert_eq!(
+            ratio_vote(&[(0, 10.0), (0, 11.0), (1, 40.0)], 0.75),
+            Some(0)
+        );
+        // ...but a close view of another card does, however many views card 0 has
+        assert_eq!(
+            ratio_vote(&[(0, 10.0), (0, 10.5), (0, 11.0), (1, 12.0)], 0.75),
+            None
+        );
+        // Without another card among the neighbours, the farthest one is the bound
+        assert_eq!(ratio_vote(&[(0, 10.0), (0, 11.0)], 0.75), None);
+        assert_eq!(ratio_vote(&[(0, 10.0), (0, 30.0)], 0.75), Some(0));
+        assert_eq!(ratio_vote(&[(0, 10.0)], 0.75), None);
+        assert_eq!(ratio_vote(&[], 0.75), None);
+    }
+
+    #[test]
+    fn test_lower_min_good_matches_accepts_fewer_votes() {
+        let ranking = Ranking {
+            candidates: vec![Candidate {
+                card_idx: 0,
+                votes: MIN_GOOD_MATCHES - 1,
+            }],
+            query_features: 100,
+            extract_time: Duration::ZERO,
+            match_time: Duration::ZERO,
+        };
+        assert!(!Identifier::from_cards(Vec::new()).accepts(&ranking));
+
+        let mut config = crate::config::Config::default();
+        config.matching.min_good_matches = MIN_GOOD_MATCHES / 2;
+        let identifier = Identifier::from_cards(Vec::new()).with_params(config.matching.params());
+        assert!(identifier.accepts(&ranking));
+        assert_eq!(identifier.uncalibrated_confidence(MIN_GOOD_MATCHES), 1.0);
+    }
+
+    #[test]
+    fn test_identify_errors() {
+        let blank = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(200, 280, Luma([128])));
+        assert!(matches!(
+            Identifier::from_cards(Vec::new()).identify(&blank),
+            Err(IdentifyError::IndexUnavailable)
+        ));
+
+        let identifier = Identifier::from_cards(vec![card_for("a", &noise_image(1))]);
+        assert!(matches!(
+            identifier.identify(&blank),
+            Err(IdentifyError::NoFeatures)
+        ));
+    }
+}
@@ -0,0 +1,801 @@
+use crate::features::{self, Descriptors, FeatureError, Views};
+use inkwell_core::compact::{CompactIndex, DEFAULT_ACCEPT_VOTES};
+use inkwell_core::Card;
+use serde::Serialize;
+use sqlx::{
+    query::Query,
+    sqlite::{SqliteArguments, SqliteRow},
+    Pool, Row, Sqlite,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The matcher's descriptors (see [`features`]) are read into `Card::akaze_data`.
+#[cfg(feature = "opencv")]
+const CARD_COLUMNS: &str =
+    "id, name, subtitle, phash, image_url, akaze_data, rarity, promo_grouping, set_code, card_number";
+#[cfg(not(feature = "opencv"))]
+const CARD_COLUMNS: &str =
+    "id, name, subtitle, phash, image_url, orb_data AS akaze_data, rarity, promo_grouping, set_code, card_number";
+/// Same as [`CARD_COLUMNS`] without loading the descriptors.
+const CARD_METADATA_COLUMNS: &str =
+    "id, name, subtitle, phash, image_url, NULL AS akaze_data, rarity, promo_grouping, set_code, card_number";
+
+/// In-memory descriptor index. Each card has one or more reference views: the
+/// official render (`Card::akaze_data`) and any extra views from `card_references`.
+/// `train_vec` holds every view's descriptors, and `view_cards[img_idx]` maps the
+/// `img_idx` reported by the matcher back into `cards`.
+pub struct GlobalIndex {
+    pub train_vec: Views,
+    pub cards: Vec<Card>,
+    view_cards: Vec<usize>,
+    positions: HashMap<String, usize>,
+    /// Descriptors of `cards[i]`'s official render; empty if it has none
+    renders: Vec<Views>,
+    /// Extra views by card id
+    references: HashMap<String, Views>,
+}
+
+impl GlobalIndex {
+    pub(crate) fn new() -> Self {
+        Self {
+            train_vec: Views::new(),
+            cards: Vec::new(),
+            view_cards: Vec::new(),
+            positions: HashMap::new(),
+            renders: Vec::new(),
+            references: HashMap::new(),
+        }
+    }
+
+    /// Builds an index over `cards` and their extra views (descriptor blobs by card id).
+    /// Also returns how many cards were left out for lack of usable descriptors.
+    pub fn build(cards: Vec<Card>, references: HashMap<String, Vec<Vec<u8>>>) -> (Self, usize) {
+        let mut index = Self::new();
+        for (card_id, views) in references {
+            if let Err(e) = index.store_references(&card_id, &views) {
+                tracing::warn!("Skipping unusable reference views of {}: {}", card_id, e);
+            }
+        }
+
+        let mut skipped = 0;
+        for card in cards {
+            let id = card.id.clone();
+            match index.insert(card) {
+                Ok(true) => {}
+                Ok(false) => skipped += 1,
+                Err(e) => {
+                    tracing::warn!("Skipping card {} with unusable descriptors: {}", id, e);
+                    skipped += 1;
+                }
+            }
+        }
+        index.rebuild();
+        (index, skipped)
+    }
+
+    /// Position in `cards` of the card whose view is `train_vec[img_idx]`.
+    pub fn card_of_view(&self, img_idx: usize) -> usize {
+        self.view_cards[img_idx]
+    }
+
+    /// Number of reference views across all cards.
+    pub fn view_count(&self) -> usize {
+        self.view_cards.len()
+    }
+
+    /// Adds a card, or replaces the render descriptors and metadata of an already
+    /// indexed one. Cards without any view can't be matched, so they're left out (and
+    /// dropped if they were indexed before). Returns whether the card is now in the index.
+    pub fn upsert(&mut self, card: Card) -> Result<bool, FeatureError> {
+        let indexed = self.insert(card)?;
+        self.rebuild();
+        Ok(indexed)
+    }
+
+    fn insert(&mut self, card: Card) -> Result<bool, FeatureError> {
+        let mut render = Views::new();
+        if !card.akaze_data.is_empty() {
+            render.push(Descriptors::from_bytes(&card.akaze_data)?);
+        }
+        if render.is_empty() && !self.references.contains_key(&card.id) {
+            self.detach(&card.id);
+            return Ok(false);
+        }
+
+        match self.positions.get(&card.id) {
+            Some(&i) => {
+                self.renders[i] = render;
+                self.cards[i] = card;
+            }
+            None => {
+                self.positions.insert(card.id.clone(), self.cards.len());
+                self.renders.push(render);
+                self.cards.push(card);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Removes a card and its extra views, moving the last card into its slot.
+    /// Returns whether it was indexed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        self.references.remove(id);
+        let removed = self.detach(id);
+        if removed {
+            self.rebuild();
+        }
+        removed
+    }
+
+    fn detach(&mut self, id: &str) -> bool {
+        let Some(i) = self.positions.remove(id) else {
+            return false;
+        };
+        self.cards.swap_remove(i);
+        self.renders.swap_remove(i);
+        if let Some(moved) = self.cards.get(i) {
+            self.positions.insert(moved.id.clone(), i);
+        }
+        true
+    }
+
+    /// Replaces the extra views of a card. They are kept even while the card itself
+    /// isn't indexed, and count once it is. Returns whether the card is now in the index.
+    pub fn set_references(
+        &mut self,
+        card_id: &str,
+        descriptors: &[Vec<u8>],
+    ) -> Result<bool, FeatureError> {
+        self.store_references(card_id, descriptors)?;
+        if let Some(&i) = self.positions.get(card_id) {
+            if self.renders[i].is_empty() && !self.references.contains_key(card_id) {
+                self.detach(card_id);
+            }
+        }
+        self.rebuild();
+        Ok(self.positions.contains_key(card_id))
+    }
+
+    fn store_references(
+        &mut self,
+        card_id: &str,
+        descriptors: &[Vec<u8>],
+    ) -> Result<(), FeatureError> {
+        let views = descriptors
+            .iter()
+            .filter(|data| !data.is_empty())
+            .map(|data| Descriptors::from_bytes(data))
+            .collect::<Result<Views, _>>()?;
+        if views.is_empty() {
+            self.references.remove(card_id);
+        } else {
+            self.references.insert(card_id.to_string(), views);
+        }
+        Ok(())
+    }
+
+    /// Applies a batch of changes: the extra views of `ids` are replaced by those in
+    /// `references`, `cards` are upserted, and any of `ids` without a usable entry in
+    /// `cards` is removed.
+    pub fn apply_changes(
+        &mut self,
+        ids: &[String],
+        cards: Vec<Card>,
+        mut references: HashMap<String, Vec<Vec<u8>>>,
+    ) {
+        for id in ids {
+            let views = references.remove(id).unwrap_or_default();
+            if let Err(e) = self.store_references(id, &views) {
+                tracing::warn!("Dropping unusable reference views of {}: {}", id, e);
+                self.references.remove(id);
+            }
+        }
+
+        let mut kept = HashSet::new();
+        for card in cards {
+            let id = card.id.clone();
+            match self.insert(card) {
+                Ok(true) => {
+                    kept.insert(id);
+                }
+                Ok(false) => {}
+                Err(e) => tracing::warn!("Dropping card {} from index: {}", id, e),
+            }
+        }
+
+        let mut removed = 0;
+        for id in ids.iter().filter(|id| !kept.contains(*id)) {
+            self.references.remove(id);
+            if self.detach(id) {
+                removed += 1;
+            }
+        }
+        self.rebuild();
+
+        tracing::info!(
+            "Index updated incrementally: {} upserted, {} removed, {} total.",
+            kept.len(),
+            removed,
+            self.cards.len()
+        );
+    }
+
+    /// Lays out every card's views, render first, in `train_vec` and `view_cards`.
+    /// Views are shared, not copied (see [`Views::extend_from`]).
+    fn rebuild(&mut self) {
+        let mut train_vec = Views::new();
+        let mut view_cards = Vec::with_capacity(self.cards.len());
+        for (i, card) in self.cards.iter().enumerate() {
+            train_vec.extend_from(&self.renders[i]);
+            if let Some(references) = self.references.get(&card.id) {
+                train_vec.extend_from(references);
+            }
+            view_cards.resize(train_vec.len(), i);
+        }
+        self.train_vec = train_vec;
+        self.view_cards = view_cards;
+    }
+}
+
+fn card_from_row(row: &SqliteRow) -> Card {
+    let akaze_data: Option<Vec<u8>> = row.get("akaze_data");
+    Card {
+        id: row.get("id"),
+        name: row.get("name"),
+        subtitle: row.get("subtitle"),
+        phash: row.get("phash"),
+        akaze_data: akaze_data.unwrap_or_default(),
+        image_url: row.get("image_url"),
+        rarity: row.get("rarity"),
+        promo_grouping: row.get("promo_grouping"),
+        set_code: row.get("set_code"),
+        card_number: row.get("card_number"),
+    }
+}
+
+/// Looks a card up by id, without its descriptors.
+pub async fn fetch_card(pool: &Pool<Sqlite>, id: &str) -> Result<Option<Card>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM cards WHERE id = ?",
+        CARD_METADATA_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(card_from_row))
+}
+
+/// Looks a card up by set code and collector number, without its descriptors.
+pub async fn fetch_card_by_number(
+    pool: &Pool<Sqlite>,
+    set_code: &str,
+    card_number: u32,
+) -> Result<Option<Card>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM cards WHERE set_code = ? AND card_number = ? ORDER BY id LIMIT 1",
+        CARD_METADATA_COLUMNS
+    ))
+    .bind(set_code)
+    .bind(card_number)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(card_from_row))
+}
+
+/// Looks a card up by the set column of an exported CSV: a promo grouping such as "P3",
+/// or a set number (padded in Dreamborn exports) of a non-promo card.
+pub async fn fetch_card_by_group(
+    pool: &Pool<Sqlite>,
+    group: &str,
+    card_number: u32,
+) -> Result<Option<Card>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM cards
+         WHERE card_number = ? AND (promo_grouping = ? OR (promo_grouping IS NULL AND set_code = ?))
+         ORDER BY promo_grouping IS NULL, id LIMIT 1",
+        CARD_METADATA_COLUMNS
+    ))
+    .bind(card_number)
+    .bind(group)
+    .bind(inkwell_core::import::set_code_for_group(group))
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(card_from_row))
+}
+
+/// Catalog filters; every one that is set must match.
+#[derive(Clone, Debug, Default)]
+pub struct CardFilter {
+    /// Words that must all appear in the name or subtitle, case-insensitively
+    pub text: Option<String>,
+    pub set_code: Option<String>,
+    pub rarity: Option<String>,
+    pub promo_grouping: Option<String>,
+    pub card_number: Option<u32>,
+    /// Rank by [`inkwell_core::search`] instead: typos are forgiven, the text may also
+    /// hold a set and number such as "1/123", and the best matches come first
+    pub fuzzy: bool,
+}
+
+/// One `LIKE` pattern per word of a text filter.
+fn like_patterns(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            let escaped = word
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+        .collect()
+}
+
+/// A page of the cards matching `filter`, in set and collector number order (best match
+/// first when fuzzy), and the number of matches overall. Descriptors are not loaded.
+pub async fn search_cards(
+    pool: &Pool<Sqlite>,
+    filter: &CardFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Card>, i64), sqlx::Error> {
+    let fuzzy_text = filter.text.as_deref().filter(|_| filter.fuzzy);
+    let patterns = match fuzzy_text {
+        Some(_) => Vec::new(),
+        None => like_patterns(filter.text.as_deref().unwrap_or_default()),
+    };
+    let mut conditions = String::from(
+        "(?1 IS NULL OR set_code = ?1) AND (?2 IS NULL OR rarity = ?2 COLLATE NOCASE) \
+         AND (?3 IS NULL OR promo_grouping = ?3 COLLATE NOCASE) AND (?4 IS NULL OR card_number = ?4)",
+    );
+    for i in 0..patterns.len() {
+        conditions.push_str(&format!(
+            " AND (name || ' ' || subtitle) LIKE ?{} ESCAPE '\\'",
+            i + 5
+        ));
+    }
+    if let Some(text) = fuzzy_text {
+        // The catalog is small enough to score in full
+        let sql = format!(
+            "SELECT {} FROM cards WHERE {} ORDER BY CAST(set_code AS INTEGER), set_code, card_number, id",
+            CARD_METADATA_COLUMNS, conditions
+        );
+        let rows = bind_card_filter(&sql, filter, &patterns)
+            .fetch_all(pool)
+            .await?;
+        let cards: Vec<Card> = rows.iter().map(card_from_row).collect();
+        let matches = inkwell_core::search::search(text, &cards, usize::MAX);
+        let page = matches
+            .iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|&card| card.clone())
+            .collect();
+        return Ok((page, matches.len() as i64));
+    }
+    let count_sql = format!("SELECT COUNT(*) AS total FROM cards WHERE {}", conditions);
+    let total: i64 = bind_card_filter(&count_sql, filter, &patterns)
+        .fetch_one(pool)
+        .await?
+        .get("total");
+    let page_sql = format!(
+        "SELECT {} FROM cards WHERE {} \
+         ORDER BY CAST(set_code AS INTEGER), set_code, card_number, id LIMIT {} OFFSET {}",
+        CARD_METADATA_COLUMNS, conditions, limit, offset
+    );
+    let rows = bind_card_filter(&page_sql, filter, &patterns)
+        .fetch_all(pool)
+        .await?;
+    Ok((rows.iter().map(card_from_row).collect(), total))
+}
+
+fn bind_card_filter<'q>(
+    sql: &'q str,
+    filter: &'q CardFilter,
+    patterns: &'q [String],
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    let mut query = sqlx::query(sql)
+        .bind(filter.set_code.as_deref())
+        .bind(filter.rarity.as_deref())
+        .bind(filter.promo_grouping.as_deref())
+        .bind(filter.card_number);
+    for pattern in patterns {
+        query = query.bind(pattern);
+    }
+    query
+}
+
+/// Everything known about a catalog card, for `GET /api/cards/{id}`.
+#[derive(Clone, Debug, Serialize)]
+pub struct CardDetail {
+    #[serde(flatten)]
+    pub card: Card,
+    /// Where ingestion downloaded the image from
+    pub image_source_url: Option<String>,
+    /// Number of extra reference views besides the official render
+    pub reference_views: i64,
+}
+
+pub async fn fetch_card_detail(
+    pool: &Pool<Sqlite>,
+    id: &str,
+) -> Result<Option<CardDetail>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {}, image_source_url,
+            (SELECT COUNT(*) FROM card_references r WHERE r.card_id = cards.id) AS reference_views
+         FROM cards WHERE id = ?",
+        CARD_METADATA_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| CardDetail {
+        card: card_from_row(&row),
+        image_source_url: row.get("image_source_url"),
+        reference_views: row.get("reference_views"),
+    }))
+}
+
+pub async fn load_index(pool: &Pool<Sqlite>) -> Result<GlobalIndex, sqlx::Error> {
+    load_index_excluding(pool, &[]).await
+}
+
+/// Like [`load_index`], but leaves out reference views of the given kinds, e.g. to
+/// measure what they contribute.
+pub async fn load_index_excluding(
+    pool: &Pool<Sqlite>,
+    kinds: &[&str],
+) -> Result<GlobalIndex, sqlx::Error> {
+    tracing::info!("Indexing cards for hot-RAM lookup...");
+    let rows = sqlx::query(&format!("SELECT {} FROM cards", CARD_COLUMNS))
+        .fetch_all(pool)
+        .await?;
+    let cards = rows.iter().map(card_from_row).collect();
+
+    let mut references: HashMap<String, Vec<Vec<u8>>> = HashMap::new();
+    let placeholders = vec!["?"; kinds.len()].join(", ");
+    let sql = format!(
+        "SELECT card_id, {} AS descriptors FROM card_references
+         WHERE {} IS NOT NULL AND kind NOT IN ({}) ORDER BY id",
+        features::DESCRIPTOR_COLUMN,
+        features::DESCRIPTOR_COLUMN,
+        placeholders
+    );
+    let mut query = sqlx::query(&sql);
+    for kind in kinds {
+        query = query.bind(*kind);
+    }
+    let rows = query.fetch_all(pool).await?;
+    for row in &rows {
+        references
+            .entry(row.get("card_id"))
+            .or_default()
+            .push(row.get("descriptors"));
+    }
+
+    let (index, skipped) = GlobalIndex::build(cards, references);
+    tracing::info!(
+        "Indexed {} cards with {} reference views ({} without features skipped).",
+        index.cards.len(),
+        index.view_count(),
+        skipped
+    );
+    Ok(index)
+}
+
+/// Every card with pure-Rust descriptors, without its AKAZE descriptors, in catalog order.
+pub async fn fetch_compact_features(
+    pool: &Pool<Sqlite>,
+) -> Result<Vec<(Card, Vec<u8>)>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {}, orb_data FROM cards WHERE length(orb_data) > 0 ORDER BY set_code, card_number, id",
+        CARD_METADATA_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (card_from_row(row), row.get("orb_data")))
+        .collect())
+}
+
+/// Builds the compact index clients match on-device (see [`inkwell_core::compact`]) from
+/// every card with pure-Rust descriptors.
+pub async fn load_compact_index(
+    pool: &Pool<Sqlite>,
+    working_size: u32,
+) -> Result<CompactIndex, sqlx::Error> {
+    let mut index = CompactIndex::new(working_size, DEFAULT_ACCEPT_VOTES);
+    for (card, descriptors) in fetch_compact_features(pool).await? {
+        index.push(card, &descriptors);
+    }
+    Ok(index)
+}
+
+/// Reads the given cards from the database; ids that no longer exist are simply absent.
+pub async fn fetch_cards(pool: &Pool<Sqlite>, ids: &[String]) -> Result<Vec<Card>, sqlx::Error> {
+    fetch_cards_selecting(pool, ids, CARD_COLUMNS).await
+}
+
+/// Like [`fetch_cards`], without loading the descriptors.
+pub async fn fetch_card_metadata(
+    pool: &Pool<Sqlite>,
+    ids: &[String],
+) -> Result<Vec<Card>, sqlx::Error> {
+    fetch_cards_selecting(pool, ids, CARD_METADATA_COLUMNS).await
+}
+
+async fn fetch_cards_selecting(
+    pool: &Pool<Sqlite>,
+    ids: &[String],
+    columns: &str,
+) -> Result<Vec<Card>, sqlx::Error> {
+    // Stay well under SQLite's bound-parameter limit
+    const CHUNK_SIZE: usize = 500;
+
+    let mut cards = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(CHUNK_SIZE) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let sql = format!(
+            "SELECT {} FROM cards WHERE id IN ({})",
+            columns, placeholders
+        );
+        let mut query = sqlx::query(&sql);
+        for id in chunk {
+            query = query.bind(id);
+        }
+        let rows = query.fetch_all(pool).await?;
+        cards.extend(rows.iter().map(card_from_row));
+    }
+    Ok(cards)
+}
+
+/// An extra reference view, without its descriptors.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReferenceView {
+    pub id: i64,
+    pub card_id: String,
+    pub kind: String,
+    pub source: Option<String>,
+    pub scan_id: Option<i64>,
+    /// Number of descriptors this build can match on
+    pub features: i64,
+    pub created_at: String,
+}
+
+/// Stores an extra reference view of a card, with descriptors from
+/// [`features::extract_reference`], and returns its id.
+pub async fn insert_reference<'e, E>(
+    executor: E,
+    card_id: &str,
+    kind: &str,
+    source: Option<&str>,
+    scan_id: Option<i64>,
+    descriptors: &[u8],
+) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = Sqlite>,
+{
+    // `akaze_data` predates the other kind and can't be NULL; empty means none
+    let (akaze_data, orb_data): (&[u8], Option<&[u8]>) = if cfg!(feature = "opencv") {
+        (descriptors, None)
+    } else {
+        (&[], Some(descriptors))
+    };
+    let result = sqlx::query(
+        "INSERT INTO card_references (card_id, kind, source, scan_id, akaze_data, orb_data) VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(card_id)
+    .bind(kind)
+    .bind(source)
+    .bind(scan_id)
+    .bind(akaze_data)
+    .bind(orb_data)
+    .execute(executor)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Deletes a reference view, returning the card it belonged to.
+pub async fn delete_reference(pool: &Pool<Sqlite>, id: i64) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query("DELETE FROM card_references WHERE id = ? RETURNING card_id")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|row| row.get("card_id")))
+}
+
+pub async fn list_references(
+    pool: &Pool<Sqlite>,
+    card_id: &str,
+) -> Result<Vec<ReferenceView>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT id, card_id, kind, source, scan_id, COALESCE(length({}), 0) / {} AS features, created_at FROM card_references WHERE card_id = ? ORDER BY id",
+        features::DESCRIPTOR_COLUMN,
+        features::DESCRIPTOR_SIZE
+    ))
+    .bind(card_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| ReferenceView {
+            id: row.get("id"),
+            card_id: row.get("card_id"),
+            kind: row.get("kind"),
+            source: row.get("source"),
+            scan_id: row.get("scan_id"),
+            features: row.get("features"),
+            created_at: row.get("created_at"),
+        })
+        .collect())
+}
+
+/// Extra views of the given cards by card id, as passed to [`GlobalIndex::apply_changes`].
+pub async fn fetch_references_for(
+    pool: &Pool<Sqlite>,
+    ids: &[String],
+) -> Result<HashMap<String, Vec<Vec<u8>>>, sqlx::Error> {
+    // Stay well under SQLite's bound-parameter limit
+    const CHUNK_SIZE: usize = 500;
+
+    let mut references: HashMap<String, Vec<Vec<u8>>> = HashMap::new();
+    for chunk in ids.chunks(CHUNK_SIZE) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let sql = format!(
+            "SELECT card_id, {} AS descriptors FROM card_references
+             WHERE {} IS NOT NULL AND card_id IN ({}) ORDER BY id",
+            features::DESCRIPTOR_COLUMN,
+            features::DESCRIPTOR_COLUMN,
+            placeholders
+        );
+        let mut query = sqlx::query(&sql);
+        for id in chunk {
+            query = query.bind(id);
+        }
+        for row in query.fetch_all(pool).await? {
+            references
+                .entry(row.get("card_id"))
+                .or_default()
+                .push(row.get("descriptors"));
+        }
+    }
+    Ok(references)
+}
+
+/// Descriptors of every extra view of a card, as passed to [`GlobalIndex::set_references`].
+pub async fn fetch_references(
+    pool: &Pool<Sqlite>,
+    card_id: &str,
+) -> Result<Vec<Vec<u8>>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} AS descriptors FROM card_references WHERE {} IS NOT NULL AND card_id = ? ORDER BY id",
+        features::DESCRIPTOR_COLUMN,
+        features::DESCRIPTOR_COLUMN
+    ))
+    .bind(card_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(|row| row.get("descriptors")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like_patterns_escape_wildcards() {
+        assert_eq!(like_patterns("  Mickey  brave "), ["%Mickey%", "%brave%"]);
+        assert_eq!(like_patterns("100%_off"), ["%100\\%\\_off%"]);
+        assert!(like_patterns("").is_empty());
+    }
+
+    fn card(id: &str, rows: usize) -> Card {
+        Card {
+            id: id.to_string(),
+            name: id.to_string(),
+            subtitle: String::new(),
+            phash: String::new(),
+            akaze_data: vec![rows as u8; rows * features::DESCRIPTOR_SIZE],
+            image_url: String::new(),
+            rarity: "Common".to_string(),
+            promo_grouping: None,
+            set_code: "1".to_string(),
+            card_number: 1,
+        }
+    }
+
+    fn assert_aligned(index: &GlobalIndex) {
+        assert_eq!(index.train_vec.len(), index.cards.len());
+        for (i, card) in index.cards.iter().enumerate() {
+            assert_eq!(index.positions[&card.id], i);
+            let expected_rows = card.akaze_data.len() / features::DESCRIPTOR_SIZE;
+            assert_eq!(index.train_vec.rows(i), expected_rows);
+        }
+    }
+
+    #[test]
+    fn test_incremental_updates_keep_index_aligned() {
+        let mut index = GlobalIndex::new();
+        index.upsert(card("a", 1)).unwrap();
+        index.upsert(card("b", 2)).unwrap();
+        index.upsert(card("c", 3)).unwrap();
+        assert_aligned(&index);
+
+        // Replace in place
+        index.upsert(card("b", 5)).unwrap();
+        assert_eq!(index.cards.len(), 3);
+        assert_aligned(&index);
+
+        // Removing from the middle moves the last card into the gap
+        assert!(index.remove("a"));
+        assert!(!index.remove("a"));
+        assert_eq!(index.cards[0].id, "c");
+        assert_aligned(&index);
+
+        // Ids without a fetched card are dropped
+        index.apply_changes(
+            &["b".to_string(), "d".to_string()],
+            vec![card("d", 4)],
+            HashMap::new(),
+        );
+        let ids: Vec<&str> = index.cards.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "d"]);
+        assert_aligned(&index);
+
+        // A card whose descriptors went missing is dropped too
+        assert!(!index.upsert(card("c", 0)).unwrap());
+        assert_eq!(index.cards.len(), 1);
+        assert_aligned(&index);
+    }
+
+    fn views(rows: &[usize]) -> Vec<Vec<u8>> {
+        rows.iter()
+            .map(|&rows| vec![rows as u8; rows * features::DESCRIPTOR_SIZE])
+            .collect()
+    }
+
+    fn view_rows(index: &GlobalIndex) -> Vec<(String, usize)> {
+        (0..index.view_count())
+            .map(|j| {
+                let card = &index.cards[index.card_of_view(j)];
+                (card.id.clone(), index.train_vec.rows(j))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reference_views_count_for_their_card() {
+        let mut index = GlobalIndex::new();
+        index.upsert(card("a", 1)).unwrap();
+        index.upsert(card("b", 2)).unwrap();
+        assert!(index.set_references("b", &views(&[3, 4])).unwrap());
+        assert_eq!(
+            view_rows(&index),
+            vec![
+                ("a".to_string(), 1),
+                ("b".to_string(), 2),
+                ("b".to_string(), 3),
+                ("b".to_string(), 4)
+            ]
+        );
+
+        // Views of a card that isn't indexed yet wait for it, and are enough on their own
+        assert!(!index.set_references("z", &views(&[5])).unwrap());
+        assert_eq!(index.view_count(), 4);
+        assert!(index.upsert(card("z", 0)).unwrap());
+        assert_eq!(view_rows(&index)[4], ("z".to_string(), 5));
+
+        // Updating the render keeps the extra views
+        index.upsert(card("b", 6)).unwrap();
+        assert_eq!(
+            view_rows(&index)[1..3],
+            [("b".to_string(), 6), ("b".to_string(), 3)]
+        );
+
+        // Removing a card drops its views; a card left without any view is dropped
+        assert!(index.remove("b"));
+        assert!(!index.set_references("z", &[]).unwrap());
+        assert_eq!(view_rows(&index), vec![("a".to_string(), 1)]);
+        assert_eq!(index.cards.len(), 1);
+    }
+}
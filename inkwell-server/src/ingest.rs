@@ -1,14 +1,38 @@
+use crate::download::{Downloader, RetryPolicy};
 use futures::StreamExt;
 use image::io::Reader as ImageReader;
 use img_hash::{HashAlg, HasherConfig};
-use reqwest::Client;
-use sqlx::{Pool, Sqlite};
-use std::{path::Path, sync::Arc};
+use inkwell_core::orb;
+use inkwell_server::{
+    augment::{self, AUGMENTED_KIND},
+    config::Config,
+    features, index,
+};
+use reqwest::header;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Row, Sqlite};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tokio::fs;
 
 const LORCANA_JSON_URL: &str = "https://lorcanajson.org/files/current/en/allCards.json";
-const IMAGE_DIR: &str = "card_images";
-const CONCURRENCY_LIMIT: usize = 10;
+/// URL prefix card images are served under (see the `/card_images` route).
+const IMAGE_URL_PREFIX: &str = "card_images";
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Condition for a card whose features needn't be extracted again. Without OpenCV there
+/// are no AKAZE descriptors to expect.
+#[cfg(feature = "opencv")]
+const COMPLETE_CARD: &str = "akaze_data IS NOT NULL AND phash IS NOT NULL AND phash != ''";
+#[cfg(not(feature = "opencv"))]
+const COMPLETE_CARD: &str = "phash IS NOT NULL AND phash != ''";
 
 #[derive(serde::Deserialize, Debug)]
 struct LorcanaCard {
@@ -31,17 +55,335 @@ struct LorcanaImages {
     full: String,
 }
 
+impl LorcanaCard {
+    /// Our card id, e.g. "1-12-345" (set code, number, lorcanajson id).
+    fn card_id(&self) -> String {
+        format!("{}-{}-{}", self.set_code, self.number, self.id)
+    }
+}
+
+/// What happened to a single card during an ingestion run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CardOutcome {
+    /// The card was not in the database before this run.
+    New,
+    /// Features were re-extracted or metadata changed.
+    Updated,
+    /// The card was already complete and nothing changed.
+    Skipped,
+}
+
+/// A row of `ingestion_runs`, as exposed by the admin API.
+#[derive(Clone, Debug, Serialize)]
+pub struct IngestionRun {
+    pub id: i64,
+    pub status: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub total_cards: i64,
+    pub new_cards: i64,
+    pub updated_cards: i64,
+    pub failed_cards: i64,
+    pub skipped_cards: i64,
+    pub error: Option<String>,
+}
+
+/// The result of a completed run: its recorded counts plus the cards whose
+/// indexed data changed, so the caller can update the index incrementally.
+#[derive(Clone, Debug)]
+pub struct IngestionOutcome {
+    pub run: IngestionRun,
+    pub changed_ids: Vec<String>,
+}
+
+/// A per-card failure recorded during a run.
+#[derive(Clone, Debug, Serialize)]
+pub struct IngestionError {
+    pub card_id: String,
+    pub message: String,
+    pub occurred_at: String,
+}
+
+const RUN_COLUMNS: &str = "id, status, started_at, finished_at, total_cards, new_cards, updated_cards, failed_cards, skipped_cards, error";
+
+fn run_from_row(row: &sqlx::sqlite::SqliteRow) -> IngestionRun {
+    IngestionRun {
+        id: row.get("id"),
+        status: row.get("status"),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+        total_cards: row.get("total_cards"),
+        new_cards: row.get("new_cards"),
+        updated_cards: row.get("updated_cards"),
+        failed_cards: row.get("failed_cards"),
+        skipped_cards: row.get("skipped_cards"),
+        error: row.get("error"),
+    }
+}
+
+/// Marks runs left in the `running` state by a previous process as interrupted.
+pub async fn mark_interrupted_runs(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE ingestion_runs SET status = 'interrupted', finished_at = CURRENT_TIMESTAMP WHERE status = 'running'",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn fetch_run(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+) -> Result<Option<IngestionRun>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM ingestion_runs WHERE id = ?",
+        RUN_COLUMNS
+    ))
+    .bind(run_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(run_from_row))
+}
+
+/// Returns the most recent run with the given status, or the most recent run overall.
+pub async fn latest_run(
+    pool: &Pool<Sqlite>,
+    status: Option<&str>,
+) -> Result<Option<IngestionRun>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM ingestion_runs WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC LIMIT 1",
+        RUN_COLUMNS
+    ))
+    .bind(status)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(run_from_row))
+}
+
+pub async fn list_runs(
+    pool: &Pool<Sqlite>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<IngestionRun>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM ingestion_runs ORDER BY id DESC LIMIT ? OFFSET ?",
+        RUN_COLUMNS
+    ))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(run_from_row).collect())
+}
+
+pub async fn list_run_errors(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+) -> Result<Vec<IngestionError>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT card_id, message, occurred_at FROM ingestion_errors WHERE run_id = ? ORDER BY id",
+    )
+    .bind(run_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| IngestionError {
+            card_id: row.get("card_id"),
+            message: row.get("message"),
+            occurred_at: row.get("occurred_at"),
+        })
+        .collect())
+}
+
+async fn record_outcome(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+    outcome: CardOutcome,
+) -> Result<(), sqlx::Error> {
+    let column = match outcome {
+        CardOutcome::New => "new_cards",
+        CardOutcome::Updated => "updated_cards",
+        CardOutcome::Skipped => "skipped_cards",
+    };
+    sqlx::query(&format!(
+        "UPDATE ingestion_runs SET {0} = {0} + 1 WHERE id = ?",
+        column
+    ))
+    .bind(run_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A card that failed in a previous run and will be retried first in the next one.
+#[derive(Clone, Debug, Serialize)]
+pub struct FailedCard {
+    pub card_id: String,
+    pub attempts: i64,
+    pub last_error: String,
+    pub first_failed_at: String,
+    pub last_failed_at: String,
+}
+
+pub async fn list_failed_cards(pool: &Pool<Sqlite>) -> Result<Vec<FailedCard>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT card_id, attempts, last_error, first_failed_at, last_failed_at FROM ingestion_failed_cards ORDER BY last_failed_at DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| FailedCard {
+            card_id: row.get("card_id"),
+            attempts: row.get("attempts"),
+            last_error: row.get("last_error"),
+            first_failed_at: row.get("first_failed_at"),
+            last_failed_at: row.get("last_failed_at"),
+        })
+        .collect())
+}
+
+async fn record_failure(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+    card_id: &str,
+    message: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO ingestion_errors (run_id, card_id, message) VALUES (?, ?, ?)")
+        .bind(run_id)
+        .bind(card_id)
+        .bind(message)
+        .execute(pool)
+        .await?;
+    sqlx::query("UPDATE ingestion_runs SET failed_cards = failed_cards + 1 WHERE id = ?")
+        .bind(run_id)
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO ingestion_failed_cards (card_id, last_error) VALUES (?, ?)
+        ON CONFLICT(card_id) DO UPDATE SET
+            attempts = attempts + 1,
+            last_error = excluded.last_error,
+            last_failed_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(card_id)
+    .bind(message)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn clear_failure(pool: &Pool<Sqlite>, card_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM ingestion_failed_cards WHERE card_id = ?")
+        .bind(card_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Inserts a new `running` row into `ingestion_runs` and returns its id, or `None` if
+/// another run is still `running`. The check and the insert are one statement, so this
+/// also keeps a server and an `ingest` command from running at the same time.
+pub async fn start_run(pool: &Pool<Sqlite>) -> Result<Option<i64>, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO ingestion_runs (status) SELECT 'running'
+         WHERE NOT EXISTS (SELECT 1 FROM ingestion_runs WHERE status = 'running')",
+    )
+    .execute(pool)
+    .await?;
+    Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+}
+
+/// Runs a full ingestion, recording its progress and outcome in `ingestion_runs`.
 pub async fn run_ingestion(
     pool: Pool<Sqlite>,
-    image_dir: String,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("Starting ingestion job...");
+    config: &Config,
+) -> Result<IngestionOutcome, BoxError> {
+    let Some(run_id) = start_run(&pool).await? else {
+        return Err("an ingestion run is already in progress".into());
+    };
+    execute_run(&pool, run_id, config).await
+}
+
+/// Performs the ingestion for a run previously created with [`start_run`].
+pub async fn execute_run(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+    config: &Config,
+) -> Result<IngestionOutcome, BoxError> {
+    tracing::info!("Starting ingestion run {}...", run_id);
+
+    let started = std::time::Instant::now();
+    let result = ingest_cards(pool, run_id, config).await;
+
+    let (status, error) = match &result {
+        Ok(_) => ("completed", None),
+        Err(e) => ("failed", Some(e.to_string())),
+    };
+    metrics::counter!("inkwell_ingestion_runs_total", "status" => status).increment(1);
+    metrics::gauge!("inkwell_ingestion_last_run_duration_seconds")
+        .set(started.elapsed().as_secs_f64());
+    metrics::gauge!("inkwell_ingestion_last_run_timestamp_seconds")
+        .set(chrono::Utc::now().timestamp() as f64);
+    sqlx::query(
+        "UPDATE ingestion_runs SET status = ?, error = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?",
+    )
+    .bind(status)
+    .bind(&error)
+    .bind(run_id)
+    .execute(pool)
+    .await?;
 
-    fs::create_dir_all(&image_dir).await?;
+    let changed_ids = result?;
 
-    println!("Fetching cards from {}", LORCANA_JSON_URL);
-    let client = Client::new();
-    let resp = client.get(LORCANA_JSON_URL).send().await?;
+    let run = fetch_run(pool, run_id)
+        .await?
+        .ok_or("ingestion run disappeared")?;
+    for (result, count) in [
+        ("new", run.new_cards),
+        ("updated", run.updated_cards),
+        ("skipped", run.skipped_cards),
+        ("failed", run.failed_cards),
+    ] {
+        metrics::gauge!("inkwell_ingestion_last_run_cards", "result" => result).set(count as f64);
+    }
+    tracing::info!(
+        "Ingestion run {} complete: {} new, {} updated, {} skipped, {} failed.",
+        run.id,
+        run.new_cards,
+        run.updated_cards,
+        run.skipped_cards,
+        run.failed_cards
+    );
+    Ok(IngestionOutcome { run, changed_ids })
+}
+
+/// Ingests every card in the catalog and returns the ids of new or updated cards.
+async fn ingest_cards(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+    config: &Config,
+) -> Result<Vec<String>, BoxError> {
+    fs::create_dir_all(&config.paths.card_images).await?;
+
+    let downloader = Downloader::new(
+        config.ingest.request_timeout(),
+        config.ingest.requests_per_second,
+        RetryPolicy {
+            max_attempts: config.ingest.max_attempts,
+            initial_backoff: INITIAL_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+        },
+    )?;
+
+    tracing::info!("Fetching cards from {}", LORCANA_JSON_URL);
+    let resp = downloader
+        .send(|client| client.get(LORCANA_JSON_URL))
+        .await?
+        .error_for_status()?;
     let json_text = resp.text().await?;
 
     #[derive(serde::Deserialize)]
@@ -49,131 +391,554 @@ pub async fn run_ingestion(
         cards: Vec<LorcanaCard>,
     }
 
-    let wrapper: Wrapper = serde_json::from_str(&json_text)?;
-    println!("Found {} cards in JSON.", wrapper.cards.len());
+    let mut wrapper: Wrapper = serde_json::from_str(&json_text)?;
+    tracing::info!("Found {} cards in JSON.", wrapper.cards.len());
+
+    // Retry cards that failed last time before anything else
+    let previously_failed: HashSet<String> =
+        sqlx::query_scalar("SELECT card_id FROM ingestion_failed_cards")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+    if !previously_failed.is_empty() {
+        tracing::info!(
+            "Retrying {} previously failed cards first.",
+            previously_failed.len()
+        );
+        wrapper
+            .cards
+            .sort_by_key(|card| !previously_failed.contains(&card.card_id()));
+    }
+
+    sqlx::query("UPDATE ingestion_runs SET total_cards = ? WHERE id = ?")
+        .bind(wrapper.cards.len() as i64)
+        .bind(run_id)
+        .execute(pool)
+        .await?;
 
-    let client = Arc::new(client);
+    let changed_ids = std::sync::Mutex::new(Vec::new());
 
     futures::stream::iter(wrapper.cards)
-        .for_each_concurrent(CONCURRENCY_LIMIT, |card_data| {
-            let pool = pool.clone();
-            let client = client.clone();
-            let image_dir = image_dir.clone();
+        .for_each_concurrent(config.ingest.concurrency, |card_data| {
+            let downloader = &downloader;
+            let changed_ids = &changed_ids;
+            let previously_failed = &previously_failed;
             async move {
-                let id = format!("{}-{}-{}", card_data.set_code, card_data.number, card_data.id);
-                let local_path = Path::new(&image_dir).join(format!("{}.jpg", id));
-                let db_image_url = format!("{}/{}.jpg", IMAGE_DIR, id);
-
-                let process_result = async {
-                    // Check if card exists and has complete data
-                    let existing_card: Option<sqlx::sqlite::SqliteRow> = sqlx::query(
-                        "SELECT id FROM cards WHERE id = ? AND akaze_data IS NOT NULL AND phash IS NOT NULL AND phash != ''"
-                    )
-                    .bind(&id)
-                    .fetch_optional(&pool)
-                    .await?;
-
-                    let file_exists = fs::try_exists(&local_path).await.unwrap_or(false);
-                    let needs_image_processing = !file_exists || existing_card.is_none();
-
-                    let subtitle = card_data.subtitle.clone().unwrap_or_default();
-                    let rarity = card_data.rarity.clone().unwrap_or_else(|| "Unknown".to_string());
-
-                    if needs_image_processing {
-                        let img_bytes = if !file_exists {
-                            println!("Downloading image for {}...", id);
-                            let bytes = client.get(&card_data.images.full).send().await?.bytes().await?;
-                            fs::write(&local_path, &bytes).await?;
-                            bytes
-                        } else {
-                            axum::body::Bytes::from(fs::read(&local_path).await?)
-                        };
-
-                        let (phash_str, akaze_bytes) = tokio::task::spawn_blocking(move || {
-                            let img = ImageReader::new(std::io::Cursor::new(&img_bytes))
-                                .with_guessed_format()?
-                                .decode()?;
-                            let processed = inkwell_core::preprocess_image(&img);
-
-                            let phash_str = {
-                                let hasher = HasherConfig::new().hash_alg(HashAlg::Gradient).hash_size(12, 12).to_hasher();
-                                let hash = hasher.hash_image(&processed);
-                                hash.as_bytes()
-                                    .iter()
-                                    .map(|b| format!("{:02x}", b))
-                                    .collect::<String>()
-                            };
-
-                            let (_, akaze_bytes) = inkwell_core::compute_akaze_features(&img)?;
-                            Result::< (String, Vec<u8>), Box<dyn std::error::Error + Send + Sync>>::Ok((phash_str, akaze_bytes))
-                        }).await??;
-
-                        sqlx::query(
-                            r#"
-                            INSERT INTO cards (id, name, subtitle, set_code, image_url, phash, meta_json, akaze_data, rarity, promo_grouping, card_number)
-                            VALUES (?, ?, ?, ?, ?, ?, '{}', ?, ?, ?, ?)
-                            ON CONFLICT(id) DO UPDATE SET
-                                name = excluded.name,
-                                subtitle = excluded.subtitle,
-                                phash = excluded.phash,
-                                image_url = excluded.image_url,
-                                akaze_data = excluded.akaze_data,
-                                rarity = excluded.rarity,
-                                promo_grouping = excluded.promo_grouping,
-                                set_code = excluded.set_code,
-                                card_number = excluded.card_number
-                            "#,
-                        )
-                        .bind(&id)
-                        .bind(&card_data.name)
-                        .bind(&subtitle)
-                        .bind(&card_data.set_code)
-                        .bind(&db_image_url)
-                        .bind(&phash_str)
-                        .bind(&akaze_bytes)
-                        .bind(&rarity)
-                        .bind(&card_data.promo_grouping)
-                        .bind(card_data.number)
-                        .execute(&pool)
-                        .await?;
-                        println!("Processed {}: {} [{}]", id, card_data.name, phash_str);
-                    } else {
-                        // Metadata only update
-                        sqlx::query(
-                            r#"
-                            UPDATE cards SET
-                                name = ?,
-                                subtitle = ?,
-                                rarity = ?,
-                                promo_grouping = ?,
-                                set_code = ?,
-                                card_number = ?
-                            WHERE id = ?
-                            "#,
-                        )
-                        .bind(&card_data.name)
-                        .bind(&subtitle)
-                        .bind(&rarity)
-                        .bind(&card_data.promo_grouping)
-                        .bind(&card_data.set_code)
-                        .bind(card_data.number)
-                        .bind(&id)
-                        .execute(&pool)
-                        .await?;
+                let id = card_data.card_id();
+
+                let recorded = match process_card(pool, downloader, config, &id, &card_data).await {
+                    Ok(outcome) => {
+                        if outcome != CardOutcome::Skipped {
+                            changed_ids.lock().unwrap().push(id.clone());
+                        }
+                        if previously_failed.contains(&id) {
+                            if let Err(e) = clear_failure(pool, &id).await {
+                                tracing::error!("Failed to clear failure for {}: {}", id, e);
+                            }
+                        }
+                        record_outcome(pool, run_id, outcome).await
                     }
+                    Err(e) => {
+                        tracing::warn!("Error processing card {}: {}", id, e);
+                        record_failure(pool, run_id, &id, &e.to_string()).await
+                    }
+                };
 
-                    // Ok to map to Box<dyn Error + Send + Sync> here
-                    Result::<(), Box<dyn std::error::Error + Send + Sync>>::Ok(())
+                if let Err(e) = recorded {
+                    tracing::error!("Failed to record ingestion progress for {}: {}", id, e);
                 }
-                .await;
+            }
+        })
+        .await;
+
+    Ok(changed_ids.into_inner().unwrap())
+}
 
-                if let Err(e) = process_result {
-                    eprintln!("Error processing card {}-{}: {}", card_data.set_code, card_data.number, e);
+/// Validators and content hash remembered from the last download of a card image.
+#[derive(Default)]
+struct StoredImage {
+    source_url: Option<String>,
+    hash: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+enum ImageFetch {
+    /// The server confirmed our copy is current (HTTP 304).
+    NotModified,
+    Fetched {
+        bytes: axum::body::Bytes,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// Downloads an image, sending the stored validators so unchanged images cost a 304.
+async fn fetch_image(
+    downloader: &Downloader,
+    url: &str,
+    validators: Option<&StoredImage>,
+) -> Result<ImageFetch, BoxError> {
+    let resp = downloader
+        .send(|client| {
+            let mut request = client.get(url);
+            if let Some(stored) = validators {
+                if let Some(etag) = &stored.etag {
+                    request = request.header(header::IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &stored.last_modified {
+                    request = request.header(header::IF_MODIFIED_SINCE, last_modified);
                 }
             }
+            request
         })
-        .await;
+        .await?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(ImageFetch::NotModified);
+    }
+    let resp = resp.error_for_status()?;
 
-    println!("Ingestion complete.");
-    Ok(())
+    let header_value = |name: header::HeaderName| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header_value(header::ETAG);
+    let last_modified = header_value(header::LAST_MODIFIED);
+
+    Ok(ImageFetch::Fetched {
+        bytes: resp.bytes().await?,
+        etag,
+        last_modified,
+    })
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Computes the legacy perceptual hash and, in builds with OpenCV, the AKAZE descriptors
+/// for an image. Without OpenCV the render's descriptors are the pure-Rust ones
+/// [`sync_compact_features`] stores.
+async fn extract_features(
+    img_bytes: axum::body::Bytes,
+    working_size: u32,
+) -> Result<(String, Option<Vec<u8>>), BoxError> {
+    tokio::task::spawn_blocking(move || {
+        let img = ImageReader::new(std::io::Cursor::new(&img_bytes))
+            .with_guessed_format()?
+            .decode()?;
+        let processed = inkwell_core::preprocess_image(&img);
+
+        let phash_str = {
+            let hasher = HasherConfig::new()
+                .hash_alg(HashAlg::Gradient)
+                .hash_size(12, 12)
+                .to_hasher();
+            let hash = hasher.hash_image(&processed);
+            hash.as_bytes()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+
+        let akaze_bytes = if cfg!(feature = "opencv") {
+            Some(features::extract_reference(&img, working_size)?)
+        } else {
+            None
+        };
+        Result::<(String, Option<Vec<u8>>), BoxError>::Ok((phash_str, akaze_bytes))
+    })
+    .await?
+}
+
+async fn process_card(
+    pool: &Pool<Sqlite>,
+    downloader: &Downloader,
+    config: &Config,
+    id: &str,
+    card_data: &LorcanaCard,
+) -> Result<CardOutcome, BoxError> {
+    let (outcome, render_changed) = store_card(pool, downloader, config, id, card_data).await?;
+    let local_path = local_image_path(config, id);
+    let views_changed = sync_augmented_views(pool, config, id, &local_path, render_changed).await?;
+    let orb_changed = sync_compact_features(pool, config, id, &local_path, render_changed).await?;
+    // Without OpenCV those are the descriptors the index matches on
+    let views_changed = views_changed || (orb_changed && cfg!(not(feature = "opencv")));
+
+    Ok(match outcome {
+        CardOutcome::Skipped if views_changed => CardOutcome::Updated,
+        outcome => outcome,
+    })
+}
+
+fn local_image_path(config: &Config, id: &str) -> PathBuf {
+    Path::new(&config.paths.card_images).join(format!("{}.jpg", id))
+}
+
+/// Downloads the card's image if needed and stores its features and metadata. Also
+/// returns whether features were re-extracted from the image.
+async fn store_card(
+    pool: &Pool<Sqlite>,
+    downloader: &Downloader,
+    config: &Config,
+    id: &str,
+    card_data: &LorcanaCard,
+) -> Result<(CardOutcome, bool), BoxError> {
+    let local_path = local_image_path(config, id);
+    let db_image_url = format!("{}/{}.jpg", IMAGE_URL_PREFIX, id);
+    let source_url = &card_data.images.full;
+
+    // Check if card exists, whether it has complete data and what we know about its image
+    let existing_card: Option<sqlx::sqlite::SqliteRow> = sqlx::query(&format!(
+        r#"
+        SELECT
+            {} AS complete,
+            image_source_url, image_hash, image_etag, image_last_modified
+        FROM cards WHERE id = ?
+        "#,
+        COMPLETE_CARD
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    let is_complete = existing_card
+        .as_ref()
+        .map(|row| row.get::<bool, _>("complete"))
+        .unwrap_or(false);
+    let mut stored = existing_card
+        .as_ref()
+        .map(|row| StoredImage {
+            source_url: row.get("image_source_url"),
+            hash: row.get("image_hash"),
+            etag: row.get("image_etag"),
+            last_modified: row.get("image_last_modified"),
+        })
+        .unwrap_or_default();
+
+    let file_exists = fs::try_exists(&local_path).await.unwrap_or(false);
+
+    // Cards ingested before hashes were tracked: hash the copy we already have
+    if file_exists && stored.hash.is_none() {
+        stored.hash = Some(content_hash(&fs::read(&local_path).await?));
+    }
+
+    // Only revalidate when our local copy came from the same URL
+    let validators = (file_exists && stored.source_url.as_deref() == Some(source_url.as_str()))
+        .then_some(&stored);
+
+    // Decide which bytes (if any) need feature extraction
+    let (img_bytes, etag, last_modified) =
+        match fetch_image(downloader, source_url, validators).await? {
+            ImageFetch::NotModified => {
+                tracing::debug!("Image for {} not modified.", id);
+                let bytes = if is_complete {
+                    None
+                } else {
+                    Some(axum::body::Bytes::from(fs::read(&local_path).await?))
+                };
+                (bytes, stored.etag.clone(), stored.last_modified.clone())
+            }
+            ImageFetch::Fetched {
+                bytes,
+                etag,
+                last_modified,
+            } => {
+                let hash = content_hash(&bytes);
+                let unchanged = file_exists && stored.hash.as_deref() == Some(hash.as_str());
+                if !unchanged {
+                    if file_exists {
+                        tracing::info!("Image for {} changed; re-extracting features.", id);
+                    } else {
+                        tracing::debug!("Downloaded image for {}.", id);
+                    }
+                    fs::write(&local_path, &bytes).await?;
+                }
+                stored.hash = Some(hash);
+                let bytes = (!unchanged || !is_complete).then_some(bytes);
+                (bytes, etag, last_modified)
+            }
+        };
+
+    let subtitle = card_data.subtitle.clone().unwrap_or_default();
+    let rarity = card_data
+        .rarity
+        .clone()
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    if let Some(img_bytes) = img_bytes {
+        let (phash_str, akaze_bytes) =
+            extract_features(img_bytes, config.matching.working_size).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO cards (id, name, subtitle, set_code, image_url, phash, meta_json, akaze_data, rarity, promo_grouping, card_number,
+                               image_source_url, image_hash, image_etag, image_last_modified)
+            VALUES (?, ?, ?, ?, ?, ?, '{}', ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                subtitle = excluded.subtitle,
+                phash = excluded.phash,
+                image_url = excluded.image_url,
+                akaze_data = excluded.akaze_data,
+                rarity = excluded.rarity,
+                promo_grouping = excluded.promo_grouping,
+                set_code = excluded.set_code,
+                card_number = excluded.card_number,
+                image_source_url = excluded.image_source_url,
+                image_hash = excluded.image_hash,
+                image_etag = excluded.image_etag,
+                image_last_modified = excluded.image_last_modified
+            "#,
+        )
+        .bind(id)
+        .bind(&card_data.name)
+        .bind(&subtitle)
+        .bind(&card_data.set_code)
+        .bind(&db_image_url)
+        .bind(&phash_str)
+        .bind(&akaze_bytes)
+        .bind(&rarity)
+        .bind(&card_data.promo_grouping)
+        .bind(card_data.number)
+        .bind(source_url)
+        .bind(&stored.hash)
+        .bind(&etag)
+        .bind(&last_modified)
+        .execute(pool)
+        .await?;
+        tracing::info!("Processed {}: {} [{}]", id, card_data.name, phash_str);
+
+        let outcome = if existing_card.is_some() {
+            CardOutcome::Updated
+        } else {
+            CardOutcome::New
+        };
+        Ok((outcome, true))
+    } else {
+        // Image unchanged; refresh validators without affecting the outcome
+        sqlx::query(
+            "UPDATE cards SET image_source_url = ?, image_hash = ?, image_etag = ?, image_last_modified = ? WHERE id = ?",
+        )
+        .bind(source_url)
+        .bind(&stored.hash)
+        .bind(&etag)
+        .bind(&last_modified)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        // Metadata only update, touching the row only when something changed
+        let result = sqlx::query(
+            r#"
+            UPDATE cards SET
+                name = ?1,
+                subtitle = ?2,
+                rarity = ?3,
+                promo_grouping = ?4,
+                set_code = ?5,
+                card_number = ?6
+            WHERE id = ?7 AND (
+                name IS NOT ?1 OR
+                subtitle IS NOT ?2 OR
+                rarity IS NOT ?3 OR
+                promo_grouping IS NOT ?4 OR
+                set_code IS NOT ?5 OR
+                card_number IS NOT ?6
+            )
+            "#,
+        )
+        .bind(&card_data.name)
+        .bind(&subtitle)
+        .bind(&rarity)
+        .bind(&card_data.promo_grouping)
+        .bind(&card_data.set_code)
+        .bind(card_data.number)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        let outcome = if result.rows_affected() > 0 {
+            CardOutcome::Updated
+        } else {
+            CardOutcome::Skipped
+        };
+        Ok((outcome, false))
+    }
+}
+
+/// Keeps a card's augmented reference views in line with `ingest.augment_variants`:
+/// they're regenerated when the render changed, the configured count differs from the
+/// one they were generated with or some lack this build's descriptors (they were made by
+/// a build with other [`features`]), and removed when augmentation is off. Returns
+/// whether they changed.
+async fn sync_augmented_views(
+    pool: &Pool<Sqlite>,
+    config: &Config,
+    id: &str,
+    image_path: &Path,
+    render_changed: bool,
+) -> Result<bool, BoxError> {
+    let wanted = config.ingest.augment_variants;
+    let row = sqlx::query(&format!(
+        "SELECT augment_variants, EXISTS (
+            SELECT 1 FROM card_references r
+            WHERE r.card_id = cards.id AND r.kind = ? AND ({0} IS NULL OR length({0}) = 0)
+         ) AS foreign_views
+         FROM cards WHERE id = ?",
+        features::DESCRIPTOR_COLUMN
+    ))
+    .bind(AUGMENTED_KIND)
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+    let generated: i64 = row.get("augment_variants");
+    let foreign_views: bool = row.get("foreign_views");
+    if !augmented_views_outdated(generated as usize, wanted, render_changed || foreign_views) {
+        return Ok(false);
+    }
+
+    let views = if wanted > 0 {
+        let bytes = fs::read(image_path).await?;
+        augmented_features(bytes, id, wanted, config.matching.working_size).await?
+    } else {
+        Vec::new()
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM card_references WHERE card_id = ? AND kind = ?")
+        .bind(id)
+        .bind(AUGMENTED_KIND)
+        .execute(&mut *tx)
+        .await?;
+    for (variant, descriptors) in &views {
+        let source = format!("variant {}", variant + 1);
+        index::insert_reference(
+            &mut *tx,
+            id,
+            AUGMENTED_KIND,
+            Some(&source),
+            None,
+            descriptors,
+        )
+        .await?;
+    }
+    // The attempted count, not the stored one: variants without features are dropped
+    sqlx::query("UPDATE cards SET augment_variants = ? WHERE id = ?")
+        .bind(wanted as i64)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::debug!(
+        "Stored {} of {} augmented views for {}.",
+        views.len(),
+        wanted,
+        id
+    );
+    Ok(true)
+}
+
+/// Whether views generated from `generated` variants need redoing for `wanted`.
+fn augmented_views_outdated(generated: usize, wanted: usize, render_changed: bool) -> bool {
+    generated != wanted || (render_changed && wanted > 0)
+}
+
+/// Stores the pure-Rust descriptors of a card's render for the compact index, when the
+/// render changed or they were never computed. Returns whether they were stored.
+async fn sync_compact_features(
+    pool: &Pool<Sqlite>,
+    config: &Config,
+    id: &str,
+    image_path: &Path,
+    render_changed: bool,
+) -> Result<bool, BoxError> {
+    if !render_changed {
+        let missing: bool =
+            sqlx::query("SELECT orb_data IS NULL AS missing FROM cards WHERE id = ?")
+                .bind(id)
+                .fetch_one(pool)
+                .await?
+                .get("missing");
+        if !missing {
+            return Ok(false);
+        }
+    }
+
+    let img_bytes = fs::read(image_path).await?;
+    let working_size = config.matching.working_size;
+    let orb_data = tokio::task::spawn_blocking(move || {
+        let img = ImageReader::new(std::io::Cursor::new(&img_bytes))
+            .with_guessed_format()?
+            .decode()?;
+        let (_, descriptors) = orb::extract(&img, working_size, orb::REFERENCE_FEATURES);
+        Result::<Vec<u8>, BoxError>::Ok(descriptors)
+    })
+    .await??;
+
+    sqlx::query("UPDATE cards SET orb_data = ? WHERE id = ?")
+        .bind(&orb_data)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(true)
+}
+
+/// Descriptors (see [`features`]) of `count` synthetic variants of a card image, by
+/// variant number; variants without features are left out.
+async fn augmented_features(
+    img_bytes: Vec<u8>,
+    id: &str,
+    count: usize,
+    working_size: u32,
+) -> Result<Vec<(usize, Vec<u8>)>, BoxError> {
+    let seed = augment::seed_for(id);
+    tokio::task::spawn_blocking(move || {
+        let img = ImageReader::new(std::io::Cursor::new(&img_bytes))
+            .with_guessed_format()?
+            .decode()?;
+        let mut views = Vec::with_capacity(count);
+        for (i, variant) in augment::variants(&img, count, seed).iter().enumerate() {
+            let descriptors = features::extract_reference(variant, working_size)?;
+            if !descriptors.is_empty() {
+                views.push((i, descriptors));
+            }
+        }
+        Result::<Vec<(usize, Vec<u8>)>, BoxError>::Ok(views)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_augmented_views_outdated() {
+        // 4 variants generated, one without features: 3 stored views are still current
+        assert!(!augmented_views_outdated(4, 4, false));
+        assert!(augmented_views_outdated(4, 4, true));
+        assert!(augmented_views_outdated(4, 8, false));
+        // Augmentation turned off removes the views once, then leaves the card alone
+        assert!(augmented_views_outdated(4, 0, false));
+        assert!(!augmented_views_outdated(0, 0, true));
+    }
+
+    #[tokio::test]
+    async fn test_start_run_refuses_while_one_is_running() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("../migrations").run(&pool).await.unwrap();
+
+        assert!(start_run(&pool).await.unwrap().is_some());
+        assert_eq!(start_run(&pool).await.unwrap(), None);
+        mark_interrupted_runs(&pool).await.unwrap();
+        assert!(start_run(&pool).await.unwrap().is_some());
+    }
 }
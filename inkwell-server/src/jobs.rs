@@ -0,0 +1,141 @@
+use crate::{ingest, offline::CompactCache};
+use chrono::Utc;
+use cron::Schedule;
+use inkwell_server::{config::Config, identify::Identifier, index};
+use sqlx::{Pool, Sqlite};
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
+
+#[derive(Debug)]
+pub enum TriggerError {
+    /// Another run, in this process or another one, is in progress.
+    AlreadyRunning,
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for TriggerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TriggerError::AlreadyRunning => write!(f, "an ingestion run is already in progress"),
+            TriggerError::Database(e) => write!(f, "failed to start ingestion run: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TriggerError {}
+
+/// Runs ingestion jobs one at a time and applies the changed cards to the index afterwards.
+#[derive(Clone)]
+pub struct IngestionRunner {
+    pool: Pool<Sqlite>,
+    identifier: Arc<RwLock<Identifier>>,
+    config: Arc<Config>,
+    compact_index: CompactCache,
+    lock: Arc<Mutex<()>>,
+}
+
+impl IngestionRunner {
+    pub fn new(
+        pool: Pool<Sqlite>,
+        identifier: Arc<RwLock<Identifier>>,
+        config: Arc<Config>,
+        compact_index: CompactCache,
+    ) -> Self {
+        Self {
+            pool,
+            identifier,
+            config,
+            compact_index,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Starts a run in the background and returns its id, or fails if one is already in progress.
+    pub async fn trigger(&self) -> Result<i64, TriggerError> {
+        let guard = self
+            .lock
+            .clone()
+            .try_lock_owned()
+            .map_err(|_| TriggerError::AlreadyRunning)?;
+        let run_id = ingest::start_run(&self.pool)
+            .await
+            .map_err(TriggerError::Database)?
+            .ok_or(TriggerError::AlreadyRunning)?;
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            this.execute(guard, run_id).await;
+        });
+        Ok(run_id)
+    }
+
+    /// Runs ingestion to completion on the current task, skipping if a run is already in progress.
+    pub async fn run_now(&self) {
+        let guard = match self.lock.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                tracing::info!("Skipping ingestion: {}", TriggerError::AlreadyRunning);
+                return;
+            }
+        };
+        match ingest::start_run(&self.pool).await {
+            Ok(Some(run_id)) => self.execute(guard, run_id).await,
+            Ok(None) => tracing::info!("Skipping ingestion: {}", TriggerError::AlreadyRunning),
+            Err(e) => tracing::error!("Failed to start ingestion run: {}", e),
+        }
+    }
+
+    async fn execute(&self, _guard: OwnedMutexGuard<()>, run_id: i64) {
+        let outcome = match ingest::execute_run(&self.pool, run_id, &self.config).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                tracing::error!("Ingestion job failed: {}", e);
+                return;
+            }
+        };
+        // Even a run that changed no cards may have filled in missing descriptors
+        self.compact_index.invalidate().await;
+
+        if outcome.changed_ids.is_empty() {
+            tracing::info!("No cards changed; index left as is.");
+            return;
+        }
+
+        // Read from the database before taking the write lock so scans aren't blocked on I/O
+        let changes = futures::try_join!(
+            index::fetch_cards(&self.pool, &outcome.changed_ids),
+            index::fetch_references_for(&self.pool, &outcome.changed_ids),
+        );
+        match changes {
+            Ok((cards, references)) => {
+                let mut wl = self.identifier.write().await;
+                wl.apply_changes(&outcome.changed_ids, cards, references);
+            }
+            Err(e) => tracing::error!("Failed to update index: {}", e),
+        }
+    }
+}
+
+/// Runs ingestion on startup (if requested) and then at every tick of `schedule`.
+pub async fn run_schedule(runner: IngestionRunner, schedule: Option<Schedule>, on_startup: bool) {
+    if on_startup {
+        runner.run_now().await;
+    }
+
+    let Some(schedule) = schedule else {
+        tracing::info!("No ingestion schedule configured; only on-demand runs will happen.");
+        return;
+    };
+
+    loop {
+        let Some(next) = schedule.upcoming(Utc).next() else {
+            tracing::warn!("Ingestion schedule has no upcoming runs.");
+            return;
+        };
+        tracing::info!("Next scheduled ingestion at {}", next);
+
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        runner.run_now().await;
+    }
+}
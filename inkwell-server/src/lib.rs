@@ -0,0 +1,10 @@
+//! Card identification and configuration shared by the HTTP server, the `verify` tool and tests.
+
+pub mod augment;
+pub mod calibration;
+pub mod config;
+pub mod dataset;
+pub mod error;
+pub mod features;
+pub mod identify;
+pub mod index;
@@ -1,72 +1,71 @@
 use axum::{
     body::Bytes,
-    extract::State,
-    routing::{get, post},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
-use image::io::Reader as ImageReader;
-use inkwell_core::{akaze_bytes_to_mat, Card, ScanResult};
-use opencv::{
-    core::{DMatch, Mat, Vector, NORM_HAMMING},
-    features2d::BFMatcher,
-    prelude::*,
+use inkwell_core::{
+    export::{self, ExportFormat, ExportItem},
+    import::{self, ImportResult},
+    Card, ScanResult,
 };
+use inkwell_server::{
+    calibration::Calibration,
+    config::Config,
+    error::IdentifyError,
+    identify::{self, Identifier},
+    index,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::Deserialize;
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
-use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 
+mod admin;
+mod collection;
+mod download;
 mod ingest;
+mod jobs;
+mod offline;
+mod references;
+mod scans;
+mod sessions;
+mod telemetry;
 
 #[derive(Clone)]
 struct AppState {
-    #[allow(dead_code)]
     pool: Pool<Sqlite>,
-    index: Arc<tokio::sync::RwLock<Arc<GlobalIndex>>>,
+    config: Arc<Config>,
+    identifier: Arc<tokio::sync::RwLock<Identifier>>,
+    ingestion: jobs::IngestionRunner,
+    compact_index: offline::CompactCache,
+    metrics: PrometheusHandle,
 }
 
-struct GlobalIndex {
-    train_vec: Vector<Mat>,
-    cards: Vec<Card>,
-}
+const USAGE: &str = "Usage: inkwell-server [--config <path>] [--print-config] [<command>]
 
-async fn load_index(pool: &Pool<Sqlite>) -> Result<GlobalIndex, sqlx::Error> {
-    println!("Indexing cards for hot-RAM lookup...");
-    let rows = sqlx::query("SELECT id, name, subtitle, phash, image_url, akaze_data, rarity, promo_grouping, set_code, card_number FROM cards")
-        .fetch_all(pool)
-        .await?;
+Commands:
+  serve   Start the HTTP server and the ingestion scheduler (default)
+  ingest  Run a single ingestion and exit
+  add-references <dir> [--kind <kind>]
+          Add the labeled images under <dir> as extra reference views of their cards
+          (kind defaults to \"photo\"; e.g. \"sleeved\"). Labels follow the `verify eval`
+          conventions. A running server picks them up on restart or on
+          POST /api/admin/index/reload.
 
-    let mut train_vec = Vector::<Mat>::new();
-    let mut cards = Vec::new();
-    for row in rows {
-        let akaze_data: Vec<u8> = row.get("akaze_data");
-        let phash_str: String = row.get("phash");
-
-        let card = Card {
-            id: row.get("id"),
-            name: row.get("name"),
-            subtitle: row.get("subtitle"),
-            phash: phash_str,
-            akaze_data: akaze_data.clone(),
-            image_url: row.get("image_url"),
-            rarity: row.get("rarity"),
-            promo_grouping: row.get("promo_grouping"),
-            set_code: row.get("set_code"),
-            card_number: row.get("card_number"),
-        };
-
-        if let Ok(m) = inkwell_core::akaze_bytes_to_mat(&akaze_data) {
-            train_vec.push(m);
-            cards.push(card);
-        }
-    }
-    println!("Indexed {} cards.", cards.len());
-    Ok(GlobalIndex { train_vec, cards })
-}
+Options:
+  --config <path>  Read settings from this TOML file (default: $INKWELL_CONFIG or
+                   ./inkwell.toml if present). Environment variables override it.
+  --print-config   Print the effective configuration and exit";
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     dotenvy::dotenv().ok();
 
     tracing_subscriber::fmt()
@@ -76,8 +75,66 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .init();
 
-    let database_url =
-        std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:inkwell.db".to_string());
+    let mut config_path = None;
+    let mut print_config = false;
+    let mut kind = None;
+    let mut positional = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => match args.next() {
+                Some(path) => config_path = Some(path),
+                None => usage_error("--config needs a path"),
+            },
+            "--print-config" => print_config = true,
+            "--kind" => match args.next() {
+                Some(value) => kind = Some(value),
+                None => usage_error("--kind needs a value"),
+            },
+            "-h" | "--help" | "help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let config = Config::load(config_path.as_deref())?;
+    if print_config {
+        print!("{}", config.to_toml_redacted());
+        return Ok(());
+    }
+    let config = Arc::new(config);
+
+    let positional: Vec<&str> = positional.iter().map(String::as_str).collect();
+    match (positional.as_slice(), kind) {
+        ([] | ["serve"], None) => serve(config).await,
+        (["ingest"], None) => ingest_once(config).await,
+        (["add-references", dir], kind) => {
+            add_references(
+                config,
+                dir,
+                kind.as_deref().unwrap_or(references::DEFAULT_KIND),
+            )
+            .await
+        }
+        ([command, ..], _) if !["serve", "ingest", "add-references"].contains(command) => {
+            usage_error(&format!("Unknown command: {}", command))
+        }
+        _ => usage_error("Unexpected arguments"),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    std::process::exit(2);
+}
+
+/// Opens the database (creating it if needed) and applies pending migrations.
+async fn connect_db(
+    config: &Config,
+) -> Result<Pool<Sqlite>, Box<dyn std::error::Error + Send + Sync>> {
+    let database_url = &config.database.url;
 
     // Setup DB
     // Ensure parent directories exist for sqlite
@@ -93,267 +150,257 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let connection_options =
-        sqlx::sqlite::SqliteConnectOptions::from_str(&database_url)?.create_if_missing(true);
+        sqlx::sqlite::SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
 
     let pool = SqlitePoolOptions::new()
-        .max_connections(5)
+        .max_connections(config.database.max_connections)
         .connect_with(connection_options)
         .await?;
 
     // Run migrations
     sqlx::migrate!("../migrations").run(&pool).await?;
 
+    Ok(pool)
+}
+
+/// Runs a single ingestion without starting the HTTP server.
+async fn ingest_once(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pool = connect_db(&config).await?;
+    let outcome = ingest::run_ingestion(pool, &config).await?;
+    println!("{}", serde_json::to_string_pretty(&outcome.run)?);
+    Ok(())
+}
+
+/// Imports reference views from a folder without starting the HTTP server.
+async fn add_references(
+    config: Arc<Config>,
+    dir: &str,
+    kind: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pool = connect_db(&config).await?;
+    let summary = references::import_dir(&pool, &config, std::path::Path::new(dir), kind).await?;
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+async fn serve(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pool = connect_db(&config).await?;
+    ingest::mark_interrupted_runs(&pool).await?;
+
+    // Validated by Config::load
+    let schedule = config.ingest.schedule()?;
+
+    if config.admin.token.is_none() {
+        tracing::warn!("admin.token (ADMIN_TOKEN) is not set; admin endpoints are disabled.");
+    }
+
     // Load and Index Cards
-    let index = load_index(&pool).await?;
+    let calibration = match &config.matching.calibration_file {
+        Some(path) => Some(Calibration::load(path)?),
+        None => {
+            tracing::info!("No calibration file configured; using uncalibrated confidence.");
+            None
+        }
+    };
+    let identifier = Identifier::load(&pool)
+        .await?
+        .with_calibration(calibration)
+        .with_params(config.matching.params());
+    let identifier = Arc::new(tokio::sync::RwLock::new(identifier));
+    let compact_index = offline::CompactCache::default();
+    let ingestion = jobs::IngestionRunner::new(
+        pool.clone(),
+        identifier.clone(),
+        config.clone(),
+        compact_index.clone(),
+    );
 
     let state = AppState {
         pool: pool.clone(),
-        index: Arc::new(tokio::sync::RwLock::new(Arc::new(index))),
+        config: config.clone(),
+        identifier,
+        ingestion: ingestion.clone(),
+        compact_index,
+        metrics: telemetry::install()?,
     };
 
-    // Spawn ingestion background task
-    let bg_pool = pool.clone();
-    let bg_index = state.index.clone();
-    tokio::spawn(async move {
-        loop {
-            let image_dir =
-                std::env::var("CARD_IMAGES_DIR").unwrap_or_else(|_| "card_images".to_string());
-            if let Err(e) = ingest::run_ingestion(bg_pool.clone(), image_dir).await {
-                eprintln!("Ingestion job failed: {}", e);
-            } else {
-                match load_index(&bg_pool).await {
-                    Ok(new_index) => {
-                        let mut wl = bg_index.write().await;
-                        *wl = Arc::new(new_index);
-                        println!("Reloaded index in background.");
-                    }
-                    Err(e) => eprintln!("Failed to reload index: {}", e),
-                }
-            }
-            tokio::time::sleep(tokio::time::Duration::from_secs(24 * 60 * 60)).await;
-        }
-    });
+    // Spawn ingestion scheduler
+    tokio::spawn(jobs::run_schedule(
+        ingestion,
+        schedule,
+        config.ingest.on_startup,
+    ));
+    tokio::spawn(scans::run_retention(pool.clone(), config.clone()));
 
     // Setup Routes
-    let app = Router::new()
-        .route("/health", get(|| async { "OK" }))
-        .route("/api/identify", post(identify_card))
-        .route("/api/stats", get(get_stats))
-        .nest_service(
-            "/card_images",
-            tower_http::services::ServeDir::new("card_images"),
-        )
-        .fallback_service(tower_http::services::ServeDir::new("dist"))
+    let mut app =
+        Router::new()
+            .route("/health", get(|| async { "OK" }))
+            .route("/api/identify", post(identify_card))
+            .route("/api/scans/:id/label", post(label_scan))
+            .route("/api/stats", get(get_stats))
+            .route("/api/index/compact", get(get_compact_index))
+            .route("/api/cards", get(search_cards))
+            .route("/api/cards/:id", get(get_card))
+            .route("/api/import/resolve", post(resolve_import))
+            .route(
+                "/api/collection/import",
+                post(import_collection).route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    admin::require_write,
+                )),
+            )
+            .route("/api/collection/export", get(export_collection))
+            .route("/api/sessions", get(list_sessions))
+            .route(
+                "/api/sessions/:id",
+                put(save_session).delete(delete_session).route_layer(
+                    middleware::from_fn_with_state(state.clone(), admin::require_write),
+                ),
+            )
+            .route("/api/sessions/:id/export", get(export_session))
+            .nest("/api/admin", admin::router(state.clone()))
+            .nest_service(
+                "/card_images",
+                tower_http::services::ServeDir::new(&config.paths.card_images),
+            )
+            .fallback_service(tower_http::services::ServeDir::new(&config.paths.dist));
+    if config.server.metrics {
+        app = app.route("/metrics", get(telemetry::render));
+    }
+    let app = app
+        .layer(middleware::from_fn(telemetry::track_http))
         .with_state(state);
 
     // Start Server
-    let addr = "0.0.0.0:4000";
+    let addr = &config.server.addr;
     println!("Listening on http://{}", addr);
     let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
-async fn identify_card(State(state): State<AppState>, body: Bytes) -> Json<ScanResult> {
-    tracing::info!("Received identification request ({} bytes)", body.len());
-
-    let global_index = {
-        let rl = state.index.read().await;
-        rl.clone()
+async fn identify_card(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ScanResult>, IdentifyError> {
+    let mut scan = scans::NewScan {
+        image_format: identify::sniff_extension(&body),
+        client_ip: Some(client_ip(
+            &headers,
+            peer,
+            &state.config.server.trusted_proxies,
+        )),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        ..Default::default()
     };
-
-    let scan_result = tokio::task::spawn_blocking(move || {
-        // Save image for debugging if configured (Synchronous I/O)
-        if let Ok(dir) = std::env::var("CAPTURED_IMAGES_DIR") {
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis();
-            let _ = std::fs::create_dir_all(&dir);
-            let filename = format!("{}/img_{}.jpg", dir, timestamp);
-            if let Err(e) = std::fs::write(&filename, &body) {
-                tracing::error!("Failed to save image: {}", e);
-            } else {
-                tracing::debug!("Saved image to {}", filename);
-            }
+    let result = identify_upload(&state, body.clone(), &mut scan).await;
+
+    let outcome = match &result {
+        Ok(scan) if scan.card.is_some() => "match",
+        Ok(_) => "no_match",
+        Err(e) => {
+            metrics::counter!("inkwell_identify_errors_total", "code" => e.code()).increment(1);
+            "error"
         }
+    };
+    metrics::counter!("inkwell_identify_total", "outcome" => outcome).increment(1);
 
-        // Decode Image
-        let img_reader = match ImageReader::new(Cursor::new(&body)).with_guessed_format() {
-            Ok(reader) => reader,
-            Err(e) => {
-                tracing::error!("Failed to guess image format: {}", e);
-                return ScanResult {
-                    card: None,
-                    confidence: 0.0,
-                    global_total_scans: 0,
-                };
-            }
-        };
-        let img_result = img_reader.decode();
-
-        let raw_img = match img_result {
-            Ok(img) => img,
-            Err(e) => {
-                tracing::error!("Failed to decode image: {}", e);
-                return ScanResult {
-                    card: None,
-                    confidence: 0.0,
-                    global_total_scans: 0,
-                };
-            }
-        };
-
-        // Compute AKAZE
-        let (_kp, query_desc_bytes) = match inkwell_core::compute_akaze_features(&raw_img) {
-            Ok(res) => res,
-            Err(e) => {
-                tracing::error!("AKAZE computation failed: {}", e);
-                return ScanResult {
-                    card: None,
-                    confidence: 0.0,
-                    global_total_scans: 0,
-                };
-            }
-        };
-
-        if query_desc_bytes.is_empty() {
-            tracing::warn!("No features found in query image.");
-            return ScanResult {
-                card: None,
-                confidence: 0.0,
-                global_total_scans: 0,
-            };
+    scan.outcome = outcome;
+    match &result {
+        Ok(result) => scan.card_id = result.card.as_ref().map(|card| card.id.clone()),
+        Err(e) => scan.error_code = Some(e.code()),
+    }
+    let scan_id = match scans::record(&state.pool, &scan, body.len()).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            tracing::error!("Failed to log scan: {}", e);
+            None
         }
-
-        let query_mat = match akaze_bytes_to_mat(&query_desc_bytes) {
-            Ok(m) => m,
-            Err(e) => {
-                tracing::error!("Failed to create query Mat: {}", e);
-                return ScanResult {
-                    card: None,
-                    confidence: 0.0,
-                    global_total_scans: 0,
-                };
-            }
-        };
-
-        // Match against index
-        // Use BFMatcher with NORM_HAMMING
-        let mut matcher = match BFMatcher::create(NORM_HAMMING, false) {
-            Ok(m) => m,
-            Err(e) => {
-                tracing::error!("Failed to create BFMatcher: {}", e);
-                return ScanResult {
-                    card: None,
-                    confidence: 0.0,
-                    global_total_scans: 0,
-                };
-            }
-        };
-
-        if let Err(e) = matcher.add(&global_index.train_vec) {
-            tracing::error!("Matcher add failed: {}", e);
-            return ScanResult {
-                card: None,
-                confidence: 0.0,
-                global_total_scans: 0,
-            };
+    };
+    // Written in the background so a slow disk doesn't delay the response
+    if let (Some(id), Some(dir)) = (scan_id, state.config.paths.captured_images.clone()) {
+        if !body.is_empty() {
+            let pool = state.pool.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    scans::store_capture(&pool, &dir, id, scan.image_format, &body).await
+                {
+                    tracing::error!("Failed to save capture of scan {}: {}", id, e);
+                }
+            });
         }
+    }
 
-        if let Err(e) = matcher.train() {
-            tracing::error!("Matcher train failed: {}", e);
-            return ScanResult {
-                card: None,
-                confidence: 0.0,
-                global_total_scans: 0,
-            };
-        }
+    result.map(|mut result| {
+        result.scan_id = scan_id;
+        Json(result)
+    })
+}
 
-        let mut best_card: Option<Card> = None;
-        let mut max_good_matches = 0;
-
-        // Low Match Count threshold (depends on feature count).
-        // AKAZE typically extracts 100-1000 features.
-        // Let's set a minimum threshold.
-        const MIN_GOOD_MATCHES: usize = 50;
-        let ratio_thresh = 0.75;
-
-        let mut matches = Vector::<Vector<DMatch>>::new();
-        if matcher
-            .knn_match(&query_mat, &mut matches, 2, &Mat::default(), false)
-            .is_err()
-        {
-            tracing::error!("knn_match failed");
-            return ScanResult {
-                card: None,
-                confidence: 0.0,
-                global_total_scans: 0,
-            };
-        }
+/// Address of the client. Behind one of `trusted_proxies` it comes from the headers the
+/// proxy sets: `CF-Connecting-IP`, or else the last `X-Forwarded-For` hop that isn't a
+/// trusted proxy (earlier ones are whatever the client sent).
+fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[IpAddr]) -> String {
+    let peer = peer.ip();
+    if !trusted_proxies.contains(&peer) {
+        return peer.to_string();
+    }
+    let header_value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    header_value("cf-connecting-ip")
+        .and_then(|v| v.trim().parse::<IpAddr>().ok())
+        .or_else(|| {
+            header_value("x-forwarded-for")?
+                .rsplit(',')
+                .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+                .find(|ip| !trusted_proxies.contains(ip))
+        })
+        .unwrap_or(peer)
+        .to_string()
+}
 
-        let mut votes = std::collections::HashMap::new();
+async fn identify_upload(
+    state: &AppState,
+    body: Bytes,
+    scan: &mut scans::NewScan,
+) -> Result<ScanResult, IdentifyError> {
+    tracing::info!("Received identification request ({} bytes)", body.len());
 
-        for m in matches {
-            let m = m.to_vec();
-            if let [m0, m1, ..] = m.as_slice() {
-                if m0.distance < ratio_thresh * m1.distance {
-                    let img_idx = m0.img_idx as usize;
-                    *votes.entry(img_idx).or_insert(0) += 1;
-                }
-            }
-        }
+    if body.is_empty() {
+        return Err(IdentifyError::EmptyBody);
+    }
 
-        for (card_idx, vote_count) in votes {
-            if vote_count > max_good_matches {
-                max_good_matches = vote_count;
-                best_card = global_index.cards.get(card_idx).cloned();
-            }
-        }
+    // Held for the duration of matching; index updates wait for in-flight scans.
+    let identifier = state.identifier.clone().read_owned().await;
+    if identifier.is_empty() {
+        return Err(IdentifyError::IndexUnavailable);
+    }
 
-        if let Some(card) = best_card {
-            if max_good_matches >= MIN_GOOD_MATCHES {
-                // Primitive confidence: cap at 100 matches?
-                let confidence = (max_good_matches as f64 / 100.0).min(1.0);
-                tracing::info!(
-                    "Match found: {} ({} good matches)",
-                    card.name, max_good_matches
-                );
-                ScanResult {
-                    card: Some(card),
-                    confidence,
-                    global_total_scans: 0,
-                }
-            } else {
-                tracing::info!(
-                    "Best match {} had only {} good matches. Below threshold.",
-                    card.name, max_good_matches
-                );
-                ScanResult {
-                    card: None,
-                    confidence: 0.0,
-                    global_total_scans: 0,
-                }
-            }
-        } else {
-            tracing::info!("No match found.");
-            ScanResult {
-                card: None,
-                confidence: 0.0,
-                global_total_scans: 0,
-            }
-        }
+    let (scan_result, ranking, candidates, confidence) = tokio::task::spawn_blocking(move || {
+        let raw_img = identify::decode_image(&body)?;
+        let (result, ranking) = identifier.identify_ranked(&raw_img)?;
+        let candidates = identifier.top_candidates(&ranking, 5);
+        let confidence = identifier.confidence(&ranking);
+        Ok::<_, IdentifyError>((result, ranking, candidates, confidence))
     })
     .await
-    .unwrap_or_else(|e| {
-        eprintln!("Blocking task panicked: {}", e);
-        ScanResult {
-            card: None,
-            confidence: 0.0,
-            global_total_scans: 0,
-        }
-    });
+    .map_err(|e| IdentifyError::Internal(e.to_string()))??;
+
+    scan.candidates = candidates;
+    scan.confidence = confidence;
+    scan.extract_ms = Some(ranking.extract_time.as_secs_f64() * 1000.0);
+    scan.match_ms = Some(ranking.match_time.as_secs_f64() * 1000.0);
 
     let mut final_result = scan_result;
 
@@ -374,7 +421,320 @@ async fn identify_card(State(state): State<AppState>, body: Bytes) -> Json<ScanR
         final_result.global_total_scans = row.get::<i64, _>("value") as u64;
     }
 
-    Json(final_result)
+    Ok(final_result)
+}
+
+/// The card a scan actually showed, by id or by set code and collector number.
+#[derive(Deserialize)]
+struct LabelRequest {
+    card_id: Option<String>,
+    set_code: Option<String>,
+    card_number: Option<u32>,
+}
+
+/// Records the user's correction of a scan, pending an admin's approval, and returns the
+/// card it was labeled with.
+async fn label_scan(
+    State(state): State<AppState>,
+    Path(scan_id): Path<i64>,
+    Json(request): Json<LabelRequest>,
+) -> Result<Json<Card>, (StatusCode, Json<serde_json::Value>)> {
+    let error =
+        |status: StatusCode, message: &str| (status, Json(serde_json::json!({ "error": message })));
+    let internal = |e: sqlx::Error| {
+        tracing::error!("Failed to label scan {}: {}", scan_id, e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+    };
+
+    let card = match (&request.card_id, &request.set_code, request.card_number) {
+        (Some(id), _, _) => index::fetch_card(&state.pool, id).await,
+        (None, Some(set_code), Some(number)) => {
+            index::fetch_card_by_number(&state.pool, set_code, number).await
+        }
+        _ => {
+            return Err(error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "expected card_id, or set_code and card_number",
+            ))
+        }
+    }
+    .map_err(internal)?
+    .ok_or_else(|| error(StatusCode::NOT_FOUND, "unknown card"))?;
+
+    let scan = scans::fetch_scan(&state.pool, scan_id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "unknown scan"))?;
+    // Approved labels feed the index and datasets, so they can't be changed anonymously
+    if scan.label_approved_at.is_some()
+        || !scans::set_label(&state.pool, scan_id, &card.id)
+            .await
+            .map_err(internal)?
+    {
+        return Err(error(
+            StatusCode::CONFLICT,
+            "the label was already approved",
+        ));
+    }
+    tracing::info!("Scan {} labeled as {}, pending approval", scan_id, card.id);
+    metrics::counter!("inkwell_scan_labels_total").increment(1);
+
+    Ok(Json(card))
+}
+
+#[derive(Deserialize)]
+struct CardSearchQuery {
+    /// Words to find in the name or subtitle
+    q: Option<String>,
+    set: Option<String>,
+    rarity: Option<String>,
+    /// Promo grouping, e.g. "P3"
+    promo: Option<String>,
+    number: Option<u32>,
+    /// Rank `q` as typeahead does, forgiving typos
+    #[serde(default)]
+    fuzzy: bool,
+    #[serde(default = "default_card_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+}
+
+fn default_card_limit() -> i64 {
+    50
+}
+
+/// Browses the catalog: `{"total", "limit", "offset", "cards": [...]}`.
+async fn search_cards(
+    State(state): State<AppState>,
+    Query(query): Query<CardSearchQuery>,
+) -> Result<Json<serde_json::Value>, JsonRejection> {
+    let limit = query.limit.clamp(1, 200);
+    let offset = query.offset.max(0);
+    let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+    let filter = index::CardFilter {
+        text: non_empty(query.q),
+        set_code: non_empty(query.set),
+        rarity: non_empty(query.rarity),
+        promo_grouping: non_empty(query.promo),
+        card_number: query.number,
+        fuzzy: query.fuzzy,
+    };
+    let (cards, total) = index::search_cards(&state.pool, &filter, limit, offset)
+        .await
+        .map_err(|e| database_error("Failed to search cards", e))?;
+    Ok(Json(serde_json::json!({
+        "total": total,
+        "limit": limit,
+        "offset": offset,
+        "cards": cards,
+    })))
+}
+
+async fn get_card(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<index::CardDetail>, JsonRejection> {
+    index::fetch_card_detail(&state.pool, &id)
+        .await
+        .map_err(|e| database_error("Failed to fetch card", e))?
+        .map(Json)
+        .ok_or_else(|| json_error(StatusCode::NOT_FOUND, "no such card"))
+}
+
+type JsonRejection = (StatusCode, Json<serde_json::Value>);
+
+fn json_error(status: StatusCode, message: &str) -> JsonRejection {
+    (status, Json(serde_json::json!({ "error": message })))
+}
+
+/// Logs a database error and hides it behind a generic 500.
+fn database_error(context: &str, e: sqlx::Error) -> JsonRejection {
+    tracing::error!("{}: {}", context, e);
+    json_error(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+}
+
+/// Parses an exported CSV and resolves its rows to catalog cards.
+async fn parse_and_resolve(state: &AppState, body: &str) -> Result<ImportResult, JsonRejection> {
+    let parsed = import::parse_csv(body)
+        .map_err(|e| json_error(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()))?;
+    collection::resolve(&state.pool, parsed)
+        .await
+        .map_err(|e| database_error("Failed to resolve import", e))
+}
+
+/// Resolves an exported CSV for the client, which merges the cards into its session.
+async fn resolve_import(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<ImportResult>, JsonRejection> {
+    let result = parse_and_resolve(&state, &body).await?;
+    tracing::info!(
+        "Resolved import: {} rows, {} unresolved",
+        result.items.len(),
+        result.unresolved.len()
+    );
+    Ok(Json(result))
+}
+
+/// Merges an exported CSV into the server-side collection.
+async fn import_collection(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<serde_json::Value>, JsonRejection> {
+    let result = parse_and_resolve(&state, &body).await?;
+    let copies = collection::merge(&state.pool, &result.items)
+        .await
+        .map_err(|e| database_error("Failed to import into the collection", e))?;
+    tracing::info!(
+        "Imported {} copies into the collection, {} rows unresolved",
+        copies,
+        result.unresolved.len()
+    );
+    Ok(Json(serde_json::json!({
+        "format": result.format,
+        "rows": result.items.len(),
+        "copies": copies,
+        "unresolved": result.unresolved,
+    })))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// One of [`ExportFormat::NAMES`]; defaults to "standard"
+    format: Option<String>,
+    /// Column spec for the "template" format
+    template: Option<String>,
+}
+
+impl ExportQuery {
+    fn format(&self) -> Result<ExportFormat, JsonRejection> {
+        ExportFormat::from_name(
+            self.format.as_deref().unwrap_or("standard"),
+            self.template.as_deref(),
+        )
+        .map_err(|e| json_error(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()))
+    }
+}
+
+/// Renders `items` as a download named `inkwell_<name>`.
+fn export_download(items: &[ExportItem], format: &ExportFormat, name: &str) -> Response {
+    let file_name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"inkwell_{}.{}\"",
+                    file_name,
+                    format.file_extension()
+                ),
+            ),
+        ],
+        export::export(items, format),
+    )
+        .into_response()
+}
+
+/// The server-side collection in any export format.
+async fn export_collection(
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, JsonRejection> {
+    let format = query.format()?;
+    let items = collection::export_items(&state.pool)
+        .await
+        .map_err(|e| database_error("Failed to export the collection", e))?;
+    Ok(export_download(&items, &format, "collection"))
+}
+
+async fn list_sessions(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<sessions::SessionSummary>>, JsonRejection> {
+    sessions::list(&state.pool)
+        .await
+        .map(Json)
+        .map_err(|e| database_error("Failed to list sessions", e))
+}
+
+/// Stores a session uploaded by the client, replacing any earlier copy.
+async fn save_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(session): Json<sessions::SessionUpload>,
+) -> Result<StatusCode, JsonRejection> {
+    sessions::save(&state.pool, &id, &session)
+        .await
+        .map_err(|e| database_error("Failed to save session", e))?;
+    tracing::debug!("Saved session {} ({} items)", id, session.items.len());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, JsonRejection> {
+    match sessions::delete(&state.pool, &id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(json_error(StatusCode::NOT_FOUND, "no such session")),
+        Err(e) => Err(database_error("Failed to delete session", e)),
+    }
+}
+
+/// A synced session in any export format.
+async fn export_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, JsonRejection> {
+    let format = query.format()?;
+    let (name, items) = sessions::export_items(&state.pool, &id)
+        .await
+        .map_err(|e| database_error("Failed to export session", e))?
+        .ok_or_else(|| json_error(StatusCode::NOT_FOUND, "no such session"))?;
+    Ok(export_download(&items, &format, &name))
+}
+
+/// The compact index for on-device identification; revalidated via ETag.
+async fn get_compact_index(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let (bytes, etag) = state
+        .compact_index
+        .get(&state.pool, state.config.matching.working_size)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to export compact index: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == etag);
+    let cache_headers = [
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    Ok((
+        cache_headers,
+        [(header::CONTENT_TYPE, "application/octet-stream")],
+        bytes,
+    )
+        .into_response())
 }
 
 async fn get_stats(State(state): State<AppState>) -> Json<serde_json::Value> {
@@ -390,3 +750,33 @@ async fn get_stats(State(state): State<AppState>) -> Json<serde_json::Value> {
         "total_scanned_cards": total
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip_trusts_headers_only_from_proxies() {
+        let headers = HeaderMap::from_iter([
+            (
+                "cf-connecting-ip".parse().unwrap(),
+                "203.0.113.7".parse().unwrap(),
+            ),
+            (
+                "x-forwarded-for".parse().unwrap(),
+                "10.9.9.9, 198.51.100.2, 127.0.0.1".parse().unwrap(),
+            ),
+        ]);
+        let proxy: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let trusted = [proxy.ip()];
+
+        let client: SocketAddr = "192.0.2.1:5000".parse().unwrap();
+        assert_eq!(client_ip(&headers, client, &trusted), "192.0.2.1");
+        assert_eq!(client_ip(&headers, proxy, &[]), "127.0.0.1");
+        assert_eq!(client_ip(&headers, proxy, &trusted), "203.0.113.7");
+
+        let mut forwarded = headers.clone();
+        forwarded.remove("cf-connecting-ip");
+        assert_eq!(client_ip(&forwarded, proxy, &trusted), "198.51.100.2");
+    }
+}
@@ -0,0 +1,53 @@
+//! The compact index clients download to identify cards on-device (see
+//! [`inkwell_core::compact`]), built from the pure-Rust descriptors ingestion stores in
+//! `cards.orb_data`.
+
+use axum::body::Bytes;
+use inkwell_server::index;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Encoded compact index of every card with descriptors, plus an ETag for it.
+pub async fn export(
+    pool: &Pool<Sqlite>,
+    working_size: u32,
+) -> Result<(Bytes, String), sqlx::Error> {
+    let bytes = index::load_compact_index(pool, working_size)
+        .await?
+        .encode();
+    let etag = format!("\"{}\"", hex::encode(&Sha256::digest(&bytes)[..16]));
+    Ok((bytes.into(), etag))
+}
+
+/// The last [`export`], kept until ingestion or an index reload may have changed the cards
+/// it was built from; building it reads every card's descriptors.
+#[derive(Clone, Default)]
+pub struct CompactCache {
+    // Held while building, so concurrent requests wait for one build instead of each
+    // starting their own, and an invalidation can't be overwritten by an older build
+    export: Arc<Mutex<Option<(Bytes, String)>>>,
+}
+
+impl CompactCache {
+    /// The cached export, building it first if there is none.
+    pub async fn get(
+        &self,
+        pool: &Pool<Sqlite>,
+        working_size: u32,
+    ) -> Result<(Bytes, String), sqlx::Error> {
+        let mut cached = self.export.lock().await;
+        if let Some(export) = cached.as_ref() {
+            return Ok(export.clone());
+        }
+        let fresh = export(pool, working_size).await?;
+        *cached = Some(fresh.clone());
+        Ok(fresh)
+    }
+
+    /// Drops the cached export; the next request builds a new one.
+    pub async fn invalidate(&self) {
+        self.export.lock().await.take();
+    }
+}
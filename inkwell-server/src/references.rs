@@ -0,0 +1,137 @@
+use inkwell_server::{
+    config::Config,
+    dataset::{collect_images, label_for},
+    features,
+    identify::{self, Identifier},
+    index,
+};
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite};
+use std::path::Path;
+use tokio::sync::RwLock;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Default `kind` of views imported from a folder.
+pub const DEFAULT_KIND: &str = "photo";
+
+/// Extracts the descriptors of a reference photo like those of the official renders.
+pub async fn extract_view(bytes: Vec<u8>, working_size: u32) -> Result<Vec<u8>, BoxError> {
+    tokio::task::spawn_blocking(move || -> Result<Vec<u8>, BoxError> {
+        let img = identify::decode_image(&bytes)?;
+        let descriptors = features::extract_reference(&img, working_size)?;
+        if descriptors.is_empty() {
+            return Err("no features found in image".into());
+        }
+        Ok(descriptors)
+    })
+    .await?
+}
+
+/// Stores a reference view of `card_id` and reloads the card's views into `identifier`.
+/// Returns the new reference id.
+pub async fn add_view(
+    pool: &Pool<Sqlite>,
+    identifier: &RwLock<Identifier>,
+    card_id: &str,
+    kind: &str,
+    source: Option<&str>,
+    scan_id: Option<i64>,
+    descriptors: &[u8],
+) -> Result<i64, BoxError> {
+    let id = index::insert_reference(pool, card_id, kind, source, scan_id, descriptors).await?;
+    refresh(pool, identifier, card_id).await?;
+    Ok(id)
+}
+
+/// Reloads a card's extra views from the database into the live index.
+pub async fn refresh(
+    pool: &Pool<Sqlite>,
+    identifier: &RwLock<Identifier>,
+    card_id: &str,
+) -> Result<(), BoxError> {
+    let views = index::fetch_references(pool, card_id).await?;
+    identifier.write().await.set_references(card_id, &views)?;
+    Ok(())
+}
+
+/// What [`import_dir`] did.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub added: usize,
+    /// Already imported from the same file
+    pub existing: usize,
+    /// Negative examples, unknown cards and images without features
+    pub skipped: usize,
+}
+
+/// Adds every labeled image under `dir` (see [`inkwell_server::dataset`]) as a reference
+/// view of its card. Files are recorded by their path relative to `dir`, so importing
+/// the same folder again only adds new images.
+pub async fn import_dir(
+    pool: &Pool<Sqlite>,
+    config: &Config,
+    dir: &Path,
+    kind: &str,
+) -> Result<ImportSummary, BoxError> {
+    let mut paths = Vec::new();
+    collect_images(dir, &mut paths)?;
+    paths.sort();
+    tracing::info!("Importing {} images from {}", paths.len(), dir.display());
+
+    let mut summary = ImportSummary::default();
+    for path in &paths {
+        let source = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+        let Some(card_id) = label_for(path)? else {
+            summary.skipped += 1;
+            continue;
+        };
+        if index::fetch_card(pool, &card_id).await?.is_none() {
+            tracing::warn!("{}: unknown card {}", source, card_id);
+            summary.skipped += 1;
+            continue;
+        }
+
+        let exists = sqlx::query("SELECT 1 FROM card_references WHERE card_id = ? AND source = ?")
+            .bind(&card_id)
+            .bind(&source)
+            .fetch_optional(pool)
+            .await?
+            .is_some();
+        if exists {
+            summary.existing += 1;
+            continue;
+        }
+
+        let bytes = tokio::fs::read(path).await?;
+        let descriptors = match extract_view(bytes, config.matching.working_size).await {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!("{}: {}", source, e);
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        index::insert_reference(pool, &card_id, kind, Some(&source), None, &descriptors).await?;
+        summary.added += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Number of extra reference views per kind, for the admin status.
+pub async fn count_by_kind(pool: &Pool<Sqlite>) -> Result<serde_json::Value, sqlx::Error> {
+    let rows = sqlx::query("SELECT kind, COUNT(*) AS views FROM card_references GROUP BY kind")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            (
+                row.get::<String, _>("kind"),
+                serde_json::Value::from(row.get::<i64, _>("views")),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into())
+}
@@ -0,0 +1,369 @@
+use inkwell_server::config::Config;
+use serde::Serialize;
+use sqlx::{sqlite::SqliteRow, Pool, Row, Sqlite};
+use std::path::Path;
+use std::time::Duration;
+
+/// How often the retention policy is applied.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// What gets logged about one identification request.
+#[derive(Debug, Default)]
+pub struct NewScan {
+    /// `match`, `no_match` or `error`
+    pub outcome: &'static str,
+    pub card_id: Option<String>,
+    pub confidence: f64,
+    /// Best candidates as (card id, votes)
+    pub candidates: Vec<(String, usize)>,
+    pub error_code: Option<&'static str>,
+    /// File extension of the detected upload format
+    pub image_format: Option<&'static str>,
+    pub extract_ms: Option<f64>,
+    pub match_ms: Option<f64>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CandidateJson<'a> {
+    card_id: &'a str,
+    votes: usize,
+}
+
+/// A row of `scans`, as exposed by the admin API.
+#[derive(Clone, Debug, Serialize)]
+pub struct Scan {
+    pub id: i64,
+    pub created_at: String,
+    pub outcome: String,
+    pub card_id: Option<String>,
+    pub confidence: f64,
+    pub candidates: serde_json::Value,
+    pub error_code: Option<String>,
+    pub image_format: Option<String>,
+    pub image_bytes: i64,
+    pub image_path: Option<String>,
+    pub extract_ms: Option<f64>,
+    pub match_ms: Option<f64>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// Card the user reported this scan actually showed
+    pub label_card_id: Option<String>,
+    pub labeled_at: Option<String>,
+    /// When an admin approved the label; unapproved labels aren't used for anything
+    pub label_approved_at: Option<String>,
+}
+
+const SCAN_COLUMNS: &str = "id, created_at, outcome, card_id, confidence, candidates, error_code, image_format, image_bytes, image_path, extract_ms, match_ms, client_ip, user_agent, label_card_id, labeled_at, label_approved_at";
+
+fn scan_from_row(row: &SqliteRow) -> Scan {
+    let candidates: String = row.get("candidates");
+    Scan {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        outcome: row.get("outcome"),
+        card_id: row.get("card_id"),
+        confidence: row.get("confidence"),
+        candidates: serde_json::from_str(&candidates).unwrap_or_default(),
+        error_code: row.get("error_code"),
+        image_format: row.get("image_format"),
+        image_bytes: row.get("image_bytes"),
+        image_path: row.get("image_path"),
+        extract_ms: row.get("extract_ms"),
+        match_ms: row.get("match_ms"),
+        client_ip: row.get("client_ip"),
+        user_agent: row.get("user_agent"),
+        label_card_id: row.get("label_card_id"),
+        labeled_at: row.get("labeled_at"),
+        label_approved_at: row.get("label_approved_at"),
+    }
+}
+
+/// Logs a scan of an upload of `image_bytes` bytes and returns its id.
+pub async fn record(
+    pool: &Pool<Sqlite>,
+    scan: &NewScan,
+    image_bytes: usize,
+) -> Result<i64, BoxError> {
+    let candidates: Vec<CandidateJson> = scan
+        .candidates
+        .iter()
+        .map(|(card_id, votes)| CandidateJson {
+            card_id,
+            votes: *votes,
+        })
+        .collect();
+
+    let id = sqlx::query(
+        r#"
+        INSERT INTO scans (outcome, card_id, confidence, candidates, error_code, image_format,
+                           image_bytes, extract_ms, match_ms, client_ip, user_agent)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(scan.outcome)
+    .bind(&scan.card_id)
+    .bind(scan.confidence)
+    .bind(serde_json::to_string(&candidates)?)
+    .bind(scan.error_code)
+    .bind(scan.image_format)
+    .bind(image_bytes as i64)
+    .bind(scan.extract_ms)
+    .bind(scan.match_ms)
+    .bind(&scan.client_ip)
+    .bind(&scan.user_agent)
+    .execute(pool)
+    .await?
+    .last_insert_rowid();
+
+    Ok(id)
+}
+
+/// Stores the uploaded image of scan `id` in `captures_dir` as `scan_<id>.<ext>`
+/// and links it from the log.
+pub async fn store_capture(
+    pool: &Pool<Sqlite>,
+    captures_dir: &str,
+    id: i64,
+    image_format: Option<&str>,
+    image: &[u8],
+) -> Result<(), BoxError> {
+    let file_name = format!("scan_{}.{}", id, image_format.unwrap_or("bin"));
+    tokio::fs::create_dir_all(captures_dir).await?;
+    tokio::fs::write(Path::new(captures_dir).join(&file_name), image).await?;
+    sqlx::query("UPDATE scans SET image_path = ? WHERE id = ?")
+        .bind(&file_name)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    tracing::debug!("Saved scan {} image as {}", id, file_name);
+    Ok(())
+}
+
+/// Records that scan `id` actually showed `card_id`, pending approval. Returns false if
+/// there is no such scan or its label has already been approved.
+pub async fn set_label(pool: &Pool<Sqlite>, id: i64, card_id: &str) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE scans SET label_card_id = ?, labeled_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND label_approved_at IS NULL",
+    )
+    .bind(card_id)
+    .bind(id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+/// Approves the label of scan `id`. Returns false if the scan has no label.
+pub async fn approve_label(pool: &Pool<Sqlite>, id: i64) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE scans SET label_approved_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND label_card_id IS NOT NULL",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+/// Drops the label of scan `id`, approved or not. Returns false if there is no such scan.
+pub async fn clear_label(pool: &Pool<Sqlite>, id: i64) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE scans SET label_card_id = NULL, labeled_at = NULL, label_approved_at = NULL
+         WHERE id = ?",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+pub async fn list_scans(
+    pool: &Pool<Sqlite>,
+    outcome: Option<&str>,
+    labeled: Option<bool>,
+    approved: Option<bool>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Scan>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM scans WHERE (?1 IS NULL OR outcome = ?1) AND (?2 IS NULL OR (label_card_id IS NOT NULL) = ?2) AND (?3 IS NULL OR (label_card_id IS NOT NULL AND (label_approved_at IS NOT NULL) = ?3)) ORDER BY id DESC LIMIT ?4 OFFSET ?5",
+        SCAN_COLUMNS
+    ))
+    .bind(outcome)
+    .bind(labeled)
+    .bind(approved)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(scan_from_row).collect())
+}
+
+pub async fn fetch_scan(pool: &Pool<Sqlite>, id: i64) -> Result<Option<Scan>, sqlx::Error> {
+    let row = sqlx::query(&format!("SELECT {} FROM scans WHERE id = ?", SCAN_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(scan_from_row))
+}
+
+/// What one pass of the retention policy removed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RetentionStats {
+    pub deleted_scans: usize,
+    pub deleted_images: usize,
+}
+
+/// Deletes scans older than `scans.retention_days`, then the oldest remaining
+/// captures until they fit in `scans.max_capture_mb`. Scans with an approved label
+/// and their captures are kept: they are the training set. Anyone can label a scan, so
+/// unapproved labels expire like any other scan.
+pub async fn apply_retention(
+    pool: &Pool<Sqlite>,
+    config: &Config,
+) -> Result<RetentionStats, BoxError> {
+    let captures_dir = config.paths.captured_images.as_deref();
+    let mut stats = RetentionStats::default();
+
+    if config.scans.retention_days > 0 {
+        let cutoff = format!("-{} days", config.scans.retention_days);
+        let expired =
+            sqlx::query("SELECT id, image_path FROM scans WHERE created_at < datetime('now', ?) AND label_approved_at IS NULL")
+                .bind(&cutoff)
+                .fetch_all(pool)
+                .await?;
+
+        for row in &expired {
+            let image_path: Option<String> = row.get("image_path");
+            if let (Some(dir), Some(file)) = (captures_dir, image_path) {
+                if remove_capture(dir, &file).await {
+                    stats.deleted_images += 1;
+                }
+            }
+        }
+        stats.deleted_scans = sqlx::query(
+            "DELETE FROM scans WHERE created_at < datetime('now', ?) AND label_approved_at IS NULL",
+        )
+        .bind(&cutoff)
+        .execute(pool)
+        .await?
+        .rows_affected() as usize;
+    }
+
+    let max_mb = config.scans.max_capture_mb;
+    if let Some(dir) = captures_dir.filter(|_| max_mb > 0) {
+        let limit = max_mb.saturating_mul(1024 * 1024) as i64;
+        let stored = sqlx::query(
+            "SELECT id, image_path, image_bytes FROM scans WHERE image_path IS NOT NULL AND label_approved_at IS NULL ORDER BY id",
+        )
+        .fetch_all(pool)
+        .await?;
+        let sizes: Vec<i64> = stored.iter().map(|row| row.get("image_bytes")).collect();
+
+        for row in &stored[..captures_to_evict(&sizes, limit)] {
+            let id: i64 = row.get("id");
+            let file: String = row.get("image_path");
+            if remove_capture(dir, &file).await {
+                stats.deleted_images += 1;
+            }
+            sqlx::query("UPDATE scans SET image_path = NULL WHERE id = ?")
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Number of captures, oldest first, to delete so the rest total at most `limit` bytes.
+fn captures_to_evict(sizes: &[i64], limit: i64) -> usize {
+    let mut total: i64 = sizes.iter().sum();
+    let mut evict = 0;
+    for size in sizes {
+        if total <= limit {
+            break;
+        }
+        total -= size;
+        evict += 1;
+    }
+    evict
+}
+
+/// Removes a stored capture; a file that's already gone counts as removed.
+async fn remove_capture(dir: &str, file: &str) -> bool {
+    match tokio::fs::remove_file(Path::new(dir).join(file)).await {
+        Ok(()) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(e) => {
+            tracing::warn!("Failed to delete capture {}: {}", file, e);
+            false
+        }
+    }
+}
+
+/// Applies the retention policy now and then every [`RETENTION_INTERVAL`].
+pub async fn run_retention(pool: Pool<Sqlite>, config: std::sync::Arc<Config>) {
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+    loop {
+        interval.tick().await;
+        match apply_retention(&pool, &config).await {
+            Ok(stats) if stats != RetentionStats::default() => tracing::info!(
+                "Scan retention removed {} scans and {} captures.",
+                stats.deleted_scans,
+                stats.deleted_images
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::error!("Scan retention failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures_to_evict_oldest_first() {
+        assert_eq!(captures_to_evict(&[10, 20, 30], 100), 0);
+        assert_eq!(captures_to_evict(&[10, 20, 30], 50), 1);
+        assert_eq!(captures_to_evict(&[10, 20, 30], 30), 2);
+        assert_eq!(captures_to_evict(&[10, 20, 30], 0), 3);
+        assert_eq!(captures_to_evict(&[], 0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_retention_keeps_only_approved_labels() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("../migrations").run(&pool).await.unwrap();
+
+        for approved in [false, true] {
+            let id = record(&pool, &NewScan::default(), 0).await.unwrap();
+            assert!(set_label(&pool, id, "1-1").await.unwrap());
+            if approved {
+                assert!(approve_label(&pool, id).await.unwrap());
+            }
+        }
+        sqlx::query("UPDATE scans SET created_at = datetime('now', '-60 days')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let stats = apply_retention(&pool, &Config::default()).await.unwrap();
+        assert_eq!(stats.deleted_scans, 1);
+        let left = list_scans(&pool, None, None, None, 10, 0).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert!(left[0].label_approved_at.is_some());
+    }
+}
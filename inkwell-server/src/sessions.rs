@@ -0,0 +1,123 @@
+//! Scanning sessions mirrored from the client, which keeps them in local storage and
+//! uploads each one whenever it is saved, so they can be listed and exported here.
+
+use crate::collection;
+use inkwell_core::export::ExportItem;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+
+/// A session as the client uploads it; replaces whatever was stored before.
+#[derive(Debug, Deserialize)]
+pub struct SessionUpload {
+    pub name: String,
+    pub created_at: String,
+    pub items: Vec<ExportItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Number of scanned cards, counting quantities
+    pub count: i64,
+}
+
+pub async fn save(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    session: &SessionUpload,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO sessions (id, name, created_at) VALUES (?, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET name = excluded.name, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(id)
+    .bind(&session.name)
+    .bind(&session.created_at)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM session_items WHERE session_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    for (position, item) in session.items.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO session_items
+                (session_id, position, card_id, is_foil, quantity, price, scanned_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(position as i64)
+        .bind(&item.card.id)
+        .bind(item.foil)
+        .bind(item.quantity)
+        .bind(&item.price)
+        .bind(&item.scanned_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+/// Returns whether the session existed.
+pub async fn delete(pool: &Pool<Sqlite>, id: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM session_items WHERE session_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    let deleted = sqlx::query("DELETE FROM sessions WHERE id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(deleted > 0)
+}
+
+/// Every session, most recently updated first.
+pub async fn list(pool: &Pool<Sqlite>) -> Result<Vec<SessionSummary>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT s.id, s.name, s.created_at, s.updated_at, COALESCE(SUM(i.quantity), 0) AS count
+         FROM sessions s LEFT JOIN session_items i ON i.session_id = s.id
+         GROUP BY s.id ORDER BY s.updated_at DESC, s.id",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| SessionSummary {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            count: row.get("count"),
+        })
+        .collect())
+}
+
+/// Name and items of a session, or `None` if there is no such session.
+pub async fn export_items(
+    pool: &Pool<Sqlite>,
+    id: &str,
+) -> Result<Option<(String, Vec<ExportItem>)>, sqlx::Error> {
+    let Some(name) = sqlx::query("SELECT name FROM sessions WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| row.get::<String, _>("name"))
+    else {
+        return Ok(None);
+    };
+    let rows = sqlx::query(
+        "SELECT card_id, is_foil, quantity, price, scanned_at
+         FROM session_items WHERE session_id = ? ORDER BY position",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+    Ok(Some((name, collection::with_cards(pool, &rows).await?)))
+}
@@ -0,0 +1,141 @@
+use crate::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use sqlx::{Pool, Row, Sqlite};
+use std::time::Instant;
+
+/// Histogram buckets for request, extraction and matching latencies.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the global Prometheus recorder. Metrics recorded before this (or in
+/// binaries that never call it, like `verify`) are discarded.
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)?
+        .install_recorder()?;
+
+    describe_counter!(
+        "inkwell_http_requests_total",
+        "HTTP requests by method, route and status"
+    );
+    describe_histogram!(
+        "inkwell_http_request_duration_seconds",
+        "HTTP request latency by method and route"
+    );
+    describe_counter!(
+        "inkwell_identify_total",
+        "Identification requests by outcome (match, no_match, error)"
+    );
+    describe_counter!(
+        "inkwell_identify_errors_total",
+        "Failed identification requests by error code"
+    );
+    describe_counter!(
+        "inkwell_scan_labels_total",
+        "Scans corrected or confirmed by users"
+    );
+    describe_histogram!(
+        "inkwell_feature_extraction_duration_seconds",
+        "Time spent extracting AKAZE features from a query image"
+    );
+    describe_histogram!(
+        "inkwell_matching_duration_seconds",
+        "Time spent matching query descriptors against the index"
+    );
+    describe_gauge!(
+        "inkwell_index_cards",
+        "Cards currently in the in-memory index"
+    );
+    describe_gauge!(
+        "inkwell_index_views",
+        "Reference views (renders and extra views) in the in-memory index"
+    );
+    describe_gauge!("inkwell_db_size_bytes", "Size of the SQLite database");
+    describe_counter!(
+        "inkwell_ingestion_runs_total",
+        "Finished ingestion runs by status"
+    );
+    describe_gauge!(
+        "inkwell_ingestion_last_run_cards",
+        "Cards in the last finished ingestion run by result"
+    );
+    describe_gauge!(
+        "inkwell_ingestion_last_run_duration_seconds",
+        "Wall-clock duration of the last finished ingestion run"
+    );
+    describe_gauge!(
+        "inkwell_ingestion_last_run_timestamp_seconds",
+        "Unix time the last ingestion run finished"
+    );
+
+    Ok(handle)
+}
+
+/// Counts requests and records their latency, labeled by the matched route pattern
+/// (not the raw path, to keep label cardinality bounded).
+pub async fn track_http(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    counter!(
+        "inkwell_http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status
+    )
+    .increment(1);
+    histogram!(
+        "inkwell_http_request_duration_seconds",
+        "method" => method,
+        "route" => route
+    )
+    .record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// `GET /metrics` in the Prometheus text format. Gauges that are cheap to read
+/// are refreshed on each scrape.
+pub async fn render(State(state): State<AppState>) -> Response {
+    {
+        let identifier = state.identifier.read().await;
+        gauge!("inkwell_index_cards").set(identifier.len() as f64);
+        gauge!("inkwell_index_views").set(identifier.view_count() as f64);
+    }
+    match db_size(&state.pool).await {
+        Ok(bytes) => gauge!("inkwell_db_size_bytes").set(bytes as f64),
+        Err(e) => tracing::warn!("Failed to read database size: {}", e),
+    }
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+async fn db_size(pool: &Pool<Sqlite>) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.get("size"))
+}
@@ -0,0 +1,27 @@
+-- History of catalog ingestion runs
+CREATE TABLE IF NOT EXISTS ingestion_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- 'running', 'completed', 'failed' or 'interrupted'
+    status TEXT NOT NULL DEFAULT 'running',
+    started_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at DATETIME,
+    total_cards INTEGER NOT NULL DEFAULT 0,
+    new_cards INTEGER NOT NULL DEFAULT 0,
+    updated_cards INTEGER NOT NULL DEFAULT 0,
+    failed_cards INTEGER NOT NULL DEFAULT 0,
+    skipped_cards INTEGER NOT NULL DEFAULT 0,
+    -- Set when the run as a whole failed (e.g. the card list could not be fetched)
+    error TEXT
+);
+
+-- Per-card failures recorded during a run
+CREATE TABLE IF NOT EXISTS ingestion_errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id INTEGER NOT NULL,
+    card_id TEXT NOT NULL,
+    message TEXT NOT NULL,
+    occurred_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(run_id) REFERENCES ingestion_runs(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ingestion_errors_run ON ingestion_errors(run_id);
@@ -0,0 +1,6 @@
+-- Track where each card image came from and what it contained, so ingestion
+-- can revalidate it (ETag / Last-Modified) and notice when the content changes.
+ALTER TABLE cards ADD COLUMN image_source_url TEXT;
+ALTER TABLE cards ADD COLUMN image_hash TEXT;
+ALTER TABLE cards ADD COLUMN image_etag TEXT;
+ALTER TABLE cards ADD COLUMN image_last_modified TEXT;
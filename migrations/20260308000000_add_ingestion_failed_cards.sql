@@ -0,0 +1,8 @@
+-- Cards whose last ingestion attempt failed; retried first on the next run
+CREATE TABLE IF NOT EXISTS ingestion_failed_cards (
+    card_id TEXT PRIMARY KEY,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    last_error TEXT NOT NULL,
+    first_failed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_failed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,26 @@
+-- Audit log of identification requests
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- 'match', 'no_match' or 'error'
+    outcome TEXT NOT NULL,
+    -- Matched card, if any
+    card_id TEXT,
+    confidence REAL NOT NULL DEFAULT 0,
+    -- JSON array of the best candidates: [{"card_id": "...", "votes": 123}, ...]
+    candidates TEXT NOT NULL DEFAULT '[]',
+    -- IdentifyError code for failed requests
+    error_code TEXT,
+    -- Detected upload format (e.g. 'png'), NULL if it could not be recognized
+    image_format TEXT,
+    image_bytes INTEGER NOT NULL DEFAULT 0,
+    -- File name of the stored capture inside the captures directory; NULL when
+    -- captures are disabled or the file was removed by the retention policy
+    image_path TEXT,
+    extract_ms REAL,
+    match_ms REAL,
+    client_ip TEXT,
+    user_agent TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_scans_created_at ON scans(created_at);
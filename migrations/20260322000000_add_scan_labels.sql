@@ -0,0 +1,5 @@
+-- Corrections reported by users: the card a scan actually showed
+ALTER TABLE scans ADD COLUMN label_card_id TEXT;
+ALTER TABLE scans ADD COLUMN labeled_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_scans_label_card_id ON scans(label_card_id);
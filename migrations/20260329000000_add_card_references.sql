@@ -0,0 +1,16 @@
+-- Additional reference views per card (sleeved photos, user captures, ...). The
+-- official render stays in cards.akaze_data; votes from any view count for the card.
+CREATE TABLE IF NOT EXISTS card_references (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    card_id TEXT NOT NULL REFERENCES cards(id) ON DELETE CASCADE,
+    -- What the view shows: 'photo', 'sleeved', 'capture', ...
+    kind TEXT NOT NULL,
+    -- Where it came from, e.g. the imported file name
+    source TEXT,
+    -- Scan the view was taken from, for user-contributed captures
+    scan_id INTEGER REFERENCES scans(id) ON DELETE SET NULL,
+    akaze_data BLOB NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_card_references_card_id ON card_references(card_id);
@@ -0,0 +1,3 @@
+-- Pure-Rust (ORB-style) descriptors of the official render, exported in the compact
+-- index the client downloads for on-device identification. NULL until computed.
+ALTER TABLE cards ADD COLUMN orb_data BLOB;
@@ -0,0 +1,6 @@
+-- The collection table is filled by CSV imports that merge into it: one row per card
+-- and finish, with the quantity added up.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_collection_card_finish ON collection(card_id, is_foil);
+
+-- Unit price recorded with the imported row, if the file had one
+ALTER TABLE collection ADD COLUMN price TEXT;
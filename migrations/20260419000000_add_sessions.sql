@@ -0,0 +1,20 @@
+-- Scanning sessions mirrored from the client's local storage, so they can be exported
+-- from the server. Items are replaced wholesale whenever the client saves the session.
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS session_items (
+    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    -- Order of the item in the client's list
+    position INTEGER NOT NULL,
+    card_id TEXT NOT NULL,
+    is_foil BOOLEAN NOT NULL DEFAULT 0,
+    quantity INTEGER NOT NULL DEFAULT 1,
+    price TEXT,
+    scanned_at TEXT NOT NULL,
+    PRIMARY KEY (session_id, position)
+);
@@ -0,0 +1,3 @@
+-- Labels come from anonymous clients, so they only feed reference views and datasets
+-- once an admin has approved them
+ALTER TABLE scans ADD COLUMN label_approved_at DATETIME;
@@ -0,0 +1,8 @@
+-- How many augmented variants were generated for the card's current render; some may
+-- have yielded no features, so this can exceed its stored augmented views
+ALTER TABLE cards ADD COLUMN augment_variants INTEGER NOT NULL DEFAULT 0;
+
+-- Best guess for existing cards; any that dropped a variant are regenerated once
+UPDATE cards SET augment_variants = (
+    SELECT COUNT(*) FROM card_references r WHERE r.card_id = cards.id AND r.kind = 'augmented'
+);
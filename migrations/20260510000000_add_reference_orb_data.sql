@@ -0,0 +1,3 @@
+-- Pure-Rust descriptors of extra reference views, for servers built without OpenCV.
+-- Views added by such a server have an empty akaze_data; NULL here means none.
+ALTER TABLE card_references ADD COLUMN orb_data BLOB;